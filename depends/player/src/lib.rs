@@ -20,6 +20,8 @@ use std::{
 /// midiio module handle the midi output for playing
 pub mod midiio;
 
+use midiio::OutputProfile;
+
 /// this structure handle a plain note (with start and length)
 #[derive(Debug)]
 pub struct PlainNoteWithChannel {
@@ -30,10 +32,51 @@ pub struct PlainNoteWithChannel {
     pub length: Duration,
 }
 
-/// this structure provide additional informations on files (using in the gui to display duration and additional useful informations on the file)
+/// channel used by a file, with its instrument
 #[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChannelInformations {
+    /// channel number, from 0 (9 is the General Midi percussion channel)
+    pub channel: u8,
+    /// first program (instrument) selected on the channel, if any
+    pub program: Option<u8>,
+}
+
+impl ChannelInformations {
+    /// the channel is the General Midi percussion channel
+    pub fn is_percussion(&self) -> bool {
+        self.channel == 9
+    }
+}
+
+/// this structure provide additional informations on files (using in the gui to display duration and additional useful informations on the file)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct FileInformations {
     pub duration: Option<Duration>,
+    /// title of the file (track name or text meta event)
+    pub title: Option<String>,
+    /// channels having notes, sorted by channel number
+    pub channels: Vec<ChannelInformations>,
+    /// lowest and highest played notes
+    pub note_range: Option<(u8, u8)>,
+    /// maximum number of notes sounding at the same time
+    pub polyphony_peak: usize,
+    /// initial tempo, in microseconds per beat
+    pub tempo: Option<u32>,
+    /// initial time signature (numerator, denominator)
+    pub time_signature: Option<(u8, u8)>,
+    /// scale of the book files
+    pub scale_name: Option<String>,
+    /// number of tracks of the scale, for the book files
+    pub track_count: Option<usize>,
+}
+
+impl FileInformations {
+    /// initial tempo in beats per minute
+    pub fn bpm(&self) -> Option<f32> {
+        self.tempo
+            .filter(|t| *t > 0)
+            .map(|t| 60_000_000.0 / t as f32)
+    }
 }
 
 /// Factory for the player, with a command and responses to and from the player
@@ -63,10 +106,50 @@ pub struct NotesDisplayInformations {
     pub preferred_view_inversed: bool,
 }
 
+/// a syllable (or word) of the lyrics, with the time it is sung
+#[derive(Debug, Clone)]
+pub struct LyricSyllable {
+    pub start: Duration,
+    pub text: String,
+}
+
+/// a line of lyrics, displayed as a whole
+#[derive(Debug, Clone)]
+pub struct LyricLine {
+    pub start: Duration,
+    pub syllables: Vec<LyricSyllable>,
+}
+
+impl LyricLine {
+    /// full text of the line
+    pub fn text(&self) -> String {
+        self.syllables.iter().map(|s| s.text.as_str()).collect()
+    }
+}
+
+/// a named position in the file (from marker or cue point events)
+#[derive(Debug, Clone)]
+pub struct Section {
+    pub start: Duration,
+    pub name: String,
+}
+
 #[derive(Debug)]
 pub struct NotesInformations {
     pub notes: Arc<Vec<PlainNoteWithChannel>>,
     pub display_informations: NotesDisplayInformations,
+    /// lyrics of the file (karaoke), ordered by time
+    pub lyrics: Arc<Vec<LyricLine>>,
+    /// sections of the file, ordered by time
+    pub sections: Arc<Vec<Section>>,
+}
+
+impl NotesInformations {
+    /// index of the lyric line sung at the given time
+    pub fn lyric_line_at(&self, time: Duration) -> Option<usize> {
+        let next = self.lyrics.partition_point(|l| l.start <= time);
+        next.checked_sub(1)
+    }
 }
 
 impl Default for NotesInformations {
@@ -80,6 +163,32 @@ impl Default for NotesInformations {
                 track_width: 1.0,
                 preferred_view_inversed: true,
             },
+            lyrics: Arc::new(vec![]),
+            sections: Arc::new(vec![]),
+        }
+    }
+}
+
+/// settings of a play, adjusting the play of the file
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlaySettings {
+    /// speed factor, 1.0 is the file tempo
+    pub speed: f32,
+    /// transposition of the notes, in semitones (the percussion channel is not transposed)
+    pub transpose: i8,
+    /// position in the file where the play starts
+    pub start_offset: Option<Duration>,
+    /// position in the file where the play ends
+    pub end_offset: Option<Duration>,
+}
+
+impl Default for PlaySettings {
+    fn default() -> Self {
+        PlaySettings {
+            speed: 1.0,
+            transpose: 0,
+            start_offset: None,
+            end_offset: None,
         }
     }
 }
@@ -97,9 +206,36 @@ pub trait Player: Send {
         start_time: Option<f32>,
     ) -> Result<(), Box<dyn Error>>;
 
+    /// Start playing a file, with specific play settings,
+    /// players that does not support the settings play the file as is
+    fn start_play_with_settings(
+        &mut self,
+        filename: &PathBuf,
+        start_time: Option<f32>,
+        _settings: &PlaySettings,
+    ) -> Result<(), Box<dyn Error>> {
+        self.start_play(filename, start_time)
+    }
+
     /// Stop the current play
     fn stop(&mut self);
 
+    /// Change the master volume (between 0 and 1),
+    /// players without volume control ignore it
+    fn set_volume(&mut self, _volume: f32) {}
+
+    /// Strike again the held notes when resuming from pause,
+    /// players without this option ignore it
+    fn set_restrike_on_resume(&mut self, _restrike: bool) {}
+
+    /// Change the fade out time when the play is stopped, the notes are cut if zero,
+    /// players without fade ignore it
+    fn set_fade_out(&mut self, _fade_out: Duration) {}
+
+    /// Change the device specific output options, used from the next play,
+    /// players without output options ignore it
+    fn set_output_profile(&mut self, _profile: OutputProfile) {}
+
     // is in pause ?
     fn is_paused(&self) -> bool;
 
@@ -138,12 +274,20 @@ pub enum Response {
 /// commands that can be sent to the player
 #[derive(Debug)]
 pub enum Command {
+    /// restart the play of the current file, from the beginning of the file
     Replay,
     Silence,
     Reset,
     Solo,
     Info,
+    /// toggle the pause, the sounding notes are released while paused
     Pause,
-    /// Changes the speed by the value given.
+    /// Changes the speed factor of the play (1.0 is the file tempo)
     Speed(f32),
+    /// move the play to the given play time (including the start wait)
+    Seek(Duration),
+    /// loop the play between the two play times (including the start wait)
+    SetLoop(Duration, Duration),
+    /// stop looping, the play continue to the end of the file
+    ClearLoop,
 }
@@ -0,0 +1,114 @@
+//! timer used for the play, depending on the timing of the file header,
+//! metrical (ticks per beat, tempo dependent) or SMPTE timecode (fixed tick duration)
+
+use std::{error::Error, time::Duration};
+
+use nodi::{
+    midly::{Format, Timing, TrackEvent},
+    timers::Ticker,
+    Event, Moment, Sheet, Timer,
+};
+
+use super::DEFAULT_TEMPO_IF_NOT_SET_IN_FILE;
+
+/// timer for SMPTE timecode files, the tick duration does not depend on the tempo
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimecodeTimer {
+    micros_per_tick: f64,
+}
+
+impl TimecodeTimer {
+    pub fn new(frames_per_second: f32, ticks_per_frame: u8) -> Self {
+        TimecodeTimer {
+            micros_per_tick: 1_000_000.0
+                / (frames_per_second as f64 * ticks_per_frame.max(1) as f64),
+        }
+    }
+}
+
+impl Timer for TimecodeTimer {
+    fn sleep_duration(&mut self, n_ticks: u32) -> Duration {
+        Duration::from_micros((self.micros_per_tick * n_ticks as f64) as u64)
+    }
+
+    /// tempo has no effect on timecode files
+    fn change_tempo(&mut self, _tempo: u32) {}
+}
+
+/// timer associated to a file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FileTimer {
+    Metrical(Ticker),
+    Timecode(TimecodeTimer),
+}
+
+impl TryFrom<Timing> for FileTimer {
+    type Error = Box<dyn Error>;
+
+    fn try_from(timing: Timing) -> Result<Self, Self::Error> {
+        Ok(match timing {
+            Timing::Metrical(_) => {
+                let mut ticker = Ticker::try_from(timing)?;
+                ticker.change_tempo(DEFAULT_TEMPO_IF_NOT_SET_IN_FILE);
+                FileTimer::Metrical(ticker)
+            }
+            Timing::Timecode(fps, ticks_per_frame) => {
+                FileTimer::Timecode(TimecodeTimer::new(fps.as_f32(), ticks_per_frame))
+            }
+        })
+    }
+}
+
+impl Timer for FileTimer {
+    fn sleep_duration(&mut self, n_ticks: u32) -> Duration {
+        match self {
+            FileTimer::Metrical(t) => t.sleep_duration(n_ticks),
+            FileTimer::Timecode(t) => t.sleep_duration(n_ticks),
+        }
+    }
+
+    fn change_tempo(&mut self, tempo: u32) {
+        match self {
+            FileTimer::Metrical(t) => t.change_tempo(tempo),
+            FileTimer::Timecode(t) => t.change_tempo(tempo),
+        }
+    }
+}
+
+/// create the sheet to play, depending on the file format,
+/// the sequences of format 2 files are played one after the other,
+/// each one starting with the default tempo
+pub fn create_sheet(format: Format, tracks: &[Vec<TrackEvent>]) -> Sheet {
+    match format {
+        Format::SingleTrack => Sheet::sequential(tracks),
+        Format::Parallel => Sheet::parallel(tracks),
+        Format::Sequential => tracks
+            .iter()
+            .flat_map(|track| {
+                let mut sequence = Sheet::single(track).into_inner();
+                if sequence.is_empty() {
+                    sequence.push(Moment::default());
+                }
+                sequence[0]
+                    .events
+                    .insert(0, Event::Tempo(DEFAULT_TEMPO_IF_NOT_SET_IN_FILE));
+                sequence
+            })
+            .collect(),
+    }
+}
+
+#[test]
+pub fn test_timecode_timer() {
+    use nodi::midly::Fps;
+
+    let mut timer = FileTimer::try_from(Timing::Timecode(Fps::Fps25, 40)).unwrap();
+    // 25 * 40 ticks per second
+    assert_eq!(timer.sleep_duration(1000), Duration::from_secs(1));
+    timer.change_tempo(1_000);
+    assert_eq!(timer.sleep_duration(1000), Duration::from_secs(1));
+
+    let mut timer = FileTimer::try_from(Timing::Metrical(480.into())).unwrap();
+    // 120 bpm by default
+    assert_eq!(timer.sleep_duration(480), Duration::from_millis(500));
+}
@@ -0,0 +1,235 @@
+//! file formats readers, each supported file format is handled by a reader
+//! registered in the [FileFormatRegistry], used by the player and the file browser
+
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufReader, Read},
+    path::Path,
+    sync::Arc,
+    sync::OnceLock,
+};
+
+use log::debug;
+use nodi::{midly::Smf, Sheet};
+
+use crate::{FileInformations, NotesInformations};
+
+use super::{
+    convert,
+    file_timer::FileTimer,
+    informations::{polyphony_peak, smf_informations},
+    read_book_file, read_midi_file, resolve_conversion,
+};
+
+/// number of bytes read at the beginning of the file, for the content detection
+const SNIFF_LENGTH: usize = 16;
+
+/// result of a file reading, notes for display, and the midi events for the play
+pub type ReadResult = (Arc<NotesInformations>, FileTimer, Sheet);
+
+/// Reader of a file format
+pub trait FileFormatReader: Send + Sync {
+    /// name of the format, for logs
+    fn name(&self) -> &str;
+
+    /// handled extensions, lower case and without the dot
+    fn extensions(&self) -> &[&str];
+
+    /// check from the first bytes of the file, if the content is handled by the reader
+    fn sniff(&self, header: &[u8]) -> bool;
+
+    /// read the file, for display and play
+    fn read(&self, filename: &Path, start_wait: Option<f32>) -> Result<ReadResult, Box<dyn Error>>;
+
+    /// compute the additional information on the file
+    fn compute_informations(&self, filename: &Path) -> Result<FileInformations, Box<dyn Error>> {
+        let (notes_informations, _, _) = self.read(filename, None)?;
+        let duration = notes_informations
+            .notes
+            .iter()
+            .fold(std::time::Duration::ZERO, |acc, n| {
+                acc.max(n.start + n.length)
+            });
+
+        let notes = &notes_informations.notes;
+        Ok(FileInformations {
+            duration: Some(duration),
+            note_range: notes
+                .iter()
+                .map(|n| n.note)
+                .min()
+                .zip(notes.iter().map(|n| n.note).max()),
+            polyphony_peak: polyphony_peak(notes),
+            ..Default::default()
+        })
+    }
+}
+
+/// standard midi files, karaoke files (midi files with lyrics),
+/// and RIFF midi files (unwrapped by the midi parser)
+pub struct MidiFileReader {}
+
+impl FileFormatReader for MidiFileReader {
+    fn name(&self) -> &str {
+        "midi"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["mid", "midi", "kar", "rmi"]
+    }
+
+    fn sniff(&self, header: &[u8]) -> bool {
+        header.starts_with(b"MThd")
+            || (header.starts_with(b"RIFF") && header.get(8..12) == Some(b"RMID".as_slice()))
+    }
+
+    fn read(&self, filename: &Path, start_wait: Option<f32>) -> Result<ReadResult, Box<dyn Error>> {
+        read_midi_file(filename, start_wait)
+    }
+
+    fn compute_informations(&self, filename: &Path) -> Result<FileInformations, Box<dyn Error>> {
+        let file_content_data = std::fs::read(filename)?;
+        let smf = Smf::parse(&file_content_data)?;
+        smf_informations(&smf)
+    }
+}
+
+/// virtual book files, converted to midi using the scale conversion
+pub struct BookFileReader {}
+
+impl FileFormatReader for BookFileReader {
+    fn name(&self) -> &str {
+        "book"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["book"]
+    }
+
+    fn sniff(&self, _header: &[u8]) -> bool {
+        // book files have no distinctive header, only the extension is used
+        false
+    }
+
+    fn read(&self, filename: &Path, start_wait: Option<f32>) -> Result<ReadResult, Box<dyn Error>> {
+        read_book_file(filename, start_wait)
+    }
+
+    fn compute_informations(&self, filename: &Path) -> Result<FileInformations, Box<dyn Error>> {
+        let file = File::open(filename)?;
+        let vb = bookparsing::read_book_stream(&mut BufReader::new(&file))?;
+        let conversion = resolve_conversion(&vb)?
+            .ok_or_else(|| format!("no conversion found for {}", filename.to_string_lossy()))?;
+
+        // the informations are computed on the converted midi content
+        let smf = convert(&vb, &conversion)?;
+        let mut informations = smf_informations(&smf)?;
+        informations.scale_name = Some(vb.scale.name.clone());
+        informations.track_count = Some(vb.scale.definition.tracks.tracks.len());
+        Ok(informations)
+    }
+}
+
+/// list of the known file formats
+pub struct FileFormatRegistry {
+    readers: Vec<Box<dyn FileFormatReader>>,
+}
+
+impl Default for FileFormatRegistry {
+    fn default() -> Self {
+        let mut registry = FileFormatRegistry::new();
+        registry.register(Box::new(MidiFileReader {}));
+        registry.register(Box::new(BookFileReader {}));
+        registry
+    }
+}
+
+impl FileFormatRegistry {
+    /// create an empty registry
+    pub fn new() -> Self {
+        FileFormatRegistry { readers: vec![] }
+    }
+
+    /// add a reader, the first registered readers have priority
+    pub fn register(&mut self, reader: Box<dyn FileFormatReader>) {
+        self.readers.push(reader);
+    }
+
+    /// all the handled extensions, lower case and without the dot
+    pub fn extensions(&self) -> Vec<String> {
+        self.readers
+            .iter()
+            .flat_map(|r| r.extensions().iter().map(|e| e.to_string()))
+            .collect()
+    }
+
+    /// extension filters for the file browser (".mid", ".book" ...)
+    pub fn extensions_filters(&self) -> Vec<String> {
+        self.extensions()
+            .iter()
+            .map(|e| format!(".{}", e))
+            .collect()
+    }
+
+    /// find the reader associated to the extension of the file
+    pub fn find_by_extension(&self, filename: &Path) -> Option<&dyn FileFormatReader> {
+        let ext = filename.extension()?.to_ascii_lowercase();
+        let ext = ext.to_string_lossy();
+        self.readers
+            .iter()
+            .find(|r| r.extensions().iter().any(|e| *e == ext))
+            .map(|r| r.as_ref())
+    }
+
+    /// find the reader for the file, using the extension,
+    /// or the file content if the extension is not known
+    pub fn find(&self, filename: &Path) -> Option<&dyn FileFormatReader> {
+        if let Some(reader) = self.find_by_extension(filename) {
+            return Some(reader);
+        }
+
+        let mut header = Vec::with_capacity(SNIFF_LENGTH);
+        let mut file = File::open(filename).ok()?;
+        file.by_ref()
+            .take(SNIFF_LENGTH as u64)
+            .read_to_end(&mut header)
+            .ok()?;
+
+        let found = self
+            .readers
+            .iter()
+            .find(|r| r.sniff(&header))
+            .map(|r| r.as_ref());
+        if let Some(reader) = found {
+            debug!("{:?} detected as {} file", filename, reader.name());
+        }
+        found
+    }
+}
+
+/// registry with the formats supported by the player
+pub fn registry() -> &'static FileFormatRegistry {
+    static REGISTRY: OnceLock<FileFormatRegistry> = OnceLock::new();
+    REGISTRY.get_or_init(FileFormatRegistry::default)
+}
+
+#[test]
+pub fn test_registry_extensions() {
+    use std::path::PathBuf;
+
+    let registry = FileFormatRegistry::default();
+    let filters = registry.extensions_filters();
+    assert!(filters.contains(&".mid".to_string()));
+    assert!(filters.contains(&".book".to_string()));
+
+    let reader = registry.find_by_extension(&PathBuf::from("test.MID"));
+    assert_eq!(reader.map(|r| r.name()), Some("midi"));
+    assert!(registry
+        .find_by_extension(&PathBuf::from("test.txt"))
+        .is_none());
+
+    assert!(MidiFileReader {}.sniff(b"MThd\x00\x00\x00\x06"));
+    assert!(MidiFileReader {}.sniff(b"RIFF\x00\x01\x00\x00RMIDdata"));
+    assert!(!MidiFileReader {}.sniff(b"RIFF\x00\x01\x00\x00WAVEfmt "));
+}
@@ -0,0 +1,203 @@
+//! analysis of the midi content, for the additional informations displayed on the files
+//! (title, instruments, note range, tempo ..)
+
+use std::{collections::BTreeMap, error::Error, time::Duration};
+
+use nodi::midly::{MetaMessage, MidiMessage, Smf, TrackEventKind};
+
+use crate::{ChannelInformations, FileInformations, PlainNoteWithChannel};
+
+use super::{meta_events, to_notes};
+
+/// maximum number of notes sounding at the same time
+pub(crate) fn polyphony_peak(notes: &[PlainNoteWithChannel]) -> usize {
+    // note ends are sorted before the starts at the same time,
+    // a note following another one does not count as a chord
+    let mut changes: Vec<(Duration, i32)> = notes
+        .iter()
+        .flat_map(|n| [(n.start, 1), (n.start + n.length, -1)])
+        .collect();
+    changes.sort();
+
+    let mut sounding = 0_i32;
+    let mut peak = 0_i32;
+    for (_, change) in changes {
+        sounding += change;
+        peak = peak.max(sounding);
+    }
+    peak as usize
+}
+
+/// title of the file, the first track name (or text) of the first track
+fn title(smf: &Smf) -> Option<String> {
+    let first_track = smf.tracks.first()?;
+    let texts: Vec<(bool, String)> = first_track
+        .iter()
+        .filter_map(|e| match e.kind {
+            TrackEventKind::Meta(MetaMessage::TrackName(t)) => Some((true, t)),
+            TrackEventKind::Meta(MetaMessage::Text(t)) => Some((false, t)),
+            _ => None,
+        })
+        .map(|(is_name, t)| (is_name, meta_events::decode_text(t).trim().to_string()))
+        .filter(|(_, t)| !t.is_empty())
+        .collect();
+
+    texts
+        .iter()
+        .find(|(is_name, _)| *is_name)
+        .or(texts.first())
+        .map(|(_, t)| t.clone())
+}
+
+/// channels having notes, with the first program change of each channel
+fn channels(smf: &Smf) -> Vec<ChannelInformations> {
+    let mut channels: BTreeMap<u8, Option<u8>> = BTreeMap::new();
+    let mut programs: BTreeMap<u8, u8> = BTreeMap::new();
+    for track in &smf.tracks {
+        for e in track {
+            if let TrackEventKind::Midi { channel, message } = e.kind {
+                match message {
+                    MidiMessage::NoteOn { vel, .. } if vel > 0 => {
+                        channels.entry(channel.as_int()).or_default();
+                    }
+                    MidiMessage::ProgramChange { program } => {
+                        programs.entry(channel.as_int()).or_insert(program.as_int());
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    channels
+        .into_keys()
+        .map(|channel| ChannelInformations {
+            channel,
+            program: programs.get(&channel).copied(),
+        })
+        .collect()
+}
+
+/// compute the informations of a midi content
+pub(crate) fn smf_informations(smf: &Smf) -> Result<FileInformations, Box<dyn Error>> {
+    let notes = to_notes(smf, &None)?;
+    let duration = notes
+        .iter()
+        .fold(Duration::ZERO, |acc, n| acc.max(n.start + n.length));
+    let note_range = notes
+        .iter()
+        .map(|n| n.note)
+        .min()
+        .zip(notes.iter().map(|n| n.note).max());
+
+    let timed_meta_events = meta_events::timed_meta_events(smf);
+    let tempo = timed_meta_events.iter().find_map(|(_, m)| match m {
+        MetaMessage::Tempo(t) => Some(t.as_int()),
+        _ => None,
+    });
+    let time_signature = timed_meta_events.iter().find_map(|(_, m)| match m {
+        MetaMessage::TimeSignature(numerator, denominator, _, _) => Some((
+            *numerator,
+            1_u8.checked_shl(*denominator as u32).unwrap_or(0),
+        )),
+        _ => None,
+    });
+
+    Ok(FileInformations {
+        duration: Some(duration),
+        title: title(smf),
+        channels: channels(smf),
+        note_range,
+        polyphony_peak: polyphony_peak(&notes),
+        tempo,
+        time_signature,
+        ..Default::default()
+    })
+}
+
+#[test]
+pub fn test_smf_informations() {
+    use nodi::midly::{
+        num::{u15, u24, u28, u4, u7},
+        Format, Header, Timing, TrackEvent,
+    };
+
+    let event = |delta: u32, kind: TrackEventKind<'static>| TrackEvent {
+        delta: u28::from(delta),
+        kind,
+    };
+    let note = |on: bool, channel: u8, key: u8| TrackEventKind::Midi {
+        channel: u4::from(channel),
+        message: if on {
+            MidiMessage::NoteOn {
+                key: u7::from(key),
+                vel: u7::from(100),
+            }
+        } else {
+            MidiMessage::NoteOff {
+                key: u7::from(key),
+                vel: u7::from(0),
+            }
+        },
+    };
+
+    let smf = Smf {
+        header: Header::new(Format::Parallel, Timing::Metrical(u15::from(96))),
+        tracks: vec![
+            vec![
+                event(0, TrackEventKind::Meta(MetaMessage::Text(b"comment"))),
+                event(0, TrackEventKind::Meta(MetaMessage::TrackName(b"Song"))),
+                event(
+                    0,
+                    TrackEventKind::Meta(MetaMessage::Tempo(u24::from(600_000))),
+                ),
+                event(
+                    0,
+                    TrackEventKind::Meta(MetaMessage::TimeSignature(3, 2, 24, 8)),
+                ),
+                event(0, TrackEventKind::Meta(MetaMessage::EndOfTrack)),
+            ],
+            vec![
+                event(
+                    0,
+                    TrackEventKind::Midi {
+                        channel: u4::from(1),
+                        message: MidiMessage::ProgramChange {
+                            program: u7::from(20),
+                        },
+                    },
+                ),
+                event(0, note(true, 1, 60)),
+                event(0, note(true, 1, 64)),
+                event(0, note(true, 9, 36)),
+                event(96, note(false, 1, 60)),
+                event(0, note(false, 1, 64)),
+                event(0, note(false, 9, 36)),
+                event(0, note(true, 1, 72)),
+                event(96, note(false, 1, 72)),
+                event(0, TrackEventKind::Meta(MetaMessage::EndOfTrack)),
+            ],
+        ],
+    };
+
+    let informations = smf_informations(&smf).unwrap();
+    assert_eq!(informations.title, Some("Song".to_string()));
+    assert_eq!(informations.note_range, Some((36, 72)));
+    assert_eq!(informations.polyphony_peak, 3);
+    assert_eq!(informations.tempo, Some(600_000));
+    assert_eq!(informations.bpm(), Some(100.0));
+    assert_eq!(informations.time_signature, Some((3, 4)));
+    assert_eq!(
+        informations.channels,
+        vec![
+            ChannelInformations {
+                channel: 1,
+                program: Some(20)
+            },
+            ChannelInformations {
+                channel: 9,
+                program: None
+            }
+        ]
+    );
+}
@@ -0,0 +1,212 @@
+//! timed meta events of the midi files (lyrics, texts, markers),
+//! these events are not handled by the sheet used for the play
+
+use std::time::Duration;
+
+use nodi::midly::{Format, MetaMessage, Smf, Timing, TrackEventKind};
+
+use crate::{LyricLine, LyricSyllable, Section};
+
+use super::DEFAULT_TEMPO_IF_NOT_SET_IN_FILE;
+
+/// duration of a tick, for the given timing and tempo (microseconds per beat)
+fn tick_duration(timing: &Timing, tempo: u32) -> Duration {
+    match timing {
+        Timing::Metrical(ppq) => {
+            Duration::from_nanos(tempo as u64 * 1_000 / (ppq.as_int().max(1) as u64))
+        }
+        Timing::Timecode(fps, subframes) => {
+            Duration::from_secs_f64(1.0 / (fps.as_f32() as f64 * (*subframes).max(1) as f64))
+        }
+    }
+}
+
+/// meta messages of the file, with their time from the beginning of the play
+pub(crate) fn timed_meta_events<'a>(smf: &Smf<'a>) -> Vec<(Duration, MetaMessage<'a>)> {
+    // absolute ticks of all the events, tracks are merged
+    // or played one after the other for sequential files
+    // (None is the start of a sequence, the tempo is reset)
+    let mut events: Vec<(u64, Option<&TrackEventKind<'a>>)> = vec![];
+    let mut sequence_start = 0_u64;
+    for track in &smf.tracks {
+        let mut tick = match smf.header.format {
+            Format::Sequential => {
+                events.push((sequence_start, None));
+                sequence_start
+            }
+            _ => 0,
+        };
+        for e in track {
+            tick += e.delta.as_int() as u64;
+            events.push((tick, Some(&e.kind)));
+        }
+        sequence_start = tick;
+    }
+    // stable sort, the track order is kept for simultaneous events
+    events.sort_by_key(|(tick, _)| *tick);
+
+    let mut result = vec![];
+    let mut tempo = DEFAULT_TEMPO_IF_NOT_SET_IN_FILE;
+    let mut current_tick = 0_u64;
+    let mut current_time = Duration::ZERO;
+    for (tick, kind) in events {
+        current_time += tick_duration(&smf.header.timing, tempo) * (tick - current_tick) as u32;
+        current_tick = tick;
+
+        match kind {
+            None => tempo = DEFAULT_TEMPO_IF_NOT_SET_IN_FILE,
+            Some(TrackEventKind::Meta(meta)) => {
+                if let MetaMessage::Tempo(t) = meta {
+                    tempo = t.as_int();
+                }
+                result.push((current_time, *meta));
+            }
+            _ => {}
+        }
+    }
+    result
+}
+
+/// decode a text from a midi file, utf8 or latin1 (used by most of the karaoke files)
+pub(crate) fn decode_text(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(s) => s.to_string(),
+        Err(_) => bytes.iter().map(|b| *b as char).collect(),
+    }
+}
+
+/// construct the lyrics lines, from lyric events (FF 05),
+/// or text events (FF 01) for karaoke files that does not have lyric events
+pub(crate) fn lyrics_lines(
+    events: &[(Duration, MetaMessage)],
+    shift_duration: Duration,
+) -> Vec<LyricLine> {
+    let lyrics: Vec<(Duration, &[u8])> = events
+        .iter()
+        .filter_map(|(t, m)| match m {
+            MetaMessage::Lyric(text) => Some((*t, *text)),
+            _ => None,
+        })
+        .collect();
+
+    let texts = if lyrics.is_empty() {
+        // .kar files, the lyrics are in text events, @ prefixed ones are headers
+        events
+            .iter()
+            .filter_map(|(t, m)| match m {
+                MetaMessage::Text(text) if !text.starts_with(b"@") => Some((*t, *text)),
+                _ => None,
+            })
+            .collect()
+    } else {
+        lyrics
+    };
+
+    let mut lines: Vec<LyricLine> = vec![];
+    let mut current: Option<LyricLine> = None;
+
+    for (time, bytes) in texts {
+        let raw = decode_text(bytes);
+        let start = time + shift_duration;
+
+        // karaoke convention, / new line, \ new paragraph
+        let new_line_before = raw.starts_with('/') || raw.starts_with('\\');
+        // lyric event convention, the line ends with a carriage return
+        let new_line_after = raw.ends_with('\r') || raw.ends_with('\n');
+
+        let text: String = raw
+            .trim_start_matches(['/', '\\'])
+            .trim_end_matches(['\r', '\n'])
+            .to_string();
+
+        if new_line_before {
+            if let Some(line) = current.take() {
+                lines.push(line);
+            }
+        }
+
+        if !text.is_empty() {
+            current
+                .get_or_insert_with(|| LyricLine {
+                    start,
+                    syllables: vec![],
+                })
+                .syllables
+                .push(LyricSyllable { start, text });
+        }
+
+        if new_line_after {
+            if let Some(line) = current.take() {
+                lines.push(line);
+            }
+        }
+    }
+
+    if let Some(line) = current.take() {
+        lines.push(line);
+    }
+
+    lines
+}
+
+/// construct the sections of the file, from the marker and cue point events
+pub(crate) fn sections(
+    events: &[(Duration, MetaMessage)],
+    shift_duration: Duration,
+) -> Vec<Section> {
+    events
+        .iter()
+        .filter_map(|(t, m)| match m {
+            MetaMessage::Marker(text) | MetaMessage::CuePoint(text) => Some(Section {
+                start: *t + shift_duration,
+                name: decode_text(text).trim().to_string(),
+            }),
+            _ => None,
+        })
+        .filter(|s| !s.name.is_empty())
+        .collect()
+}
+
+#[test]
+pub fn test_lyrics_lines() {
+    let events = vec![
+        (
+            Duration::from_secs(0),
+            MetaMessage::Text(b"@KMIDI KARAOKE FILE"),
+        ),
+        (Duration::from_secs(1), MetaMessage::Text(b"\\Au ")),
+        (Duration::from_secs(2), MetaMessage::Text(b"clair ")),
+        (Duration::from_secs(3), MetaMessage::Text(b"/de la ")),
+        (Duration::from_secs(4), MetaMessage::Text(b"lune")),
+    ];
+
+    let lines = lyrics_lines(&events, Duration::from_secs(2));
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0].text(), "Au clair ");
+    assert_eq!(lines[0].start, Duration::from_secs(3));
+    assert_eq!(lines[1].text(), "de la lune");
+
+    // lyric events have the priority on the texts
+    let events = vec![
+        (Duration::from_secs(0), MetaMessage::Text(b"a comment")),
+        (Duration::from_secs(1), MetaMessage::Lyric(b"Au clair\r")),
+        (Duration::from_secs(2), MetaMessage::Lyric(b"de la lune\r")),
+    ];
+    let lines = lyrics_lines(&events, Duration::ZERO);
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[1].text(), "de la lune");
+}
+
+#[test]
+pub fn test_sections() {
+    let events = vec![
+        (Duration::from_secs(0), MetaMessage::Marker(b"Intro")),
+        (Duration::from_secs(1), MetaMessage::Text(b"a comment")),
+        (Duration::from_secs(10), MetaMessage::CuePoint(b"Trio ")),
+        (Duration::from_secs(12), MetaMessage::Marker(b"")),
+    ];
+    let sections = sections(&events, Duration::from_secs(1));
+    assert_eq!(sections.len(), 2);
+    assert_eq!(sections[1].name, "Trio");
+    assert_eq!(sections[1].start, Duration::from_secs(11));
+}
@@ -2,7 +2,7 @@ use std::{
     error::Error,
     fs::File,
     io::BufReader,
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::{
         mpsc::{channel, Receiver, Sender},
         Arc, Mutex, MutexGuard,
@@ -14,14 +14,13 @@ use std::{
 use bookparsing::VirtualBook;
 use midir::{MidiOutput, MidiOutputConnection};
 use nodi::{
-    midly::{Format, MidiMessage, Smf},
-    timers::Ticker,
-    Event, MidiEvent, Sheet, Timer,
+    midly::{MidiMessage, Smf},
+    Event, MidiEvent, Moment, Sheet, Timer,
 };
 
 use crate::{
     Command, FileInformations, FileInformationsConstructor, NotesDisplayInformations,
-    NotesInformations, PlainNoteWithChannel, Player, PlayerFactory, Response,
+    NotesInformations, PlainNoteWithChannel, PlaySettings, Player, PlayerFactory, Response,
 };
 
 use std::convert::TryFrom;
@@ -30,9 +29,29 @@ use log::{debug, error, info, warn};
 
 use thread_priority::*;
 
+use self::file_timer::{create_sheet, FileTimer};
 use self::midiconverter::{convert, create_conversion_from_scale, read_conversion, Conversion};
+use self::sounding_notes::SoundingNotes;
+use self::volume::VolumeScaler;
 
+pub use self::output_profile::{AllNotesOffStrategy, OutputProfile};
+pub use self::volume::VolumeMode;
+
+/// file formats readers
+pub mod formats;
+
+/// General Midi names
+pub mod general_midi;
+
+mod file_timer;
+mod informations;
+mod meta_events;
 mod midiconverter;
+mod output_profile;
+mod seek;
+mod sounding_notes;
+mod transpose;
+mod volume;
 
 // 120 bpm default tempo for files that does not have tempo signature in it
 // 48 ticks per quarter note
@@ -42,9 +61,43 @@ mod midiconverter;
 const BEAT_TIME_IN_MICROSECOND: u32 = 60 * 1_000_000 / 120;
 const DEFAULT_TEMPO_IF_NOT_SET_IN_FILE: u32 = BEAT_TIME_IN_MICROSECOND;
 
+/// shorter loops are ignored
+pub const MINIMUM_LOOP_DURATION: Duration = Duration::from_millis(200);
+
+// slowest play speed factor
+const MINIMUM_SPEED: f32 = 0.1;
+
+// the waits between the moments are cut in slices of this duration when a fade out is set,
+// the volume is lowered at each slice and the stop is checked
+const FADE_STEP: Duration = Duration::from_millis(20);
+
+/// volume factor of the fade out, from 1 at the start of the fade to 0 at its end
+fn fade_factor(fade_started: Option<Instant>, fade_out: Duration) -> f32 {
+    fade_started
+        .map(|start| (1.0 - start.elapsed().as_secs_f32() / fade_out.as_secs_f32()).max(0.0))
+        .unwrap_or(1.0)
+}
+
 /// Midi device player factory
 pub struct MidiPlayerFactory {
     pub device_no: usize,
+    /// strike again the notes held when the pause has been activated, on resume
+    pub restrike_on_resume: bool,
+    /// device specific output options
+    pub output_profile: OutputProfile,
+    /// fade out time when the play is stopped, the notes are cut if zero
+    pub fade_out: Duration,
+}
+
+impl Default for MidiPlayerFactory {
+    fn default() -> Self {
+        Self {
+            device_no: 0,
+            restrike_on_resume: true,
+            output_profile: OutputProfile::default(),
+            fade_out: Duration::ZERO,
+        }
+    }
 }
 
 #[profiling::all_functions]
@@ -72,6 +125,10 @@ impl PlayerFactory for MidiPlayerFactory {
             ispaused: Arc::new(Mutex::new(false)),
             isplaying: Arc::new(Mutex::new(false)),
             notes: Arc::new(Mutex::new(Arc::new(NotesInformations::default()))),
+            restrike_on_resume: Arc::new(Mutex::new(self.restrike_on_resume)),
+            output_profile: self.output_profile.clone(),
+            volume: Arc::new(Mutex::new(1.0)),
+            fade_out: Arc::new(Mutex::new(self.fade_out)),
         }))
     }
 
@@ -83,9 +140,12 @@ impl PlayerFactory for MidiPlayerFactory {
 }
 
 /// midi device reference
+#[derive(Debug, Clone)]
 pub struct DeviceInformation {
     pub no: usize,
     pub label: String,
+    /// port name, without the device number
+    pub name: String,
 }
 
 #[profiling::all_functions]
@@ -117,15 +177,11 @@ impl MidiPlayerFactory {
             println!("No active MIDI output device detected.");
         } else {
             for (i, p) in out_ports.iter().enumerate() {
-                let label = format!(
-                    "#{}: {}",
-                    i,
-                    midi_out
-                        .port_name(p)
-                        .as_deref()
-                        .unwrap_or("<no device name>")
-                );
-                returned.push(DeviceInformation { no: i, label });
+                let name = midi_out
+                    .port_name(p)
+                    .unwrap_or_else(|_| "<no device name>".into());
+                let label = format!("#{}: {}", i, &name);
+                returned.push(DeviceInformation { no: i, label, name });
             }
         }
         Ok(returned)
@@ -146,20 +202,9 @@ pub struct MidiFileInformationsConstructor {}
 /// file information trait, specific to midi, and midi controlled equipments
 impl FileInformationsConstructor for MidiFileInformationsConstructor {
     fn compute(&mut self, filename: &PathBuf) -> Result<FileInformations, Box<dyn Error>> {
-        match read_all_kind_of_files(filename, None) {
-            Ok(res) => {
-                let result = res
-                    .0
-                    .notes
-                    .iter()
-                    .fold(Duration::new(0, 0), |acc, n| acc.max(n.start + n.length));
-
-                // result
-                Ok(FileInformations {
-                    duration: Some(result),
-                })
-            }
-            Err(e) => Err(e),
+        match formats::registry().find(filename) {
+            Some(reader) => reader.compute_informations(filename),
+            None => Err(format!("this file type : {:?} is not known", filename).into()),
         }
     }
 }
@@ -187,6 +232,18 @@ pub struct MidiPlayer {
     /// note representation for the display
     // shared between threads
     notes: Arc<Mutex<Arc<NotesInformations>>>,
+
+    /// strike again the held notes when resuming from pause, shared with the play thread
+    restrike_on_resume: Arc<Mutex<bool>>,
+
+    /// device specific output options
+    output_profile: OutputProfile,
+
+    /// master volume, between 0 and 1, shared with the play thread
+    volume: Arc<Mutex<f32>>,
+
+    /// fade out time when the play is stopped, shared with the play thread
+    fade_out: Arc<Mutex<Duration>>,
 }
 
 impl Drop for MidiPlayer {
@@ -196,44 +253,124 @@ impl Drop for MidiPlayer {
     }
 }
 
-#[allow(dead_code)]
-fn send_panic(con: &mut MutexGuard<MidiOutputConnection>) {
-    // panic
-    let mut buf = Vec::new();
-    buf.push(0xcc);
-    buf.push(123);
-    if let Err(e) = con.send(&buf) {
-        error!("error in sending panic code :{}", e);
+/// reset the output, using the device strategy
+fn send_panic(
+    con: &mut MutexGuard<MidiOutputConnection>,
+    buf: &mut Vec<u8>,
+    strategy: &AllNotesOffStrategy,
+) {
+    for event in strategy.reset_events() {
+        buf.clear();
+        let _ = event.write(buf);
+        if let Err(e) = con.send(buf) {
+            error!("error in sending panic code : {:?} {}", event, e);
+        }
     }
 }
 
-// sending all note off
-fn all_notes_off(con: &mut MutexGuard<MidiOutputConnection>) {
-    let mut buf = Vec::new();
-    for ch in 0..16 {
-        for note in 0..=127 {
-            let msg = MidiEvent {
-                channel: ch.into(),
-                message: MidiMessage::NoteOff {
-                    key: note.into(),
-                    vel: 127.into(),
-                },
-            };
+/// send the events changing the volume
+fn send_volume_events(
+    con: &mut MutexGuard<MidiOutputConnection>,
+    buf: &mut Vec<u8>,
+    events: &[MidiEvent],
+) {
+    for event in events {
+        buf.clear();
+        let _ = event.write(buf);
+        if let Err(e) = con.send(buf) {
+            warn!("fail to send volume : {:?} {:?}", event, e);
+        }
+    }
+}
 
-            buf.clear();
-            let _ = msg.write(&mut buf); // ignore return
-            if let Err(e) = con.send(&buf) {
-                warn!("fail to send stop note : {:?} {:?}", msg, e);
-            }
+/// send a midi event on the output, and keep track of the sounding notes
+fn send_event(
+    con: &mut MutexGuard<MidiOutputConnection>,
+    buf: &mut Vec<u8>,
+    event: &MidiEvent,
+    sounding: &mut SoundingNotes,
+) {
+    buf.clear();
+    let _ = event.write(buf); // ignore return
+    if let Err(e) = con.send(buf) {
+        warn!("fail to send event : {:?} {:?}", event, e);
+    }
+    sounding.update(event);
+}
+
+/// send note off for the sounding notes, the sounding state is kept
+/// to permit to strike them again
+fn release_sounding_notes(
+    con: &mut MutexGuard<MidiOutputConnection>,
+    buf: &mut Vec<u8>,
+    sounding: &SoundingNotes,
+    strategy: &AllNotesOffStrategy,
+) {
+    for event in sounding.note_off_events(strategy) {
+        buf.clear();
+        let _ = event.write(buf);
+        if let Err(e) = con.send(buf) {
+            warn!("fail to send stop note : {:?} {:?}", event, e);
         }
     }
 }
 
+/// strike again the sounding notes (after a pause)
+fn restrike_sounding_notes(
+    con: &mut MutexGuard<MidiOutputConnection>,
+    buf: &mut Vec<u8>,
+    sounding: &SoundingNotes,
+) {
+    for event in sounding.note_on_events() {
+        buf.clear();
+        let _ = event.write(buf);
+        if let Err(e) = con.send(buf) {
+            warn!("fail to restrike note : {:?} {:?}", event, e);
+        }
+    }
+}
+
+/// move the play position, the sounding notes are released
+/// and the channels state (programs, controllers) at the new position is sent
+#[allow(clippy::too_many_arguments)]
+fn jump_to(
+    con: &mut MutexGuard<MidiOutputConnection>,
+    buf: &mut Vec<u8>,
+    sounding: &mut SoundingNotes,
+    strategy: &AllNotesOffStrategy,
+    scaler: &mut VolumeScaler,
+    moments: &[Moment],
+    initial_timer: FileTimer,
+    target: Duration,
+) -> seek::SeekPosition<FileTimer> {
+    debug!("jump to {:?}", target);
+    release_sounding_notes(con, buf, sounding, strategy);
+    sounding.clear();
+
+    let position = seek::seek_position(moments, initial_timer, target);
+    for event in position.chased.events() {
+        send_event(con, buf, &scaler.apply(&event), sounding);
+    }
+    position
+}
+
+/// stop all the notes, the sounding ones are released, then the output is reset
+fn all_notes_off(
+    con: &mut MutexGuard<MidiOutputConnection>,
+    buf: &mut Vec<u8>,
+    sounding: &mut SoundingNotes,
+    strategy: &AllNotesOffStrategy,
+) {
+    release_sounding_notes(con, buf, sounding, strategy);
+    sounding.clear();
+    send_panic(con, buf, strategy);
+}
+
 #[profiling::function]
 fn read_midi_file(
-    filename: &PathBuf,
+    filename: &Path,
     start_wait: Option<f32>,
-) -> Result<(Arc<NotesInformations>, Ticker, Sheet), Box<dyn Error>> {
+) -> Result<(Arc<NotesInformations>, FileTimer, Sheet), Box<dyn Error>> {
     // Load bytes first
     let file_content_data = std::fs::read(filename)?;
 
@@ -243,20 +380,24 @@ fn read_midi_file(
     // get note display
     let notes = Arc::new(to_notes(&smf, &start_wait)?);
 
+    // lyrics, texts and markers
+    let shift_duration = Duration::from_secs_f32(start_wait.unwrap_or(0.0));
+    let timed_meta_events = meta_events::timed_meta_events(&smf);
+    let lyrics = meta_events::lyrics_lines(&timed_meta_events, shift_duration);
+    let sections = meta_events::sections(&timed_meta_events, shift_duration);
+
     // deconstruct the elements
     let Smf { header, tracks } = smf;
 
-    let mut timer = Ticker::try_from(header.timing)?;
+    let timer = FileTimer::try_from(header.timing)?;
     debug!("timer : {:?}", &timer);
-    timer.change_tempo(DEFAULT_TEMPO_IF_NOT_SET_IN_FILE);
 
-    let sheet = match header.format {
-        Format::SingleTrack | Format::Sequential => Sheet::sequential(&tracks),
-        Format::Parallel => Sheet::parallel(&tracks),
-    };
+    let sheet = create_sheet(header.format, &tracks);
 
     let mut notes_informations = NotesInformations::default();
     notes_informations.notes = notes;
+    notes_informations.lyrics = Arc::new(lyrics);
+    notes_informations.sections = Arc::new(sections);
 
     Ok((Arc::new(notes_informations), timer, sheet))
 }
@@ -284,10 +425,10 @@ fn resolve_conversion(vb: &VirtualBook) -> Result<Option<Conversion>, Box<dyn Er
 
 #[profiling::function]
 fn read_book_file(
-    filename: &PathBuf, // must be a book
+    filename: &Path, // must be a book
     // extension : external_dir_for_overload: &PathBuf,
     start_wait: Option<f32>,
-) -> Result<(Arc<NotesInformations>, Ticker, Sheet), Box<dyn Error>> {
+) -> Result<(Arc<NotesInformations>, FileTimer, Sheet), Box<dyn Error>> {
     // book parsing
     let file = File::open(filename)?;
     let mut reader = BufReader::new(&file);
@@ -327,14 +468,10 @@ fn read_book_file(
             // deconstruct the elements
             let Smf { header, tracks } = resultsmf;
 
-            let mut timer = Ticker::try_from(header.timing)?;
+            let timer = FileTimer::try_from(header.timing)?;
             debug!("timer : {:?}", &timer);
-            timer.change_tempo(DEFAULT_TEMPO_IF_NOT_SET_IN_FILE);
 
-            let sheet = match header.format {
-                Format::SingleTrack | Format::Sequential => Sheet::sequential(&tracks),
-                Format::Parallel => Sheet::parallel(&tracks),
-            };
+            let sheet = create_sheet(header.format, &tracks);
 
             let mut notes_informations = NotesInformations::default();
 
@@ -355,28 +492,21 @@ fn read_book_file(
 
 #[profiling::function]
 fn read_all_kind_of_files(
-    filename: &PathBuf, // must be a book
+    filename: &PathBuf,
     // extension : external_dir_for_overload: &PathBuf,
     start_wait: Option<f32>,
-) -> Result<(Arc<NotesInformations>, Ticker, Sheet), Box<dyn Error>> {
+) -> Result<(Arc<NotesInformations>, FileTimer, Sheet), Box<dyn Error>> {
     info!("reading {:?}", filename);
-    let ext_option = filename.extension();
-    if let Some(ext) = ext_option {
-        match ext.to_ascii_lowercase().to_string_lossy().as_ref() {
-            "mid" => {
-                info!("reading midi file : {:?}", filename);
-                return read_midi_file(filename, start_wait);
-            }
-            "book" => {
-                info!("reading book file : {:?}", filename);
-                return read_book_file(filename, start_wait);
-            }
-            _ => {
-                warn!("this file type : {:?} is not known", filename);
-            }
+    match formats::registry().find(filename) {
+        Some(reader) => {
+            info!("reading {} file : {:?}", reader.name(), filename);
+            reader.read(filename, start_wait)
+        }
+        None => {
+            warn!("this file type : {:?} is not known", filename);
+            Err(format!("this file type : {:?} is not known", &filename).into())
         }
     }
-    Err(format!("no extension given for the file {:?}", &filename).into())
 }
 
 /// Player trait implementation
@@ -386,6 +516,15 @@ impl Player for MidiPlayer {
         &mut self,
         filename: &PathBuf,
         start_wait: Option<f32>,
+    ) -> Result<(), Box<dyn Error>> {
+        self.start_play_with_settings(filename, start_wait, &PlaySettings::default())
+    }
+
+    fn start_play_with_settings(
+        &mut self,
+        filename: &PathBuf,
+        start_wait: Option<f32>,
+        settings: &PlaySettings,
     ) -> Result<(), Box<dyn Error>> {
         #[cfg(feature = "profiling")]
         profiling::scope!("Prepare start play");
@@ -400,8 +539,7 @@ impl Player for MidiPlayer {
 
         let _ = self.cancel.send(true); // don't handle the error
 
-        // silence the output
-        self.silence();
+        // the output is silenced by the play thread, once the previous play released the connection
 
         let (sender, receiver) = channel();
         self.cancel = sender;
@@ -422,6 +560,13 @@ impl Player for MidiPlayer {
         let ispaused = Arc::new(Mutex::new(false));
         self.ispaused = ispaused.clone();
 
+        let restrike_access = Arc::clone(&self.restrike_on_resume);
+        let all_notes_off_strategy = self.output_profile.all_notes_off;
+        let volume_mode = self.output_profile.volume_mode;
+        let volume_access = Arc::clone(&self.volume);
+        let fade_out_access = Arc::clone(&self.fade_out);
+        let settings = settings.clone();
+
         // thread spawned interpret the Midi event and send them on the line
         thread::spawn(move || {
             profiling::register_thread!("player thread");
@@ -434,11 +579,18 @@ impl Player for MidiPlayer {
             let mut buf = Vec::new();
             let mut total_duration = Duration::new(0, 0);
             let mut ticks_counter = 0_u32;
+            let mut sounding = SoundingNotes::new();
+            let master_volume = || volume_access.lock().map(|v| *v).unwrap_or(1.0);
+            let restrike_on_resume = || restrike_access.lock().map(|r| *r).unwrap_or(true);
+            let fade_out_time = || fade_out_access.lock().map(|f| *f).unwrap_or_default();
+            let mut scaler = VolumeScaler::new(volume_mode, master_volume());
 
             if let Ok(mut con) = con.lock() {
                 debug!("midi connexion aquired");
 
-                all_notes_off(&mut con);
+                all_notes_off(&mut con, &mut buf, &mut sounding, &all_notes_off_strategy);
+                // the channel volumes may have been faded out by the previous play
+                send_volume_events(&mut con, &mut buf, &scaler.volume_events());
 
                 if let Ok(output_locked) = output_reference.lock() {
                     output_locked
@@ -504,7 +656,12 @@ impl Player for MidiPlayer {
                                 // check stopped
                                 if receiver.try_recv().is_ok() {
                                     // stopped
-                                    all_notes_off(&mut con);
+                                    all_notes_off(
+                                        &mut con,
+                                        &mut buf,
+                                        &mut sounding,
+                                        &all_notes_off_strategy,
+                                    );
                                     if let Ok(mut m) = isplaying_info.lock() {
                                         *m = false;
                                     }
@@ -524,14 +681,58 @@ impl Player for MidiPlayer {
                             }
                         }
 
-                        let mut iter_moment = midi_sheet.iter();
+                        // timer at the beginning of the file, used for seeking
+                        let initial_timer = timer;
+                        let moments = midi_sheet.as_moments();
+                        let mut moment_index = 0_usize;
+                        // A-B loop, in the file time (without the start wait)
+                        let mut play_loop: Option<(Duration, Duration)> = None;
+                        let mut speed = settings.speed.max(MINIMUM_SPEED);
+                        let start_offset = settings.start_offset.unwrap_or_default();
+
+                        if !start_offset.is_zero() {
+                            let position = jump_to(
+                                &mut con,
+                                &mut buf,
+                                &mut sounding,
+                                &all_notes_off_strategy,
+                                &mut scaler,
+                                moments,
+                                initial_timer,
+                                start_offset,
+                            );
+                            timer = position.timer;
+                            moment_index = position.moment_index;
+                            ticks_counter = position.ticks_counter;
+                            total_duration = position.total_duration;
+                        }
+
+                        // the stop is delayed during the fade out
+                        let mut fade_started: Option<Instant> = None;
 
                         loop {
                             // for moment in midi_sheet {
-                            if receiver.try_recv().is_ok() {
+                            let fade_out = fade_out_time();
+                            let paused = ispaused.lock().map(|p| *p).unwrap_or(false);
+                            let stop_asked = receiver.try_recv().is_ok();
+                            if stop_asked
+                                && fade_started.is_none()
+                                && !fade_out.is_zero()
+                                && !paused
+                            {
+                                debug!("fade out the play during {:?}", fade_out);
+                                fade_started = Some(Instant::now());
+                            } else if (stop_asked && fade_started.is_none())
+                                || fade_started.is_some_and(|start| start.elapsed() >= fade_out)
+                            {
                                 // cancel received
                                 // stopped
-                                all_notes_off(&mut con);
+                                all_notes_off(
+                                    &mut con,
+                                    &mut buf,
+                                    &mut sounding,
+                                    &all_notes_off_strategy,
+                                );
                                 if let Ok(mut m) = isplaying_info.lock() {
                                     *m = false;
                                 }
@@ -543,14 +744,85 @@ impl Player for MidiPlayer {
 
                             if let Ok(receiver) = commands.lock() {
                                 if let Ok(command) = receiver.try_recv() {
+                                    // replay is a seek at the beginning of the play, after the wait
+                                    let command = match command {
+                                        Command::Replay => Command::Seek(wait_time + start_offset),
+                                        c => c,
+                                    };
                                     match command {
                                         Command::Pause => {
                                             if let Ok(mut p) = ispaused.lock() {
                                                 let readvalue: bool = *p;
                                                 *p = !readvalue;
+                                                if *p {
+                                                    // pausing, release the held notes
+                                                    debug!(
+                                                        "pause, release {} sounding notes",
+                                                        sounding.len()
+                                                    );
+                                                    release_sounding_notes(
+                                                        &mut con,
+                                                        &mut buf,
+                                                        &sounding,
+                                                        &all_notes_off_strategy,
+                                                    );
+                                                } else if restrike_on_resume()
+                                                    && !sounding.is_empty()
+                                                {
+                                                    restrike_sounding_notes(
+                                                        &mut con, &mut buf, &sounding,
+                                                    );
+                                                } else {
+                                                    // notes have been released on pause
+                                                    sounding.clear();
+                                                }
+                                            }
+                                        }
+
+                                        Command::Seek(position) => {
+                                            let position = jump_to(
+                                                &mut con,
+                                                &mut buf,
+                                                &mut sounding,
+                                                &all_notes_off_strategy,
+                                                &mut scaler,
+                                                moments,
+                                                initial_timer,
+                                                position.saturating_sub(wait_time),
+                                            );
+                                            timer = position.timer;
+                                            moment_index = position.moment_index;
+                                            ticks_counter = position.ticks_counter;
+                                            total_duration = position.total_duration;
+
+                                            if let Ok(output_locked) = output_reference.lock() {
+                                                output_locked
+                                                    .send(Response::CurrentPlayTime(
+                                                        total_duration + wait_time,
+                                                    ))
+                                                    .unwrap();
                                             }
                                         }
 
+                                        Command::SetLoop(start, end) => {
+                                            if end > start + MINIMUM_LOOP_DURATION {
+                                                play_loop = Some((
+                                                    start.saturating_sub(wait_time),
+                                                    end.saturating_sub(wait_time),
+                                                ));
+                                            } else {
+                                                warn!("loop too short {:?} {:?}", start, end);
+                                            }
+                                        }
+
+                                        Command::ClearLoop => {
+                                            play_loop = None;
+                                        }
+
+                                        Command::Speed(factor) => {
+                                            speed = factor.max(MINIMUM_SPEED);
+                                        }
+
                                         _e => {
                                             debug!("command not yet supported");
                                         }
@@ -572,7 +844,56 @@ impl Player for MidiPlayer {
                                 }
                             }
 
-                            let current = iter_moment.next();
+                            let current = moments.get(moment_index);
+                            moment_index += 1;
+
+                            // end of the loop reached, go back to the loop start
+                            let loop_start = play_loop.and_then(|(start, end)| {
+                                let next_duration = match current {
+                                    Some(moment) if !moment.is_empty() => {
+                                        total_duration + timer.sleep_duration(ticks_counter)
+                                    }
+                                    Some(_) => return None,
+                                    // end of file, inside the loop
+                                    None if start < total_duration => end,
+                                    None => return None,
+                                };
+                                (next_duration >= end).then_some(start)
+                            });
+                            // end of the play asked before the end of the file
+                            if let (Some(end), Some(moment)) = (settings.end_offset, current) {
+                                if !moment.is_empty()
+                                    && total_duration + timer.sleep_duration(ticks_counter) >= end
+                                {
+                                    info!("end of the play reached at {:?}", end);
+                                    all_notes_off(
+                                        &mut con,
+                                        &mut buf,
+                                        &mut sounding,
+                                        &all_notes_off_strategy,
+                                    );
+                                    break;
+                                }
+                            }
+
+                            if let Some(loop_start) = loop_start {
+                                let position = jump_to(
+                                    &mut con,
+                                    &mut buf,
+                                    &mut sounding,
+                                    &all_notes_off_strategy,
+                                    &mut scaler,
+                                    moments,
+                                    initial_timer,
+                                    loop_start,
+                                );
+                                timer = position.timer;
+                                moment_index = position.moment_index;
+                                ticks_counter = position.ticks_counter;
+                                total_duration = position.total_duration;
+                                continue;
+                            }
+
                             if let Some(moment) = current {
                                 if !moment.is_empty() {
                                     if let Ok(mut m) = isplaying_info.lock() {
@@ -580,11 +901,58 @@ impl Player for MidiPlayer {
                                     }
                                     let d = timer.sleep_duration(ticks_counter);
 
-                                    timer.sleep(ticks_counter);
+                                    if fade_out.is_zero() {
+                                        if !d.is_zero() {
+                                            nodi::timers::sleep(d.div_f32(speed));
+                                        }
+                                    } else {
+                                        // the wait is cut in slices, a stop asked during a long
+                                        // note starts the fade, which ramps down the volume
+                                        // while the notes are held, and ends exactly on time
+                                        let wake_up = Instant::now() + d.div_f32(speed);
+                                        loop {
+                                            if fade_started.is_none() && receiver.try_recv().is_ok()
+                                            {
+                                                debug!("fade out the play during {:?}", fade_out);
+                                                fade_started = Some(Instant::now());
+                                            }
+                                            if let Some(start) = fade_started {
+                                                if start.elapsed() >= fade_out {
+                                                    break;
+                                                }
+                                                let volume_events = scaler.set_volume(
+                                                    master_volume()
+                                                        * fade_factor(fade_started, fade_out),
+                                                );
+                                                send_volume_events(
+                                                    &mut con,
+                                                    &mut buf,
+                                                    &volume_events,
+                                                );
+                                            }
+                                            let now = Instant::now();
+                                            if now >= wake_up {
+                                                break;
+                                            }
+                                            nodi::timers::sleep((wake_up - now).min(FADE_STEP));
+                                        }
+                                        // end of the fade, the play is stopped at the loop start
+                                        if fade_started
+                                            .is_some_and(|start| start.elapsed() >= fade_out)
+                                        {
+                                            continue;
+                                        }
+                                    }
 
                                     total_duration += d;
                                     // total_duration = Instant::now() - start_play_time;
 
+                                    // master volume, lowered during the fade out
+                                    let volume_events = scaler.set_volume(
+                                        master_volume() * fade_factor(fade_started, fade_out),
+                                    );
+                                    send_volume_events(&mut con, &mut buf, &volume_events);
+
                                     ticks_counter = 0;
 
                                     #[cfg(feature = "profiling")]
@@ -599,9 +967,17 @@ impl Player for MidiPlayer {
                                             }
 
                                             Event::Midi(msg) => {
-                                                buf.clear();
-                                                let _ = msg.write(&mut buf);
-                                                let _ = con.send(&buf);
+                                                if let Some(msg) = transpose::transpose_event(
+                                                    msg,
+                                                    settings.transpose,
+                                                ) {
+                                                    send_event(
+                                                        &mut con,
+                                                        &mut buf,
+                                                        &scaler.apply(&msg),
+                                                        &mut sounding,
+                                                    );
+                                                }
                                             }
                                             _ => (),
                                         };
@@ -661,6 +1037,28 @@ impl Player for MidiPlayer {
         }
     }
 
+    fn set_volume(&mut self, volume: f32) {
+        if let Ok(mut v) = self.volume.lock() {
+            *v = volume.clamp(0.0, 1.0);
+        }
+    }
+
+    fn set_restrike_on_resume(&mut self, restrike: bool) {
+        if let Ok(mut r) = self.restrike_on_resume.lock() {
+            *r = restrike;
+        }
+    }
+
+    fn set_fade_out(&mut self, fade_out: Duration) {
+        if let Ok(mut f) = self.fade_out.lock() {
+            *f = fade_out;
+        }
+    }
+
+    fn set_output_profile(&mut self, profile: OutputProfile) {
+        self.output_profile = profile;
+    }
+
     fn is_playing(&self) -> bool {
         if let Ok(m) = self.isplaying.lock() {
             *m
@@ -705,15 +1103,12 @@ impl MidiPlayer {
             isplaying: Arc::new(Mutex::new(false)),
             ispaused: Arc::new(Mutex::new(false)),
             notes: Arc::new(Mutex::new(Arc::new(NotesInformations::default()))),
+            restrike_on_resume: Arc::new(Mutex::new(true)),
+            output_profile: OutputProfile::default(),
+            volume: Arc::new(Mutex::new(1.0)),
+            fade_out: Arc::new(Mutex::new(Duration::ZERO)),
         }
     }
-
-    /// silence all notes (sending cmd stop all)
-    fn silence(&self) {
-        let mut con = self.midi_output_connection.lock().unwrap();
-        let _ = con.send(&[0xb0, 123]);
-        let _ = con.send(&[0xb0, 120]);
-    }
 }
 
 /// convert midi file to notes
@@ -723,15 +1118,11 @@ pub fn to_notes(
 ) -> Result<Vec<PlainNoteWithChannel>, Box<dyn Error>> {
     let Smf { header, tracks } = smf;
     let miditiming = header.timing;
-    let mut timer = Ticker::try_from(miditiming)?;
+    let mut timer = FileTimer::try_from(miditiming)?;
 
     debug!("timer : {:?}", &timer);
-    timer.change_tempo(DEFAULT_TEMPO_IF_NOT_SET_IN_FILE);
 
-    let sheet = match header.format {
-        Format::SingleTrack | Format::Sequential => Sheet::sequential(tracks),
-        Format::Parallel => Sheet::parallel(tracks),
-    };
+    let sheet = create_sheet(header.format, tracks);
 
     let shift_duration = if start_wait.is_some() {
         Duration::from_secs_f32(start_wait.unwrap())
@@ -0,0 +1,121 @@
+//! output profiles, permit to adapt the messages sent to the capabilities of the
+//! connected device (some serial interfaces does not support large bursts)
+
+use serde::{Deserialize, Serialize};
+
+use super::volume::VolumeMode;
+
+use nodi::{
+    midly::{
+        num::{u4, u7},
+        MidiMessage,
+    },
+    MidiEvent,
+};
+
+/// the way the notes are stopped when the play is started or stopped
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AllNotesOffStrategy {
+    /// send the CC 123 (all notes off) controller on all channels
+    #[default]
+    AllNotesOffController,
+    /// send the CC 120 (all sound off) controller on all channels
+    AllSoundOffController,
+    /// release the sounding notes with NoteOn messages having a 0 velocity
+    NoteOnZeroVelocity,
+    /// send a NoteOff message for all the notes of all channels (2048 messages)
+    FullSweep,
+}
+
+impl AllNotesOffStrategy {
+    pub const ALL: [AllNotesOffStrategy; 4] = [
+        AllNotesOffStrategy::AllNotesOffController,
+        AllNotesOffStrategy::AllSoundOffController,
+        AllNotesOffStrategy::NoteOnZeroVelocity,
+        AllNotesOffStrategy::FullSweep,
+    ];
+
+    /// message sent to release a given note
+    pub fn release_note(&self, channel: u8, key: u8) -> MidiEvent {
+        let message = match self {
+            AllNotesOffStrategy::NoteOnZeroVelocity => MidiMessage::NoteOn {
+                key: u7::from(key),
+                vel: u7::from(0),
+            },
+            _ => MidiMessage::NoteOff {
+                key: u7::from(key),
+                vel: u7::from(0),
+            },
+        };
+        MidiEvent {
+            channel: u4::from(channel),
+            message,
+        }
+    }
+
+    /// messages sent to reset the output, when the sounding notes are not known
+    pub fn reset_events(&self) -> Vec<MidiEvent> {
+        let controller = |controller: u8| -> Vec<MidiEvent> {
+            (0..16)
+                .map(|channel: u8| MidiEvent {
+                    channel: u4::from(channel),
+                    message: MidiMessage::Controller {
+                        controller: u7::from(controller),
+                        value: u7::from(0),
+                    },
+                })
+                .collect()
+        };
+
+        match self {
+            AllNotesOffStrategy::AllNotesOffController => controller(123),
+            AllNotesOffStrategy::AllSoundOffController => controller(120),
+            // only the tracked notes are released
+            AllNotesOffStrategy::NoteOnZeroVelocity => vec![],
+            AllNotesOffStrategy::FullSweep => (0..16)
+                .flat_map(|channel: u8| {
+                    (0..=127).map(move |key: u8| MidiEvent {
+                        channel: u4::from(channel),
+                        message: MidiMessage::NoteOff {
+                            key: u7::from(key),
+                            vel: u7::from(127),
+                        },
+                    })
+                })
+                .collect(),
+        }
+    }
+}
+
+/// output device specific options
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutputProfile {
+    /// how the notes are stopped
+    pub all_notes_off: AllNotesOffStrategy,
+    /// how the master volume is applied
+    pub volume_mode: VolumeMode,
+}
+
+#[test]
+pub fn test_reset_events_size() {
+    assert_eq!(
+        AllNotesOffStrategy::AllNotesOffController
+            .reset_events()
+            .len(),
+        16
+    );
+    assert_eq!(AllNotesOffStrategy::FullSweep.reset_events().len(), 2048);
+    assert!(AllNotesOffStrategy::NoteOnZeroVelocity
+        .reset_events()
+        .is_empty());
+
+    let release = AllNotesOffStrategy::NoteOnZeroVelocity.release_note(2, 60);
+    assert_eq!(
+        release.message,
+        MidiMessage::NoteOn {
+            key: u7::from(60),
+            vel: u7::from(0)
+        }
+    );
+}
@@ -0,0 +1,150 @@
+//! seek in the sheet, the position is computed from the beginning of the file
+//! and the controllers state is chased, to be sent before playing from the new position
+
+use std::{collections::BTreeMap, time::Duration};
+
+use nodi::{
+    midly::{
+        num::{u4, u7},
+        MidiMessage, PitchBend,
+    },
+    Event, MidiEvent, Moment, Timer,
+};
+
+/// state of the channels (program, controllers, pitch bend), excluding the notes
+#[derive(Debug, Default)]
+pub struct ChasedState {
+    programs: BTreeMap<u8, u8>,
+    controllers: BTreeMap<(u8, u8), u8>,
+    pitch_bends: BTreeMap<u8, PitchBend>,
+}
+
+impl ChasedState {
+    /// update the state with a played event
+    pub fn update(&mut self, event: &MidiEvent) {
+        let channel = event.channel.as_int();
+        match event.message {
+            MidiMessage::ProgramChange { program } => {
+                self.programs.insert(channel, program.as_int());
+            }
+            MidiMessage::Controller { controller, value } => {
+                self.controllers
+                    .insert((channel, controller.as_int()), value.as_int());
+            }
+            MidiMessage::PitchBend { bend } => {
+                self.pitch_bends.insert(channel, bend);
+            }
+            _ => {}
+        }
+    }
+
+    /// events to send to restore the state
+    pub fn events(&self) -> Vec<MidiEvent> {
+        let programs = self.programs.iter().map(|(channel, program)| MidiEvent {
+            channel: u4::from(*channel),
+            message: MidiMessage::ProgramChange {
+                program: u7::from(*program),
+            },
+        });
+        let controllers = self
+            .controllers
+            .iter()
+            .map(|((channel, controller), value)| MidiEvent {
+                channel: u4::from(*channel),
+                message: MidiMessage::Controller {
+                    controller: u7::from(*controller),
+                    value: u7::from(*value),
+                },
+            });
+        let pitch_bends = self.pitch_bends.iter().map(|(channel, bend)| MidiEvent {
+            channel: u4::from(*channel),
+            message: MidiMessage::PitchBend { bend: *bend },
+        });
+        programs.chain(controllers).chain(pitch_bends).collect()
+    }
+}
+
+/// position in the sheet, to continue the play from
+#[derive(Debug)]
+pub struct SeekPosition<T: Timer> {
+    /// timer, with the tempo at the position
+    pub timer: T,
+    /// index of the next moment to play
+    pub moment_index: usize,
+    /// ticks elapsed since the last played moment
+    pub ticks_counter: u32,
+    /// play time of the last played moment
+    pub total_duration: Duration,
+    /// channels state at the position
+    pub chased: ChasedState,
+}
+
+/// compute the position for the given time, from the timer at the beginning of the file
+pub fn seek_position<T: Timer + Copy>(
+    moments: &[Moment],
+    initial_timer: T,
+    target: Duration,
+) -> SeekPosition<T> {
+    let mut timer = initial_timer;
+    let mut chased = ChasedState::default();
+    let mut ticks_counter = 0_u32;
+    let mut total_duration = Duration::ZERO;
+
+    for (moment_index, moment) in moments.iter().enumerate() {
+        if !moment.is_empty() {
+            let next_duration = total_duration + timer.sleep_duration(ticks_counter);
+            if next_duration >= target {
+                return SeekPosition {
+                    timer,
+                    moment_index,
+                    ticks_counter,
+                    total_duration,
+                    chased,
+                };
+            }
+            total_duration = next_duration;
+            ticks_counter = 0;
+
+            for event in &moment.events {
+                match event {
+                    Event::Tempo(val) => timer.change_tempo(*val),
+                    Event::Midi(msg) => chased.update(msg),
+                    _ => (),
+                }
+            }
+        }
+        ticks_counter += 1;
+    }
+
+    SeekPosition {
+        timer,
+        moment_index: moments.len(),
+        ticks_counter,
+        total_duration,
+        chased,
+    }
+}
+
+#[test]
+pub fn test_chased_state() {
+    let mut chased = ChasedState::default();
+    let controller = |value: u8| MidiEvent {
+        channel: u4::from(0),
+        message: MidiMessage::Controller {
+            controller: u7::from(7),
+            value: u7::from(value),
+        },
+    };
+    chased.update(&controller(100));
+    chased.update(&controller(80));
+    chased.update(&MidiEvent {
+        channel: u4::from(0),
+        message: MidiMessage::ProgramChange {
+            program: u7::from(19),
+        },
+    });
+
+    let events = chased.events();
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[1].message, controller(80).message);
+}
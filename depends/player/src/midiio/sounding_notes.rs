@@ -0,0 +1,115 @@
+//! keep track of the notes currently sounding on the output,
+//! this permit to release only the needed notes (pause, stop)
+
+use std::collections::BTreeMap;
+
+use nodi::{
+    midly::{
+        num::{u4, u7},
+        MidiMessage,
+    },
+    MidiEvent,
+};
+
+use super::output_profile::AllNotesOffStrategy;
+
+/// notes sounding on the output, with their velocity
+#[derive(Debug, Default, Clone)]
+pub struct SoundingNotes {
+    /// (channel, key) -> velocity
+    notes: BTreeMap<(u8, u8), u8>,
+}
+
+impl SoundingNotes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// update the state with a message sent on the output
+    pub fn update(&mut self, event: &MidiEvent) {
+        let channel = event.channel.as_int();
+        match event.message {
+            MidiMessage::NoteOn { key, vel } => {
+                if vel.as_int() == 0 {
+                    self.notes.remove(&(channel, key.as_int()));
+                } else {
+                    self.notes.insert((channel, key.as_int()), vel.as_int());
+                }
+            }
+            MidiMessage::NoteOff { key, .. } => {
+                self.notes.remove(&(channel, key.as_int()));
+            }
+            _ => {}
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.notes.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.notes.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.notes.clear();
+    }
+
+    /// events releasing all the sounding notes, using the given strategy
+    pub fn note_off_events(&self, strategy: &AllNotesOffStrategy) -> Vec<MidiEvent> {
+        self.notes
+            .keys()
+            .map(|(channel, key)| strategy.release_note(*channel, *key))
+            .collect()
+    }
+
+    /// note on events to strike again the sounding notes
+    pub fn note_on_events(&self) -> Vec<MidiEvent> {
+        self.notes
+            .iter()
+            .map(|((channel, key), vel)| MidiEvent {
+                channel: u4::from(*channel),
+                message: MidiMessage::NoteOn {
+                    key: u7::from(*key),
+                    vel: u7::from(*vel),
+                },
+            })
+            .collect()
+    }
+}
+
+#[test]
+pub fn test_sounding_notes_tracking() {
+    let mut sounding = SoundingNotes::new();
+    let on = |key: u8, vel: u8| MidiEvent {
+        channel: u4::from(1),
+        message: MidiMessage::NoteOn {
+            key: u7::from(key),
+            vel: u7::from(vel),
+        },
+    };
+
+    sounding.update(&on(60, 100));
+    sounding.update(&on(64, 90));
+    assert_eq!(sounding.len(), 2);
+
+    // note on with velocity 0 is a note off
+    sounding.update(&on(60, 0));
+    assert_eq!(sounding.len(), 1);
+
+    let release = sounding.note_off_events(&AllNotesOffStrategy::AllNotesOffController);
+    assert_eq!(release.len(), 1);
+
+    let restrike = sounding.note_on_events();
+    assert_eq!(restrike.len(), 1);
+    assert_eq!(restrike[0].message, on(64, 90).message);
+
+    sounding.update(&MidiEvent {
+        channel: u4::from(1),
+        message: MidiMessage::NoteOff {
+            key: u7::from(64),
+            vel: u7::from(64),
+        },
+    });
+    assert!(sounding.is_empty());
+}
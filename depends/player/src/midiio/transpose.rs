@@ -0,0 +1,68 @@
+//! transposition of the played notes
+
+use nodi::{
+    midly::{num::u7, MidiMessage},
+    MidiEvent,
+};
+
+/// channel of the percussions (channel 10 in the General Midi), not transposed
+const PERCUSSION_CHANNEL: u8 = 9;
+
+/// transpose the notes of the event, other messages are unchanged,
+/// None is returned if the transposed note is out of the midi range
+pub fn transpose_event(event: &MidiEvent, semitones: i8) -> Option<MidiEvent> {
+    if semitones == 0 || event.channel.as_int() == PERCUSSION_CHANNEL {
+        return Some(*event);
+    }
+
+    let transpose_key = |key: u7| -> Option<u7> {
+        let transposed = key.as_int() as i16 + semitones as i16;
+        (0..=127)
+            .contains(&transposed)
+            .then(|| u7::from(transposed as u8))
+    };
+
+    let message = match event.message {
+        MidiMessage::NoteOn { key, vel } => MidiMessage::NoteOn {
+            key: transpose_key(key)?,
+            vel,
+        },
+        MidiMessage::NoteOff { key, vel } => MidiMessage::NoteOff {
+            key: transpose_key(key)?,
+            vel,
+        },
+        MidiMessage::Aftertouch { key, vel } => MidiMessage::Aftertouch {
+            key: transpose_key(key)?,
+            vel,
+        },
+        m => m,
+    };
+
+    Some(MidiEvent {
+        channel: event.channel,
+        message,
+    })
+}
+
+#[test]
+pub fn test_transpose_event() {
+    use nodi::midly::num::u4;
+
+    let note_on = |channel: u8, key: u8| MidiEvent {
+        channel: u4::from(channel),
+        message: MidiMessage::NoteOn {
+            key: u7::from(key),
+            vel: u7::from(100),
+        },
+    };
+
+    let transposed = transpose_event(&note_on(0, 60), -2).unwrap();
+    assert_eq!(transposed.message, note_on(0, 58).message);
+
+    // percussions are not transposed
+    let percussion = transpose_event(&note_on(9, 36), 5).unwrap();
+    assert_eq!(percussion.message, note_on(9, 36).message);
+
+    // out of range
+    assert!(transpose_event(&note_on(0, 126), 3).is_none());
+}
@@ -0,0 +1,148 @@
+//! master volume of the output, applied on the channel volume controller (CC 7),
+//! or on the notes velocity for the devices that ignore the CC 7
+
+use serde::{Deserialize, Serialize};
+
+use nodi::{
+    midly::{
+        num::{u4, u7},
+        MidiMessage,
+    },
+    MidiEvent,
+};
+
+/// channel volume controller number
+const CHANNEL_VOLUME_CONTROLLER: u8 = 7;
+
+/// channel volume until the file defines it (General Midi default)
+const DEFAULT_CHANNEL_VOLUME: u8 = 100;
+
+/// smaller volume changes are not sent
+const VOLUME_STEP: f32 = 0.01;
+
+/// how the master volume is applied
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VolumeMode {
+    /// the channel volume controller (CC 7) of the file is scaled
+    #[default]
+    ChannelVolume,
+    /// the velocity of the notes is scaled, for the devices ignoring CC 7,
+    /// the volume changes (and the fade out) only apply to the next struck notes,
+    /// the notes already sounding keep their volume
+    Velocity,
+}
+
+impl VolumeMode {
+    pub const ALL: [VolumeMode; 2] = [VolumeMode::ChannelVolume, VolumeMode::Velocity];
+}
+
+/// scale the played events with the master volume
+#[derive(Debug, Clone)]
+pub struct VolumeScaler {
+    mode: VolumeMode,
+    /// master volume, between 0 and 1
+    volume: f32,
+    /// channel volumes defined by the file
+    channel_volumes: [u8; 16],
+}
+
+impl VolumeScaler {
+    pub fn new(mode: VolumeMode, volume: f32) -> Self {
+        VolumeScaler {
+            mode,
+            volume: volume.clamp(0.0, 1.0),
+            channel_volumes: [DEFAULT_CHANNEL_VOLUME; 16],
+        }
+    }
+
+    fn scale(&self, value: u8) -> u8 {
+        (value as f32 * self.volume).round().clamp(0.0, 127.0) as u8
+    }
+
+    /// event scaled with the master volume, the channel volumes of the file are tracked
+    pub fn apply(&mut self, event: &MidiEvent) -> MidiEvent {
+        let message = match (self.mode, event.message) {
+            (VolumeMode::ChannelVolume, MidiMessage::Controller { controller, value })
+                if controller.as_int() == CHANNEL_VOLUME_CONTROLLER =>
+            {
+                self.channel_volumes[event.channel.as_int() as usize] = value.as_int();
+                MidiMessage::Controller {
+                    controller,
+                    value: u7::from(self.scale(value.as_int())),
+                }
+            }
+            // a 0 velocity is a note off, kept as is
+            (VolumeMode::Velocity, MidiMessage::NoteOn { key, vel }) if vel.as_int() > 0 => {
+                MidiMessage::NoteOn {
+                    key,
+                    vel: u7::from(self.scale(vel.as_int()).max(1)),
+                }
+            }
+            (_, message) => message,
+        };
+        MidiEvent {
+            channel: event.channel,
+            message,
+        }
+    }
+
+    /// change the master volume, the returned events apply it on the channels
+    pub fn set_volume(&mut self, volume: f32) -> Vec<MidiEvent> {
+        let volume = volume.clamp(0.0, 1.0);
+        if volume == self.volume || (volume > 0.0 && (volume - self.volume).abs() < VOLUME_STEP) {
+            return vec![];
+        }
+        self.volume = volume;
+        self.volume_events()
+    }
+
+    /// channel volume events of all the channels, none is needed in velocity mode
+    pub fn volume_events(&self) -> Vec<MidiEvent> {
+        match self.mode {
+            VolumeMode::Velocity => vec![],
+            VolumeMode::ChannelVolume => (0..16_u8)
+                .map(|channel| MidiEvent {
+                    channel: u4::from(channel),
+                    message: MidiMessage::Controller {
+                        controller: u7::from(CHANNEL_VOLUME_CONTROLLER),
+                        value: u7::from(self.scale(self.channel_volumes[channel as usize])),
+                    },
+                })
+                .collect(),
+        }
+    }
+}
+
+#[test]
+pub fn test_volume_scaler() {
+    let controller = |value: u8| MidiEvent {
+        channel: u4::from(2),
+        message: MidiMessage::Controller {
+            controller: u7::from(CHANNEL_VOLUME_CONTROLLER),
+            value: u7::from(value),
+        },
+    };
+    let note_on = |vel: u8| MidiEvent {
+        channel: u4::from(2),
+        message: MidiMessage::NoteOn {
+            key: u7::from(60),
+            vel: u7::from(vel),
+        },
+    };
+
+    let mut scaler = VolumeScaler::new(VolumeMode::ChannelVolume, 0.5);
+    assert_eq!(scaler.apply(&controller(120)), controller(60));
+    assert_eq!(scaler.apply(&note_on(100)), note_on(100));
+    // the file channel volume is scaled on volume change
+    let events = scaler.set_volume(0.25);
+    assert_eq!(events.len(), 16);
+    assert_eq!(events[2], controller(30));
+    assert!(scaler.set_volume(0.251).is_empty());
+
+    let mut scaler = VolumeScaler::new(VolumeMode::Velocity, 0.5);
+    assert_eq!(scaler.apply(&note_on(100)), note_on(50));
+    assert_eq!(scaler.apply(&note_on(0)), note_on(0));
+    assert_eq!(scaler.apply(&controller(120)), controller(120));
+    assert!(scaler.set_volume(0.0).is_empty());
+    assert_eq!(scaler.apply(&note_on(100)), note_on(1));
+}
@@ -13,7 +13,10 @@ use std::{
 
 #[test]
 pub fn test_player_informations() {
-    let f = MidiPlayerFactory { device_no: 0 };
+    let f = MidiPlayerFactory {
+        device_no: 0,
+        ..Default::default()
+    };
 
     let mut getter = f
         .create_information_getter()
@@ -28,7 +31,10 @@ pub fn test_player_informations() {
 
 #[test]
 pub fn test_player() {
-    let f = MidiPlayerFactory { device_no: 1 };
+    let f = MidiPlayerFactory {
+        device_no: 1,
+        ..Default::default()
+    };
 
     let (sender, receiver) = channel();
     let (sendercmd, receivercmd) = channel();
@@ -58,7 +64,10 @@ pub fn test_player() {
 
 #[test]
 pub fn test_player_1() {
-    let f = MidiPlayerFactory { device_no: 0 };
+    let f = MidiPlayerFactory {
+        device_no: 0,
+        ..Default::default()
+    };
 
     let (sender, receiver) = channel();
     let (sendercmd, receivercmd) = channel();
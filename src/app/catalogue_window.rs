@@ -0,0 +1,94 @@
+use egui::*;
+
+use crate::VirtualBookApp;
+
+use super::screen_playlist;
+
+/// Render the confirmation of the file chosen by its catalogue number
+pub(crate) fn render_catalogue_confirmation(app: &mut VirtualBookApp, ctx: &egui::Context) {
+    let Some(entry) = app.catalogue.confirmation.clone() else {
+        return;
+    };
+
+    let i18n = &app.i18n;
+    let mut confirmed = false;
+    let mut cancelled = false;
+    egui::Window::new(format!(
+        "{} {}",
+        egui_phosphor::regular::LIST_NUMBERS,
+        i18n.catalogue_confirm
+    ))
+    .id(Id::new("catalogue_confirmation"))
+    .collapsible(false)
+    .resizable(false)
+    .anchor(Align2::CENTER_CENTER, Vec2::ZERO)
+    .show(ctx, |ui| {
+        ui.label(
+            RichText::new(format!("{} {}", i18n.catalogue_number, entry.number))
+                .font(FontId::proportional(20.0)),
+        );
+        ui.label(RichText::new(entry.title()).font(FontId::proportional(30.0)));
+        ui.separator();
+        ui.horizontal(|ui| {
+            if ui
+                .button(format!("{} {}", egui_phosphor::regular::CHECK, i18n.add))
+                .clicked()
+            {
+                confirmed = true;
+            }
+            if ui
+                .button(format!("{} {}", egui_phosphor::regular::X, i18n.cancel))
+                .clicked()
+            {
+                cancelled = true;
+            }
+        });
+    });
+
+    if confirmed {
+        app.catalogue.confirmation = None;
+        screen_playlist::add_to_playlist(&mut app.appplayer, &entry.path);
+        app.current_typed_no = "".into();
+    } else if cancelled {
+        app.catalogue.confirmation = None;
+    }
+}
+
+/// Render the result of the catalogue export
+pub(crate) fn render_catalogue_export_window(app: &mut VirtualBookApp, ctx: &egui::Context) {
+    if !app.catalogue_export_window_open {
+        return;
+    }
+
+    let i18n = &app.i18n;
+    let mut open = true;
+    egui::Window::new(format!(
+        "{} {}",
+        egui_phosphor::regular::PRINTER,
+        i18n.export_catalogue
+    ))
+    .id(Id::new("catalogue_export"))
+    .open(&mut open)
+    .collapsible(false)
+    .show(ctx, |ui| match &app.catalogue_export {
+        None => {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label(&i18n.exporting_catalogue);
+            });
+        }
+        Some(Ok(files)) => {
+            ui.label(&i18n.catalogue_exported);
+            for file in files {
+                ui.label(file.to_string_lossy());
+            }
+        }
+        Some(Err(e)) => {
+            ui.label(format!("{} : {}", i18n.catalogue_export_failed, e));
+        }
+    });
+
+    if !open {
+        app.catalogue_export_window_open = false;
+    }
+}
@@ -0,0 +1,53 @@
+use egui::*;
+
+use crate::VirtualBookApp;
+
+/// Render the report of the files having the same content
+pub(crate) fn render_duplicates_window(app: &mut VirtualBookApp, ctx: &egui::Context) {
+    if !app.duplicates_window_open {
+        return;
+    }
+
+    let i18n = &app.i18n;
+    let base_path = app.file_store_path.clone().unwrap_or_default();
+    let mut open = true;
+    egui::Window::new(format!(
+        "{} {}",
+        egui_phosphor::regular::COPY,
+        i18n.duplicate_files
+    ))
+    .id(Id::new("duplicates"))
+    .open(&mut open)
+    .collapsible(false)
+    .show(ctx, |ui| match &app.duplicates {
+        None => {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label(&i18n.searching_duplicates);
+            });
+        }
+        Some((groups, unchecked)) => {
+            if *unchecked > 0 {
+                ui.label(format!("{} {}", unchecked, i18n.files_not_checked_yet));
+                ui.separator();
+            }
+            if groups.is_empty() {
+                ui.label(&i18n.no_duplicate_files);
+            }
+            ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                for group in groups {
+                    ui.group(|ui| {
+                        for path in group {
+                            let displayed = path.strip_prefix(&base_path).unwrap_or(path);
+                            ui.label(displayed.to_string_lossy());
+                        }
+                    });
+                }
+            });
+        }
+    });
+
+    if !open {
+        app.duplicates_window_open = false;
+    }
+}
@@ -0,0 +1,213 @@
+use std::{
+    path::PathBuf,
+    sync::mpsc::{channel, Receiver, TryRecvError},
+    thread,
+};
+
+use egui::*;
+use log::error;
+use player::{
+    midiio::{general_midi, MidiFileInformationsConstructor},
+    ChannelInformations, FileInformations, FileInformationsConstructor,
+};
+
+use crate::{
+    duration_to_mm_ss,
+    playmetadata::{PlayMetadataDatabase, METADATA_DATABASE_FILE},
+    VirtualBookApp,
+};
+
+use super::i18n::I18NMessages;
+
+/// file whose details are displayed, the informations are computed in background
+pub(crate) struct FileDetails {
+    path: PathBuf,
+    informations: Option<FileInformations>,
+    job: Option<Receiver<Option<FileInformations>>>,
+}
+
+impl FileDetails {
+    /// get the computed informations, if the background computation is done
+    fn poll(&mut self) {
+        let Some(job) = &self.job else {
+            return;
+        };
+        match job.try_recv() {
+            Ok(informations) => {
+                self.informations = informations;
+                self.job = None;
+            }
+            Err(TryRecvError::Empty) => {}
+            Err(TryRecvError::Disconnected) => self.job = None,
+        }
+    }
+}
+
+/// informations of the file, read from the cache of the library (if any) or computed
+fn file_informations(
+    library_folder: Option<PathBuf>,
+    file: &PathBuf,
+) -> Result<FileInformations, Box<dyn std::error::Error>> {
+    let mut info_getter = MidiFileInformationsConstructor {};
+    let Some(folder) = library_folder else {
+        return info_getter.compute(file);
+    };
+    let database = PlayMetadataDatabase::new(
+        folder
+            .join(METADATA_DATABASE_FILE)
+            .to_string_lossy()
+            .to_string(),
+    )?;
+    database.cached_file_informations(&folder, file, &mut info_getter)
+}
+
+/// Display the details of a file, the known informations are used if they are complete
+/// (the playlists only store the duration of their files)
+pub(crate) fn show_file_details(
+    app: &mut VirtualBookApp,
+    path: PathBuf,
+    known: Option<FileInformations>,
+) {
+    let informations = known.filter(|i| i.note_range.is_some());
+    let mut job = None;
+    if informations.is_none() {
+        let (sender, receiver) = channel();
+        let file = path.clone();
+        let library_folder = app.file_store.as_ref().map(|s| s.base_path.clone());
+        let spawned = thread::Builder::new()
+            .name("file-details".to_string())
+            .spawn(move || {
+                let informations = file_informations(library_folder, &file)
+                    .map_err(|e| error!("fail to compute {:?} informations : {}", file, e))
+                    .ok();
+                // the details may have been closed
+                let _ = sender.send(informations);
+            });
+        match spawned {
+            Ok(_) => job = Some(receiver),
+            Err(e) => error!("fail to start the informations computation : {}", e),
+        }
+    }
+
+    app.file_details = Some(FileDetails {
+        path,
+        informations,
+        job,
+    });
+}
+
+fn channel_label(channel: &ChannelInformations, i18n: &I18NMessages) -> String {
+    let instrument = match channel.program {
+        _ if channel.is_percussion() => i18n.percussion.clone(),
+        Some(program) => general_midi::program_name(program).to_string(),
+        None => i18n.default_instrument.clone(),
+    };
+    format!("{:>2} : {}", channel.channel + 1, instrument)
+}
+
+/// Render the rows of the informations grid
+fn render_informations(ui: &mut Ui, informations: &FileInformations, i18n: &I18NMessages) {
+    let mut row = |label: &str, value: Option<String>| {
+        if let Some(value) = value {
+            ui.label(label);
+            ui.label(value);
+            ui.end_row();
+        }
+    };
+
+    row(&i18n.details_title, informations.title.clone());
+    row(
+        &i18n.details_duration,
+        informations.duration.map(|d| duration_to_mm_ss(&d)),
+    );
+    row(
+        &i18n.details_tempo,
+        informations.bpm().map(|bpm| format!("{:.0} bpm", bpm)),
+    );
+    row(
+        &i18n.details_time_signature,
+        informations
+            .time_signature
+            .map(|(numerator, denominator)| format!("{}/{}", numerator, denominator)),
+    );
+    row(
+        &i18n.details_note_range,
+        informations.note_range.map(|(low, high)| {
+            format!(
+                "{} - {}",
+                general_midi::note_name(low),
+                general_midi::note_name(high)
+            )
+        }),
+    );
+    row(
+        &i18n.details_polyphony,
+        (informations.polyphony_peak > 0).then(|| informations.polyphony_peak.to_string()),
+    );
+    row(&i18n.details_scale, informations.scale_name.clone());
+    row(
+        &i18n.details_track_count,
+        informations.track_count.map(|c| c.to_string()),
+    );
+}
+
+/// Render the details pane of the selected file, on the right of the playlist screen
+pub(crate) fn render_file_details_panel(app: &mut VirtualBookApp, ui: &mut Ui) {
+    let Some(details) = &mut app.file_details else {
+        return;
+    };
+    details.poll();
+
+    let i18n = &app.i18n;
+    let mut close = false;
+    egui::SidePanel::right("details_panel")
+        .resizable(true)
+        .default_width(220.0)
+        .show_inside(ui, |ui| {
+            ui.horizontal(|ui| {
+                if ui
+                    .button(egui_phosphor::regular::X)
+                    .on_hover_text_at_pointer(&i18n.close_file_details)
+                    .clicked()
+                {
+                    close = true;
+                }
+                let name = details
+                    .path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                ui.strong(name);
+            });
+            ui.separator();
+
+            egui::ScrollArea::vertical().show(ui, |ui| match &details.informations {
+                Some(informations) => {
+                    Grid::new("file_details").num_columns(2).show(ui, |ui| {
+                        render_informations(ui, informations, i18n);
+                    });
+
+                    if !informations.channels.is_empty() {
+                        ui.separator();
+                        ui.label(&i18n.details_channels);
+                        for channel in &informations.channels {
+                            ui.label(channel_label(channel, i18n));
+                        }
+                    }
+                }
+                None if details.job.is_some() => {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label(&i18n.computing_file_details);
+                    });
+                }
+                None => {
+                    ui.label(&i18n.file_details_unavailable);
+                }
+            });
+        });
+
+    if close {
+        app.file_details = None;
+    }
+}
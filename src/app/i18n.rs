@@ -30,6 +30,116 @@ pub struct I18NMessages {
     pub star_file_tooltip: String,
     pub star_count_tooltip: String,
     pub panel_title: String,
+    pub pause_resume: String,
+    pub restrike_notes_on_resume: String,
+    pub all_notes_off_strategy: String,
+    pub all_notes_off_controller: String,
+    pub all_sound_off_controller: String,
+    pub note_on_zero_velocity: String,
+    pub full_notes_sweep: String,
+    pub sections: String,
+    pub loop_edit: String,
+    pub clear_loop: String,
+    pub play_mode: String,
+    pub play_mode_once: String,
+    pub play_mode_repeat_all: String,
+    pub play_mode_repeat_one: String,
+    pub play_mode_shuffle: String,
+    pub go_to_previous_file: String,
+    pub replay_last: String,
+    pub history: String,
+    pub replay_history_entry: String,
+    pub playlist_missing_entries: String,
+    pub playlist_entries_found_by_name: String,
+    pub close: String,
+    pub entry_settings: String,
+    pub entry_wait: String,
+    pub entry_speed: String,
+    pub entry_transpose: String,
+    pub entry_start_offset: String,
+    pub entry_end_offset: String,
+    pub entry_output: String,
+    pub default_output: String,
+    pub add_special_entry: String,
+    pub add_pause: String,
+    pub add_text_card: String,
+    pub text_card_hint: String,
+    pub text_card_duration: String,
+    pub add_stop_marker: String,
+    pub pause_entry: String,
+    pub stop_marker: String,
+    pub resume_queue: String,
+    pub resume_queue_from_start: String,
+    pub resume_queue_at: String,
+    pub discard_queue: String,
+    pub schedule: String,
+    pub schedule_enabled: String,
+    pub add_program: String,
+    pub remove_program: String,
+    pub program_repeat_every: String,
+    pub program_playlist: String,
+    pub program_file: String,
+    pub program_attract_loop: String,
+    pub program_path_hint: String,
+    pub closing_time: String,
+    pub next_scheduled_event: String,
+    pub generate_playlist: String,
+    pub generating_playlist: String,
+    pub generator_target_duration: String,
+    pub generator_favour_starred: String,
+    pub generator_skip_played_within: String,
+    pub days: String,
+    pub master_volume: String,
+    pub fade_out: String,
+    pub volume_mode: String,
+    pub volume_channel_controller: String,
+    pub volume_velocity: String,
+    pub show_file_details: String,
+    pub right_click_for_details: String,
+    pub close_file_details: String,
+    pub computing_file_details: String,
+    pub file_details_unavailable: String,
+    pub details_title: String,
+    pub details_duration: String,
+    pub details_tempo: String,
+    pub details_time_signature: String,
+    pub details_note_range: String,
+    pub details_polyphony: String,
+    pub details_scale: String,
+    pub details_track_count: String,
+    pub details_channels: String,
+    pub percussion: String,
+    pub default_instrument: String,
+    pub duplicate_files: String,
+    pub searching_duplicates: String,
+    pub no_duplicate_files: String,
+    pub files_not_checked_yet: String,
+    pub scanning_library: String,
+    pub files_count: String,
+    pub stop_scan: String,
+    pub catalogue_mode: String,
+    pub catalogue_mode_tooltip: String,
+    pub catalogue_not_found: String,
+    pub catalogue_ambiguous: String,
+    pub catalogue_confirm: String,
+    pub catalogue_number: String,
+    pub add: String,
+    pub cancel: String,
+    pub catalogue: String,
+    pub catalogue_piece: String,
+    pub catalogue_stars: String,
+    pub export_catalogue: String,
+    pub catalogue_empty: String,
+    pub exporting_catalogue: String,
+    pub catalogue_exported: String,
+    pub catalogue_export_failed: String,
+    pub sort_by_name: String,
+    pub sort_by_play_count: String,
+    pub sort_by_stars: String,
+    pub sort_by_last_played: String,
+    pub sort_by_duration: String,
+    pub sort_by_date_added: String,
+    pub group_by_scale: String,
 }
 
 fn _create_i18n_message_with_lang(language: Option<String>) -> Box<I18NMessages> {
@@ -105,6 +215,117 @@ pub fn create_i18n_message() -> Box<I18NMessages> {
         star_file_tooltip: "Star this file".into(),
         star_count_tooltip: "Number of times this file has been starred".into(),
         panel_title: "playlist".into(),
+        pause_resume: "Pause / Resume the play".into(),
+        restrike_notes_on_resume: "Strike again the held notes on resume".into(),
+        all_notes_off_strategy: "Stopping the notes".into(),
+        all_notes_off_controller: "All notes off (CC 123)".into(),
+        all_sound_off_controller: "All sound off (CC 120)".into(),
+        note_on_zero_velocity: "Note on with velocity 0".into(),
+        full_notes_sweep: "Note off on all notes (2048 messages)".into(),
+        sections: "Sections".into(),
+        loop_edit: "Tap the book to set the loop start and end".into(),
+        clear_loop: "Remove the loop".into(),
+        play_mode: "Play mode".into(),
+        play_mode_once: "Play once".into(),
+        play_mode_repeat_all: "Repeat the playlist".into(),
+        play_mode_repeat_one: "Repeat the current file".into(),
+        play_mode_shuffle: "Shuffle".into(),
+        go_to_previous_file: "Go back to the previous file".into(),
+        replay_last: "Replay the current file, or the last played one".into(),
+        history: "History".into(),
+        replay_history_entry: "Play this file again".into(),
+        playlist_missing_entries: "Files of the playlist not found".into(),
+        playlist_entries_found_by_name: "Files found in the library by their name".into(),
+        close: "Close".into(),
+        entry_settings: "Play settings of this file".into(),
+        entry_wait: "Wait before the play".into(),
+        entry_speed: "Speed".into(),
+        entry_transpose: "Transposition (semitones)".into(),
+        entry_start_offset: "Start at".into(),
+        entry_end_offset: "End at".into(),
+        entry_output: "Output".into(),
+        default_output: "Selected output".into(),
+        add_special_entry: "Add a pause, a text card or a stop marker".into(),
+        add_pause: "Add a pause".into(),
+        add_text_card: "Add a text card".into(),
+        text_card_hint: "Text displayed full screen".into(),
+        text_card_duration: "Display time".into(),
+        add_stop_marker: "Add a stop marker".into(),
+        pause_entry: "Pause".into(),
+        stop_marker: "Stop".into(),
+        resume_queue: "Resume the previous queue ?".into(),
+        resume_queue_from_start: "Resume".into(),
+        resume_queue_at: "Resume at".into(),
+        discard_queue: "Discard".into(),
+        schedule: "Schedule".into(),
+        schedule_enabled: "Start the programs at their time".into(),
+        add_program: "Add a program".into(),
+        remove_program: "Remove the program".into(),
+        program_repeat_every: "every".into(),
+        program_playlist: "Playlist".into(),
+        program_file: "File".into(),
+        program_attract_loop: "Attract loop".into(),
+        program_path_hint: "Path in the library folder".into(),
+        closing_time: "Closing time".into(),
+        next_scheduled_event: "Next scheduled event".into(),
+        generate_playlist: "Generate a playlist".into(),
+        generating_playlist: "Generating the playlist".into(),
+        generator_target_duration: "Duration".into(),
+        generator_favour_starred: "Favour the starred files".into(),
+        generator_skip_played_within: "Skip the files played in the last".into(),
+        days: "days".into(),
+        master_volume: "Volume".into(),
+        fade_out: "Fade out on stop or next (seconds)".into(),
+        volume_mode: "Volume control".into(),
+        volume_channel_controller: "Channel volume (CC 7)".into(),
+        volume_velocity: "Notes velocity".into(),
+        show_file_details: "File details".into(),
+        right_click_for_details: "Right click for the file details".into(),
+        close_file_details: "Close the details".into(),
+        computing_file_details: "Reading the file ...".into(),
+        file_details_unavailable: "No information available for this file".into(),
+        details_title: "Title".into(),
+        details_duration: "Duration".into(),
+        details_tempo: "Tempo".into(),
+        details_time_signature: "Time signature".into(),
+        details_note_range: "Note range".into(),
+        details_polyphony: "Max polyphony".into(),
+        details_scale: "Scale".into(),
+        details_track_count: "Tracks".into(),
+        details_channels: "Channels and instruments".into(),
+        percussion: "Percussion".into(),
+        default_instrument: "Default instrument".into(),
+        duplicate_files: "Duplicate files".into(),
+        searching_duplicates: "Searching the duplicate files ...".into(),
+        no_duplicate_files: "No duplicate file found".into(),
+        files_not_checked_yet:
+            "files are not checked yet, search again later for a complete report".into(),
+        scanning_library: "Scanning the library :".into(),
+        files_count: "files".into(),
+        stop_scan: "Stop the scan".into(),
+        catalogue_mode: "Catalogue mode (exact numbers)".into(),
+        catalogue_mode_tooltip: "The typed number selects the file numbered in its name (\"12 - ...\") or in the catalogue.txt file of the library".into(),
+        catalogue_not_found: "not in the catalogue".into(),
+        catalogue_ambiguous: "several files have this number".into(),
+        catalogue_confirm: "Add this piece ?".into(),
+        catalogue_number: "No".into(),
+        add: "Add".into(),
+        cancel: "Cancel".into(),
+        catalogue: "Catalogue".into(),
+        catalogue_piece: "Piece".into(),
+        catalogue_stars: "Stars".into(),
+        export_catalogue: "Export the catalogue".into(),
+        catalogue_empty: "No numbered file in the library".into(),
+        exporting_catalogue: "Reading the durations and the stars ...".into(),
+        catalogue_exported: "The catalogue has been written :".into(),
+        catalogue_export_failed: "Fail to write the catalogue".into(),
+        sort_by_name: "Name".into(),
+        sort_by_play_count: "Most played".into(),
+        sort_by_stars: "Most starred".into(),
+        sort_by_last_played: "Last played".into(),
+        sort_by_duration: "Shortest".into(),
+        sort_by_date_added: "Last added".into(),
+        group_by_scale: "All the files, grouped by instrument scale".into(),
     })
 }
 
@@ -141,5 +362,116 @@ pub fn create_i18n_fr_message() -> Box<I18NMessages> {
         star_file_tooltip: "Mettre une étoile à ce fichier".into(),
         star_count_tooltip: "Nombre de fois que ce fichier a été étoilé".into(),
         panel_title: "Liste de lecture".into(),
+        pause_resume: "Mettre en pause / Reprendre le jeu".into(),
+        restrike_notes_on_resume: "Rejouer les notes tenues à la reprise".into(),
+        all_notes_off_strategy: "Arrêt des notes".into(),
+        all_notes_off_controller: "Toutes notes éteintes (CC 123)".into(),
+        all_sound_off_controller: "Tous sons éteints (CC 120)".into(),
+        note_on_zero_velocity: "Note on avec vélocité 0".into(),
+        full_notes_sweep: "Note off sur toutes les notes (2048 messages)".into(),
+        sections: "Parties".into(),
+        loop_edit: "Toucher le carton pour définir le début et la fin de la boucle".into(),
+        clear_loop: "Supprimer la boucle".into(),
+        play_mode: "Mode de lecture".into(),
+        play_mode_once: "Jouer une fois".into(),
+        play_mode_repeat_all: "Répéter la liste de lecture".into(),
+        play_mode_repeat_one: "Répéter le fichier en cours".into(),
+        play_mode_shuffle: "Lecture aléatoire".into(),
+        go_to_previous_file: "Revenir au fichier précédent".into(),
+        replay_last: "Rejouer le fichier en cours, ou le dernier joué".into(),
+        history: "Historique".into(),
+        replay_history_entry: "Rejouer ce fichier".into(),
+        playlist_missing_entries: "Fichiers de la liste de lecture non trouvés".into(),
+        playlist_entries_found_by_name: "Fichiers retrouvés dans la bibliothèque par leur nom"
+            .into(),
+        close: "Fermer".into(),
+        entry_settings: "Réglages de jeu de ce fichier".into(),
+        entry_wait: "Attente avant le jeu".into(),
+        entry_speed: "Vitesse".into(),
+        entry_transpose: "Transposition (demi-tons)".into(),
+        entry_start_offset: "Commencer à".into(),
+        entry_end_offset: "Terminer à".into(),
+        entry_output: "Sortie".into(),
+        default_output: "Sortie sélectionnée".into(),
+        add_special_entry: "Ajouter une pause, un carton ou un arrêt".into(),
+        add_pause: "Ajouter une pause".into(),
+        add_text_card: "Ajouter un carton".into(),
+        text_card_hint: "Texte affiché en plein écran".into(),
+        text_card_duration: "Durée d'affichage".into(),
+        add_stop_marker: "Ajouter un arrêt".into(),
+        pause_entry: "Pause".into(),
+        stop_marker: "Arrêt".into(),
+        resume_queue: "Reprendre la liste précédente ?".into(),
+        resume_queue_from_start: "Reprendre".into(),
+        resume_queue_at: "Reprendre à".into(),
+        discard_queue: "Abandonner".into(),
+        schedule: "Programmation".into(),
+        schedule_enabled: "Démarrer les programmes à leur horaire".into(),
+        add_program: "Ajouter un programme".into(),
+        remove_program: "Supprimer le programme".into(),
+        program_repeat_every: "toutes les".into(),
+        program_playlist: "Liste de lecture".into(),
+        program_file: "Fichier".into(),
+        program_attract_loop: "Boucle de démonstration".into(),
+        program_path_hint: "Chemin dans le dossier de la bibliothèque".into(),
+        closing_time: "Heure de fermeture".into(),
+        next_scheduled_event: "Prochain événement programmé".into(),
+        generate_playlist: "Générer une liste de lecture".into(),
+        generating_playlist: "Génération de la liste de lecture".into(),
+        generator_target_duration: "Durée".into(),
+        generator_favour_starred: "Privilégier les fichiers étoilés".into(),
+        generator_skip_played_within: "Ignorer les fichiers joués depuis".into(),
+        days: "jours".into(),
+        master_volume: "Volume".into(),
+        fade_out: "Fondu à l'arrêt ou au suivant (secondes)".into(),
+        volume_mode: "Contrôle du volume".into(),
+        volume_channel_controller: "Volume des canaux (CC 7)".into(),
+        volume_velocity: "Vélocité des notes".into(),
+        show_file_details: "Détails du fichier".into(),
+        right_click_for_details: "Clic droit pour les détails du fichier".into(),
+        close_file_details: "Fermer les détails".into(),
+        computing_file_details: "Lecture du fichier ...".into(),
+        file_details_unavailable: "Aucune information disponible pour ce fichier".into(),
+        details_title: "Titre".into(),
+        details_duration: "Durée".into(),
+        details_tempo: "Tempo".into(),
+        details_time_signature: "Signature rythmique".into(),
+        details_note_range: "Tessiture".into(),
+        details_polyphony: "Polyphonie max".into(),
+        details_scale: "Gamme".into(),
+        details_track_count: "Pistes".into(),
+        details_channels: "Canaux et instruments".into(),
+        percussion: "Percussions".into(),
+        default_instrument: "Instrument par défaut".into(),
+        duplicate_files: "Fichiers en double".into(),
+        searching_duplicates: "Recherche des fichiers en double ...".into(),
+        no_duplicate_files: "Aucun fichier en double".into(),
+        files_not_checked_yet: "fichiers ne sont pas encore vérifiés, relancez la recherche plus tard pour un rapport complet".into(),
+        scanning_library: "Analyse de la bibliothèque :".into(),
+        files_count: "fichiers".into(),
+        stop_scan: "Arrêter l'analyse".into(),
+        catalogue_mode: "Mode catalogue (numéros exacts)".into(),
+        catalogue_mode_tooltip: "Le numéro tapé choisit le fichier numéroté dans son nom (\"12 - ...\") ou dans le fichier catalogue.txt de la bibliothèque".into(),
+        catalogue_not_found: "absent du catalogue".into(),
+        catalogue_ambiguous: "plusieurs fichiers ont ce numéro".into(),
+        catalogue_confirm: "Ajouter ce morceau ?".into(),
+        catalogue_number: "N°".into(),
+        add: "Ajouter".into(),
+        cancel: "Annuler".into(),
+        catalogue: "Catalogue".into(),
+        catalogue_piece: "Morceau".into(),
+        catalogue_stars: "Étoiles".into(),
+        export_catalogue: "Exporter le catalogue".into(),
+        catalogue_empty: "Aucun fichier numéroté dans la bibliothèque".into(),
+        exporting_catalogue: "Lecture des durées et des étoiles ...".into(),
+        catalogue_exported: "Le catalogue a été écrit :".into(),
+        catalogue_export_failed: "Échec de l'écriture du catalogue".into(),
+        sort_by_name: "Nom".into(),
+        sort_by_play_count: "Les plus joués".into(),
+        sort_by_stars: "Les plus étoilés".into(),
+        sort_by_last_played: "Joués récemment".into(),
+        sort_by_duration: "Les plus courts".into(),
+        sort_by_date_added: "Ajoutés récemment".into(),
+        group_by_scale: "Tous les fichiers, groupés par gamme d'instrument".into(),
     })
 }
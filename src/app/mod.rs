@@ -1,6 +1,7 @@
+use std::collections::HashMap;
 use std::path::PathBuf;
-use std::sync::mpsc::channel;
-use std::time::{Duration, Instant};
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::time::{Duration, Instant, SystemTime};
 
 use chrono::{DateTime, Local};
 use egui::epaint::*;
@@ -10,11 +11,20 @@ use egui_extras::image::*;
 use egui_extras::{Size, StripBuilder};
 use im_native_dialog::ImNativeFileDialog;
 
-use player::midiio::{DeviceInformation, MidiPlayerFactory};
+use player::midiio::{
+    AllNotesOffStrategy, DeviceInformation, MidiPlayerFactory, OutputProfile, VolumeMode,
+};
 use player::{PlayerFactory, Response};
 
 use crate::appplayer::AppPlayer;
-use crate::playmetadata_manager::PlayMetadataManager;
+use crate::catalogue::{self, Catalogue, CatalogueHeadings, CatalogueSelection, EntryDetails};
+use crate::library_watcher::LibraryWatcher;
+use crate::playlist::{is_playlist_file, PlayMode, SavedQueue, PLAYLIST_EXTENSIONS};
+use crate::playlist_generator::GeneratorSettings;
+use crate::playmetadata::METADATA_DATABASE_FILE;
+use crate::playmetadata_manager::{MetadataResult, PlayMetadataManager};
+use crate::scheduler::{ProgramKind, Schedule, ScheduledAction};
+use crate::search_index::LibrarySearch;
 use crate::{duration_to_mm_ss, file_store::*};
 
 use log::{debug, error, info, warn};
@@ -23,10 +33,26 @@ use self::i18n::{create_i18n_message_with_lang, I18NMessages};
 
 use pid_lite::Controller;
 
+mod catalogue_window;
+mod duplicates_window;
+mod file_details;
 mod i18n;
+mod schedule_window;
 mod screen_playlist;
 mod screen_visu;
 
+/// time difference between the displayed and played position, considered as a jump in the file
+const JUMP_DISPLAY_THRESHOLD_MS: f64 = 2_000.0;
+
+/// storage key of the play queue, saved apart from the application state
+const SAVED_QUEUE_KEY: &str = "saved_queue";
+
+/// interval between the state (and queue) saves
+const AUTO_SAVE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// interval between two refreshes of the views, while the library is scanned
+const SCAN_REFRESH_INTERVAL: Duration = Duration::from_millis(500);
+
 /// activated screen
 #[derive(PartialEq)]
 enum Screen {
@@ -64,6 +90,31 @@ pub struct VirtualBookApp {
     #[serde(skip)]
     file_store: Option<FileStore>,
 
+    /// scan of the library folder, running in background
+    #[serde(skip)]
+    scan_job: Option<ScanJob>,
+    #[serde(skip)]
+    scan_refreshed_at: Instant,
+
+    /// sort and grouping of the file tree
+    view_order: ViewOrder,
+    /// the metadata used by the sort have changed, the views must be sorted again
+    #[serde(skip)]
+    views_outdated: bool,
+    #[serde(skip)]
+    views_refreshed_at: Instant,
+
+    /// report the changes in the library folder, to update the file store
+    #[serde(skip)]
+    library_watcher: Option<LibraryWatcher>,
+
+    /// search index of the library files, used by the filter
+    #[serde(skip)]
+    search: LibrarySearch,
+
+    /// the number pad selects the files by their catalogue number
+    catalogue: CatalogueSelection,
+
     #[serde(skip)]
     extensions_filters: Option<Vec<String>>,
 
@@ -136,6 +187,75 @@ pub struct VirtualBookApp {
     /// Currently playing file path and its play metadata
     #[serde(skip)]
     current_playing_file: Option<(PathBuf, Option<u32>)>, // (path, play_count)
+
+    /// strike again the held notes when resuming from pause
+    restrike_notes_on_resume: bool,
+
+    /// master volume of the output, between 0 and 1
+    master_volume: f32,
+
+    /// fade out time when the play is stopped or skipped, in seconds
+    fade_out_seconds: f32,
+
+    /// output profiles, by midi device name
+    output_profiles: HashMap<String, OutputProfile>,
+
+    /// taps on the book display define the loop points
+    #[serde(skip)]
+    loop_edit: bool,
+
+    /// how the playlist continues after a file
+    play_mode: PlayMode,
+
+    /// playlist entry whose settings are edited (by added time)
+    #[serde(skip)]
+    edited_entry: Option<SystemTime>,
+
+    /// special entries to add in the playlist
+    #[serde(skip)]
+    new_pause: Duration,
+    #[serde(skip)]
+    new_card_text: String,
+    #[serde(skip)]
+    new_card_duration: Option<Duration>,
+
+    /// queue of the previous session, proposed for resume at startup
+    #[serde(skip)]
+    pending_resume: Option<SavedQueue>,
+
+    /// programs started at given times of day
+    schedule: Schedule,
+    /// last time the schedule has been checked
+    #[serde(skip)]
+    schedule_checked_at: Option<chrono::NaiveDateTime>,
+    #[serde(skip)]
+    schedule_window_open: bool,
+
+    /// rules of the playlist generator
+    generator_settings: GeneratorSettings,
+    /// playlist generation running in background
+    #[serde(skip)]
+    generator_job: Option<Receiver<Vec<PathBuf>>>,
+
+    /// file displayed in the details pane
+    #[serde(skip)]
+    file_details: Option<file_details::FileDetails>,
+
+    /// duplicate files report, with the number of files not checked yet
+    #[serde(skip)]
+    duplicates: Option<(Vec<Vec<PathBuf>>, usize)>,
+    #[serde(skip)]
+    duplicates_window_open: bool,
+
+    /// folder where the catalogue is exported, chosen by the user
+    catalogue_export_folder: Option<PathBuf>,
+    #[serde(skip)]
+    catalogue_export_dialog: ImNativeFileDialog<Option<PathBuf>>,
+    /// exported catalogue files, or the export error
+    #[serde(skip)]
+    catalogue_export: Option<Result<Vec<PathBuf>, String>>,
+    #[serde(skip)]
+    catalogue_export_window_open: bool,
 }
 
 impl Default for VirtualBookApp {
@@ -165,6 +285,14 @@ impl Default for VirtualBookApp {
             file_path_dialog: im_native_dialog::ImNativeFileDialog::default(),
             file_store_path: None,
             file_store: None,
+            scan_job: None,
+            scan_refreshed_at: Instant::now(),
+            view_order: ViewOrder::default(),
+            views_outdated: false,
+            views_refreshed_at: Instant::now(),
+            library_watcher: None,
+            search: LibrarySearch::default(),
+            catalogue: CatalogueSelection::default(),
 
             bg_image: img,
             texture_handle: None,
@@ -189,7 +317,11 @@ impl Default for VirtualBookApp {
 
             hidden_number_pad: false,
 
-            extensions_filters: Some(vec![".mid".into(), ".book".into(), ".playlist".into()]),
+            extensions_filters: Some({
+                let mut filters = player::midiio::formats::registry().extensions_filters();
+                filters.extend(PLAYLIST_EXTENSIONS.iter().map(|e| format!(".{}", e)));
+                filters
+            }),
 
             play_wait: 2.0,
 
@@ -198,6 +330,29 @@ impl Default for VirtualBookApp {
             metadata_manager: PlayMetadataManager::new(),
             metadata_query_frame_counter: 0,
             current_playing_file: None,
+            restrike_notes_on_resume: true,
+            master_volume: 1.0,
+            fade_out_seconds: 2.0,
+            output_profiles: HashMap::new(),
+            loop_edit: false,
+            play_mode: PlayMode::default(),
+            edited_entry: None,
+            new_pause: Duration::from_secs(30),
+            new_card_text: String::new(),
+            new_card_duration: None,
+            pending_resume: None,
+            schedule: Schedule::default(),
+            schedule_checked_at: None,
+            schedule_window_open: false,
+            generator_settings: GeneratorSettings::default(),
+            generator_job: None,
+            file_details: None,
+            duplicates: None,
+            duplicates_window_open: false,
+            catalogue_export_folder: None,
+            catalogue_export_dialog: ImNativeFileDialog::default(),
+            catalogue_export: None,
+            catalogue_export_window_open: false,
         }
     }
 }
@@ -279,60 +434,103 @@ impl VirtualBookApp {
         ctx.set_fonts(fonts);
     }
 
-    /// Restore MIDI device from saved state
-    fn restore_midi_device(app: &mut Self) {
+    /// name of the selected MIDI device, if known
+    fn selected_device_name(&mut self) -> Option<String> {
+        if self.current_devices.is_empty() {
+            if let Ok(devices) = MidiPlayerFactory::list_all_devices() {
+                self.current_devices = devices;
+            }
+        }
+        self.current_devices
+            .iter()
+            .find(|d| d.no == self.selected_device)
+            .map(|d| d.name.clone())
+    }
+
+    /// Open the selected MIDI device, and give the player to the appplayer
+    fn open_selected_midi_device(&mut self) {
+        let name = self.selected_device_name();
+        if self.open_midi_device(self.selected_device, name.clone()) {
+            self.appplayer.default_output_name = name;
+        }
+    }
+
+    /// open the midi device for the play, returns true if opened
+    fn open_midi_device(&mut self, device_no: usize, name: Option<String>) -> bool {
+        let output_profile = name
+            .as_ref()
+            .and_then(|name| self.output_profiles.get(name).cloned())
+            .unwrap_or_default();
+
         let factory = MidiPlayerFactory {
-            device_no: app.selected_device,
+            device_no,
+            restrike_on_resume: self.restrike_notes_on_resume,
+            output_profile,
+            fade_out: Duration::from_secs_f32(self.fade_out_seconds),
         };
 
         let (_scmd, rcmd) = channel();
-        let (s, r) = channel();
+        let (s, player_event_receiver) = channel();
 
         match factory.create(s, rcmd) {
             Ok(player) => {
-                app.appplayer.player(Some((player, r, _scmd)));
+                // change the player
+                self.appplayer
+                    .player(Some((player, player_event_receiver, _scmd)));
+                self.appplayer.set_volume(self.master_volume);
+                self.appplayer.output_name = name;
+                true
             }
             Err(e) => {
                 error!("fail to open device {}", e);
+                false
+            }
+        }
+    }
+
+    /// open the output asked by the playlist entry, then start the play
+    fn handle_output_request(&mut self) {
+        let Some(name) = self.appplayer.output_request.take() else {
+            return;
+        };
+
+        // the device list may have changed since the last listing
+        if let Ok(devices) = MidiPlayerFactory::list_all_devices() {
+            self.current_devices = devices;
+        }
+        let device_no = self
+            .current_devices
+            .iter()
+            .find(|d| d.name == name)
+            .map(|d| d.no);
+
+        match device_no {
+            Some(no) if self.open_midi_device(no, Some(name.clone())) => {
+                info!("output changed to {}", name);
+                self.appplayer.play_file_on_top();
+            }
+            _ => {
+                warn!("output {} not available, play on the current output", name);
+                self.appplayer.play_file_on_top_on_current_output();
             }
         }
     }
 
+    /// Restore MIDI device from saved state
+    fn restore_midi_device(app: &mut Self) {
+        app.open_selected_midi_device();
+    }
+
     /// Restore file store from saved state
     fn restore_file_store(app: &mut Self) {
         if let Some(path) = &app.file_store_path {
             // Initialize metadata database when restoring
-            let db_path = PathBuf::from(path).join(".playmetadata.db");
+            let db_path = PathBuf::from(path).join(METADATA_DATABASE_FILE);
             info!("Restoring metadata database for folder: {:?}", path);
             app.metadata_manager.set_database_path(db_path);
+            app.appplayer.set_library_folder(Some(path.clone()));
 
-            match FileStore::new(&PathBuf::from(path)) {
-                Ok(mut storage_created) => {
-                    if storage_created.is_some() {
-                        if let Some(mut fs) = storage_created {
-                            if let Ok(v) = fs.view(&None, &app.extensions_filters) {
-                                fs.default_view = Some(v);
-
-                                // Immediately query play counts for visible files (don't wait for periodic query)
-                                let visible_paths = Self::collect_visible_file_paths(&fs);
-                                if !visible_paths.is_empty() {
-                                    debug!("Immediately querying play counts for {} visible files (on restore)", visible_paths.len());
-                                    app.metadata_manager
-                                        .query_play_counts(visible_paths, Vec::new());
-                                } else {
-                                    warn!("No visible files to query on restore - file tree may not be ready yet");
-                                }
-                            }
-                            storage_created = Some(fs);
-                        }
-                    }
-                    app.file_store = storage_created;
-                }
-                Err(e) => {
-                    error!("error in opening the path {}", &e);
-                    app.file_store = None;
-                }
-            }
+            app.open_file_store();
         }
     }
 
@@ -353,6 +551,17 @@ impl VirtualBookApp {
             .appplayer
             .set_waittime_between_file_play(old_storage.play_wait);
 
+        old_storage
+            .appplayer
+            .playlist
+            .lock()
+            .expect("fail to lock playlist")
+            .set_play_mode(old_storage.play_mode);
+
+        // the queue of the previous session is proposed for resume
+        old_storage.pending_resume =
+            eframe::get_value::<SavedQueue>(storage, SAVED_QUEUE_KEY).filter(|q| q.is_resumable());
+
         Some(old_storage)
     }
 
@@ -475,7 +684,9 @@ impl VirtualBookApp {
 
     /// Update player state and PID controller
     fn update_player_state(&mut self) {
+        // the play time is frozen while paused
         if self.appplayer.is_playing()
+            && !self.appplayer.is_paused()
             && self.adjusted_start_time + Duration::from_millis(100) < Instant::now()
         {
             let delta = Instant::now().duration_since(self.adjusted_start_time);
@@ -493,6 +704,13 @@ impl VirtualBookApp {
 
     /// Handle player response messages
     fn handle_player_responses(&mut self) {
+        // pauses and text cards of the playlist
+        self.appplayer.update_special_entry();
+        if self.appplayer.text_card_started {
+            self.appplayer.text_card_started = false;
+            self.screen = Screen::Display;
+        }
+
         let response = {
             if let Ok(mut opt_last_response) = self.appplayer.last_response.lock() {
                 opt_last_response.take()
@@ -506,7 +724,7 @@ impl VirtualBookApp {
                 Response::EndOfFile => {
                     // Clear current playing file when file ends
                     self.current_playing_file = None;
-                    self.appplayer.next();
+                    self.appplayer.file_ended();
                 }
                 Response::CurrentPlayTime(duration) => {
                     self.latest_duration_time = *duration;
@@ -515,10 +733,15 @@ impl VirtualBookApp {
                     // depending on the midi control, some may have a
                     // time shift
                     // accordingly,
-                    self.pid_controller.set_target(
-                        ((*duration).as_micros() as f64 + self.play_lattency_ms as f64 * 1000.0)
-                            / 1000.0,
-                    );
+                    let target = ((*duration).as_micros() as f64
+                        + self.play_lattency_ms as f64 * 1000.0)
+                        / 1000.0;
+                    self.pid_controller.set_target(target);
+
+                    // jump in the file (seek), the display is moved directly
+                    if (target - self.pid_regulated_offset_ms).abs() > JUMP_DISPLAY_THRESHOLD_MS {
+                        self.pid_regulated_offset_ms = target;
+                    }
                 }
                 Response::FileCancelled => {
                     // Clear current playing file when cancelled
@@ -551,6 +774,76 @@ impl VirtualBookApp {
         }
     }
 
+    /// Run the scheduled programs, the events missed while the application was closed are not run
+    fn handle_schedule(&mut self) {
+        let now = Local::now().naive_local();
+        let Some(last_check) = self.schedule_checked_at.replace(now) else {
+            return;
+        };
+
+        let Some(event) = self.schedule.due_event(last_check, now) else {
+            return;
+        };
+
+        match event.action {
+            ScheduledAction::Close => {
+                info!("closing time, the play is stopped");
+                self.appplayer.stop();
+                self.appplayer.play_mod = false;
+                self.appplayer.end_program();
+            }
+            ScheduledAction::Start(index) => {
+                let Some(program) = self.schedule.programs.get(index) else {
+                    return;
+                };
+                let path = match &self.file_store_path {
+                    Some(folder) => folder.join(&program.path),
+                    None => program.path.clone(),
+                };
+                if !path.exists() {
+                    warn!("scheduled program {} not found", path.display());
+                    return;
+                }
+
+                // a single file program plays only that file
+                let single_file = program.kind == ProgramKind::File;
+                if single_file && is_playlist_file(&path) {
+                    warn!("scheduled file {} is a playlist", path.display());
+                    return;
+                }
+                info!("start the scheduled program {}", path.display());
+                // the play mode of the operator is kept, and restored after the program
+                self.appplayer
+                    .play_program(&path, program.kind.play_mode(), single_file);
+            }
+        }
+    }
+
+    /// Add the generated playlist, once the generation is done
+    fn handle_generator_result(&mut self) {
+        let Some(job) = &self.generator_job else {
+            return;
+        };
+        match job.try_recv() {
+            Ok(files) => {
+                let mut locked_playlist = self
+                    .appplayer
+                    .playlist
+                    .lock()
+                    .expect("fail to lock playlist");
+                for file in &files {
+                    locked_playlist.add_from_path(file);
+                }
+                self.generator_job = None;
+            }
+            Err(TryRecvError::Empty) => {}
+            Err(TryRecvError::Disconnected) => {
+                error!("the playlist generation has failed");
+                self.generator_job = None;
+            }
+        }
+    }
+
     /// Handle file dialog responses
     fn handle_file_dialog(&mut self) {
         if let Some(Ok(result)) = self.file_path_dialog.check() {
@@ -558,58 +851,59 @@ impl VirtualBookApp {
             if let Some(r) = result {
                 // Initialize metadata database in the selected folder
                 // Database file will be created in the folder: .playmetadata.db
-                let db_path = r.join(".playmetadata.db");
+                let db_path = r.join(METADATA_DATABASE_FILE);
                 info!("Initializing metadata database for folder: {:?}", r);
                 self.metadata_manager.set_database_path(db_path);
+                self.appplayer.set_library_folder(Some(r.clone()));
 
-                match FileStore::new(&r) {
-                    Ok(fs) => {
-                        self.file_store = fs;
-                        debug!("folder opened, reapply the elements and views");
-                        // refilter the view using the filters
-                        if let Some(store) = &mut self.file_store {
-                            if let Ok(result) = store.view(&None, &self.extensions_filters) {
-                                store.default_view = Some(result);
-
-                                // Wait a bit for database to initialize, then query play counts
-                                // Database initialization happens asynchronously in background thread
-                                // We'll query on next frame or use periodic query
-                                debug!("File store created, will query play counts after database initialization");
-
-                                // Immediately query play counts for visible files (don't wait for periodic query)
-                                // Note: Database might not be ready yet, but query will be queued
-                                let visible_paths = Self::collect_visible_file_paths(store);
-                                if !visible_paths.is_empty() {
-                                    debug!(
-                                        "Immediately querying play counts for {} visible files",
-                                        visible_paths.len()
-                                    );
-                                    self.metadata_manager
-                                        .query_play_counts(visible_paths, Vec::new());
-                                } else {
-                                    warn!("No visible files collected after folder open!");
-                                }
-                            } else {
-                                warn!("Failed to create view for file store");
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        error!(
-                            "fail to create file store : {} \n with path {:?}",
-                            e,
-                            r.clone()
-                        )
-                    }
-                }
+                self.open_file_store();
             }
         }
+
+        if let Some(Ok(Some(folder))) = self.catalogue_export_dialog.check() {
+            info!("exporting the catalogue in {:?}", folder);
+            self.catalogue_export_folder = Some(folder);
+            self.catalogue_export = None;
+            self.catalogue_export_window_open = true;
+            let paths = self.catalogue.catalogue.entries().map(|e| e.path).collect();
+            self.metadata_manager.load_catalogue_details(paths);
+        }
     }
 
     /// Update metadata - query play counts and star counts for displayed files and process results
     fn update_metadata(&mut self) {
         // Process any results from background thread
-        if let Some(file_metadata) = self.metadata_manager.process_results() {
+        let file_metadata = match self.metadata_manager.process_results() {
+            Some(MetadataResult::PlayCounts(file_metadata)) => Some(file_metadata),
+            Some(MetadataResult::Informations(informations)) => {
+                if let Some(file_store) = &self.file_store {
+                    file_store.set_informations(&informations);
+                }
+                if self.view_order.sort == SortMode::Duration || self.view_order.group_by_scale {
+                    self.views_outdated = true;
+                }
+                for (path, computed) in &informations {
+                    self.search.index.set_informations(path, computed);
+                }
+                None
+            }
+            Some(MetadataResult::Comments(comments)) => {
+                for (path, comment) in comments {
+                    self.search.index.set_comment(&path, comment);
+                }
+                None
+            }
+            Some(MetadataResult::Duplicates(groups, unchecked)) => {
+                self.duplicates = Some((groups, unchecked));
+                None
+            }
+            Some(MetadataResult::CatalogueDetails(details)) => {
+                self.export_catalogue(&details);
+                None
+            }
+            None => None,
+        };
+        if let Some(file_metadata) = file_metadata {
             debug!(
                 "Received {} metadata results from background thread",
                 file_metadata.len()
@@ -640,6 +934,9 @@ impl VirtualBookApp {
                 );
                 if updated_count > 0 {
                     debug!("Updated {} FileNode metadata in root", updated_count);
+                    if self.view_order.sort.uses_play_statistics() {
+                        self.views_outdated = true;
+                    }
                 }
 
                 // Also update filtered view if it exists
@@ -749,7 +1046,7 @@ impl VirtualBookApp {
                 warn!("No visible files collected - checking view structure");
                 // Debug: check if view has any children
                 let view_node = view.root.borrow();
-                let file_node = view_node.node.borrow();
+                let file_node = view_node.node.read_node();
                 debug!(
                     "View root: is_folder={}, path={:?}, has {} children",
                     file_node.is_folder,
@@ -780,7 +1077,7 @@ impl VirtualBookApp {
         node: &std::rc::Rc<std::cell::RefCell<crate::file_store::FileViewNode>>,
     ) {
         let view_node = node.borrow();
-        let file_node = view_node.node.borrow();
+        let file_node = view_node.node.read_node();
 
         if !file_node.is_folder {
             paths.push(file_node.path.clone());
@@ -802,7 +1099,7 @@ impl VirtualBookApp {
         is_root: bool,
     ) {
         let view_node = node.borrow();
-        let file_node = view_node.node.borrow();
+        let file_node = view_node.node.read_node();
         let is_expanded = view_node.expanded;
 
         // Collect files if:
@@ -857,24 +1154,242 @@ impl VirtualBookApp {
         }
     }
 
+    /// Open the library folder, the tree is filled by a background scan
+    fn open_file_store(&mut self) {
+        let Some(path) = self.file_store_path.clone() else {
+            return;
+        };
+        match FileStore::scan(&path) {
+            Ok((mut file_store, scan_job)) => {
+                file_store.order = self.view_order;
+                self.file_store = Some(file_store);
+                self.scan_job = Some(scan_job);
+                self.scan_refreshed_at = Instant::now();
+                self.search = LibrarySearch::default();
+                self.metadata_manager.load_comments();
+                self.watch_library();
+            }
+            Err(e) => {
+                error!("fail to create file store : {} \n with path {:?}", e, &path);
+                self.file_store = None;
+                self.scan_job = None;
+                self.library_watcher = None;
+            }
+        }
+    }
+
+    /// Follow the background scan of the library, the views are refreshed while the tree
+    /// is filled, the informations of the files are computed once the scan is finished
+    fn handle_library_scan(&mut self) {
+        let Some(scan_job) = &self.scan_job else {
+            return;
+        };
+        let finished = scan_job.control.is_finished();
+        if !finished && self.scan_refreshed_at.elapsed() < SCAN_REFRESH_INTERVAL {
+            return;
+        }
+        self.scan_refreshed_at = Instant::now();
+
+        let Some(file_store) = &mut self.file_store else {
+            return;
+        };
+        self.search.index.sync_with_store(file_store);
+        let ranking = self.search.ranking(&self.current_typed_no);
+        file_store.refresh_views(&ranking, &self.extensions_filters);
+
+        if finished {
+            self.scan_job = None;
+            let visible_paths = Self::collect_visible_file_paths(file_store);
+            if !visible_paths.is_empty() {
+                debug!(
+                    "Immediately querying play counts for {} visible files (after scan)",
+                    visible_paths.len()
+                );
+                self.metadata_manager
+                    .query_play_counts(visible_paths, Vec::new());
+            }
+            self.scan_library_files();
+            self.load_catalogue();
+            self.query_sorted_play_counts();
+        }
+    }
+
+    /// Change the sort and grouping of the file tree
+    pub(crate) fn set_view_order(&mut self, order: ViewOrder) {
+        self.view_order = order;
+        if let Some(file_store) = &mut self.file_store {
+            file_store.order = order;
+            let ranking = self.search.ranking(&self.current_typed_no);
+            file_store.refresh_views(&ranking, &self.extensions_filters);
+        }
+        self.query_sorted_play_counts();
+    }
+
+    /// The play counts are queried for the displayed files only, the sort by play
+    /// statistics needs them for all the files
+    fn query_sorted_play_counts(&self) {
+        if !self.view_order.sort.uses_play_statistics() {
+            return;
+        }
+        if let Some(file_store) = &self.file_store {
+            self.metadata_manager
+                .query_play_counts(file_store.files(), Vec::new());
+        }
+    }
+
+    /// Sort the views again when the metadata used by the sort have changed,
+    /// not more than once per refresh interval, to keep the tree readable
+    fn handle_outdated_views(&mut self) {
+        if !self.views_outdated
+            || self.scan_job.is_some()
+            || self.views_refreshed_at.elapsed() < SCAN_REFRESH_INTERVAL
+        {
+            return;
+        }
+        self.views_outdated = false;
+        self.views_refreshed_at = Instant::now();
+        if let Some(file_store) = &mut self.file_store {
+            let ranking = self.search.ranking(&self.current_typed_no);
+            file_store.refresh_views(&ranking, &self.extensions_filters);
+        }
+    }
+
+    /// Write the printable catalogue in the chosen folder
+    fn export_catalogue(&mut self, details: &HashMap<PathBuf, EntryDetails>) {
+        let (Some(file_store), Some(folder)) = (&self.file_store, &self.catalogue_export_folder)
+        else {
+            return;
+        };
+        let headings = CatalogueHeadings {
+            title: &self.i18n.catalogue,
+            number: &self.i18n.catalogue_number,
+            piece: &self.i18n.catalogue_piece,
+            duration: &self.i18n.details_duration,
+            stars: &self.i18n.catalogue_stars,
+        };
+        let lines = self
+            .catalogue
+            .catalogue
+            .lines(&file_store.base_path, details);
+        let result = catalogue::export(folder, &headings, &lines);
+        if let Err(e) = &result {
+            error!("fail to export the catalogue : {}", e);
+        }
+        self.catalogue_export = Some(result.map_err(|e| e.to_string()));
+    }
+
+    /// Number the files of the library, from their name or the catalogue file
+    fn load_catalogue(&mut self) {
+        self.catalogue.confirmation = None;
+        self.catalogue.catalogue = match &self.file_store {
+            Some(file_store) => {
+                let files: Vec<PathBuf> = file_store
+                    .files()
+                    .into_iter()
+                    .filter(|f| accept_extension(&f.to_string_lossy(), &self.extensions_filters))
+                    .collect();
+                Catalogue::load(&file_store.base_path, &files)
+            }
+            None => Catalogue::default(),
+        };
+    }
+
+    /// Compute in background the informations (durations ...) and checksums
+    /// of all the files of the library
+    fn scan_library_files(&self) {
+        if let Some(file_store) = &self.file_store {
+            self.scan_files(file_store.files());
+        }
+    }
+
+    /// Compute in background the informations and checksums of the given files
+    fn scan_files(&self, files: Vec<PathBuf>) {
+        let files: Vec<PathBuf> = files
+            .into_iter()
+            .filter(|f| {
+                player::midiio::formats::registry()
+                    .find_by_extension(f)
+                    .is_some()
+            })
+            .collect();
+        if files.is_empty() {
+            return;
+        }
+        self.metadata_manager.compute_informations(files.clone());
+        self.metadata_manager.compute_checksums(files);
+    }
+
+    /// Watch the library folder, the file store follows the changes made on the disk
+    fn watch_library(&mut self) {
+        self.library_watcher = match (&self.file_store, &self.file_store_path) {
+            (Some(_), Some(path)) => LibraryWatcher::start(path)
+                .map_err(|e| error!("fail to watch the library {:?} : {}", path, e))
+                .ok(),
+            _ => None,
+        };
+    }
+
+    /// Update the file store with the files added, removed or modified in the library folder,
+    /// the views keep their expanded and selected elements
+    fn handle_library_changes(&mut self) {
+        // the changes made during the scan are applied once it is finished
+        if self.scan_job.is_some() {
+            return;
+        }
+        let Some(watcher) = &mut self.library_watcher else {
+            return;
+        };
+        let changed_paths = watcher.changes();
+        if changed_paths.is_empty() {
+            return;
+        }
+        let Some(file_store) = &mut self.file_store else {
+            return;
+        };
+
+        let changes = file_store.apply_changes(&changed_paths);
+        let catalogue_changed = changes.tree_changed
+            || changed_paths
+                .iter()
+                .any(|p| *p == file_store.base_path.join(catalogue::CATALOGUE_FILE));
+        if changes.tree_changed {
+            info!("the library has changed, updating the views");
+            self.search.index.sync_with_store(file_store);
+            let ranking = self.search.ranking(&self.current_typed_no);
+            file_store.refresh_views(&ranking, &self.extensions_filters);
+        }
+        if !changes.updated_files.is_empty() {
+            self.metadata_manager
+                .query_play_counts(changes.updated_files.clone(), Vec::new());
+            self.scan_files(changes.updated_files);
+        }
+        // the numbers only depend on the file names and the catalogue file
+        if catalogue_changed {
+            self.load_catalogue();
+        }
+    }
+
     /// Recursively update FileNode metadata (play counts and star counts) with debug tracking
     fn update_file_node_metadata_with_debug(
-        node: &mut std::rc::Rc<std::cell::RefCell<crate::file_store::FileNode>>,
+        node: &mut SharedFileNode,
         file_metadata: &std::collections::HashMap<
             PathBuf,
             crate::playmetadata_manager::FileMetadata,
         >,
         updated_count: &mut usize,
     ) {
-        let mut file_node = node.borrow_mut();
+        let mut file_node = node.write_node();
         if !file_node.is_folder {
             if let Some(metadata) = file_metadata.get(&file_node.path) {
                 let old_play_count = file_node.play_count;
                 let old_star_count = file_node.star_count;
+                let old_last_played = file_node.last_played;
                 file_node.play_count = Some(metadata.play_count);
                 file_node.star_count = Some(metadata.star_count);
+                file_node.last_played = metadata.last_played;
                 if old_play_count != Some(metadata.play_count)
                     || old_star_count != Some(metadata.star_count)
+                    || old_last_played != metadata.last_played
                 {
                     debug!(
                         "Updated FileNode metadata: {:?} -> play={} (was {:?}), star={} (was {:?})",
@@ -919,16 +1434,19 @@ impl VirtualBookApp {
         updated_count: &mut usize,
     ) {
         let mut view_node = node.borrow_mut();
-        let mut file_node = view_node.node.borrow_mut();
+        let mut file_node = view_node.node.write_node();
 
         if !file_node.is_folder {
             if let Some(metadata) = file_metadata.get(&file_node.path) {
                 let old_play_count = file_node.play_count;
                 let old_star_count = file_node.star_count;
+                let old_last_played = file_node.last_played;
                 file_node.play_count = Some(metadata.play_count);
                 file_node.star_count = Some(metadata.star_count);
+                file_node.last_played = metadata.last_played;
                 if old_play_count != Some(metadata.play_count)
                     || old_star_count != Some(metadata.star_count)
+                    || old_last_played != metadata.last_played
                 {
                     debug!("Updated FileViewNode metadata: {:?} -> play={} (was {:?}), star={} (was {:?})", 
                         file_node.path, metadata.play_count, old_play_count, metadata.star_count, old_star_count);
@@ -988,26 +1506,48 @@ impl VirtualBookApp {
             ))
             .clicked()
         {
-            if let Some(current_path) = &self.file_store_path {
-                let new_filestore = FileStore::new(current_path);
-                if let Ok(new_store) = new_filestore {
-                    self.file_store = new_store.map(|mut fs| {
-                        if let Ok(v) = fs.view(&None, &self.extensions_filters) {
-                                                fs.default_view = Some(v);
-
-                            // Immediately query play counts for visible files (don't wait for periodic query)
-                            let visible_paths = Self::collect_visible_file_paths(&fs);
-                            if !visible_paths.is_empty() {
-                                debug!("Immediately querying play counts for {} visible files (on reload)", visible_paths.len());
-                                self.metadata_manager.query_play_counts(visible_paths, Vec::new());
-                            }
-                                            }
-                                            fs
-                                        });
-                }
-            }
+            self.open_file_store();
             ui.close_menu();
         };
+        if ui
+            .add_enabled(
+                self.file_store.is_some(),
+                Button::new(format!(
+                    "{} {}",
+                    egui_phosphor::regular::COPY,
+                    &self.i18n.duplicate_files
+                )),
+            )
+            .clicked()
+        {
+            self.duplicates = None;
+            self.duplicates_window_open = true;
+            self.metadata_manager.find_duplicates();
+            ui.close_menu();
+        }
+        if ui
+            .add_enabled(
+                !self.catalogue.catalogue.is_empty(),
+                Button::new(format!(
+                    "{} {}",
+                    egui_phosphor::regular::PRINTER,
+                    &self.i18n.export_catalogue
+                )),
+            )
+            .on_disabled_hover_text(&self.i18n.catalogue_empty)
+            .clicked()
+        {
+            // the last export folder, or the library folder
+            let location = self
+                .catalogue_export_folder
+                .clone()
+                .filter(|folder| folder.is_dir())
+                .or_else(|| self.file_store_path.clone());
+            if let Err(e) = self.catalogue_export_dialog.open_single_dir(location) {
+                error!("fail to open the catalogue folder dialog : {}", e);
+            }
+            ui.close_menu();
+        }
     }
 
     /// Render preferences menu
@@ -1022,6 +1562,14 @@ impl VirtualBookApp {
         };
 
         ui.checkbox(&mut self.hidden_number_pad, &self.i18n.hide_num_pad);
+        if ui
+            .checkbox(&mut self.catalogue.enabled, &self.i18n.catalogue_mode)
+            .on_hover_text_at_pointer(&self.i18n.catalogue_mode_tooltip)
+            .changed()
+        {
+            self.catalogue.confirmation = None;
+            self.current_typed_no = "".into();
+        }
         ui.checkbox(&mut self.islight, &self.i18n.dark_light);
         ui.label(&self.i18n.time_between_file);
         let time_slider = egui::Slider::new(&mut self.play_wait, 0.0..=30.0);
@@ -1049,12 +1597,63 @@ impl VirtualBookApp {
         ui.label(&self.i18n.lattence_jeu);
         let play_lattency_slider = egui::Slider::new(&mut self.play_lattency_ms, -1000..=4_000);
         ui.add(play_lattency_slider);
+
+        ui.separator();
+
+        if ui
+            .checkbox(
+                &mut self.restrike_notes_on_resume,
+                &self.i18n.restrike_notes_on_resume,
+            )
+            .changed()
+        {
+            self.appplayer
+                .set_restrike_on_resume(self.restrike_notes_on_resume);
+        }
+
+        ui.label(&self.i18n.fade_out);
+        if ui
+            .add(egui::Slider::new(&mut self.fade_out_seconds, 0.0..=10.0).suffix(" s"))
+            .changed()
+        {
+            self.appplayer
+                .set_fade_out(Duration::from_secs_f32(self.fade_out_seconds));
+        }
+    }
+
+    /// Render the master volume control
+    fn render_volume_menu(&mut self, ui: &mut egui::Ui) {
+        let icon = if self.master_volume <= 0.0 {
+            egui_phosphor::regular::SPEAKER_X
+        } else if self.master_volume < 0.5 {
+            egui_phosphor::regular::SPEAKER_LOW
+        } else {
+            egui_phosphor::regular::SPEAKER_HIGH
+        };
+        ui.menu_button(icon, |ui| {
+            if ui
+                .add(
+                    egui::Slider::new(&mut self.master_volume, 0.0..=1.0)
+                        .vertical()
+                        .show_value(false),
+                )
+                .changed()
+            {
+                self.appplayer.set_volume(self.master_volume);
+            }
+        })
+        .response
+        .on_hover_text_at_pointer(format!(
+            "{} {:.0} %",
+            self.i18n.master_volume,
+            self.master_volume * 100.0
+        ));
     }
 
     /// Render MIDI device selection
     fn render_midi_devices(&mut self, ui: &mut egui::Ui) {
         ui.label("midi out interfaces");
-        for device in &self.current_devices {
+        for device in self.current_devices.clone() {
             let selected = self.selected_device == device.no;
             if ui.radio(selected, &device.label).clicked() {
                 if let Some(_old_player) = &self.appplayer.player {}
@@ -1062,26 +1661,158 @@ impl VirtualBookApp {
                 println!("Open the device");
                 self.selected_device = device.no;
 
-                let factory = MidiPlayerFactory {
-                    device_no: self.selected_device,
-                };
+                self.open_selected_midi_device();
+
+                ui.close_menu();
+            }
+        }
 
-                let (_scmd, rcmd) = channel();
-                let (s, player_event_receiver) = channel();
+        self.render_output_profile(ui);
+    }
 
-                match factory.create(s, rcmd) {
-                    Ok(player) => {
-                        // change the player
-                        self.appplayer
-                            .player(Some((player, player_event_receiver, _scmd)));
-                    }
-                    Err(e) => {
-                        error!("fail to open device {}", e);
-                    }
+    /// Render the output profile of the selected MIDI device
+    fn render_output_profile(&mut self, ui: &mut egui::Ui) {
+        let Some(name) = self
+            .current_devices
+            .iter()
+            .find(|d| d.no == self.selected_device)
+            .map(|d| d.name.clone())
+        else {
+            return;
+        };
+
+        let mut strategy = self
+            .output_profiles
+            .get(&name)
+            .map(|p| p.all_notes_off)
+            .unwrap_or_default();
+
+        let label = |strategy: &AllNotesOffStrategy| -> &str {
+            match strategy {
+                AllNotesOffStrategy::AllNotesOffController => &self.i18n.all_notes_off_controller,
+                AllNotesOffStrategy::AllSoundOffController => &self.i18n.all_sound_off_controller,
+                AllNotesOffStrategy::NoteOnZeroVelocity => &self.i18n.note_on_zero_velocity,
+                AllNotesOffStrategy::FullSweep => &self.i18n.full_notes_sweep,
+            }
+        };
+
+        ui.separator();
+        ui.label(&self.i18n.all_notes_off_strategy);
+        let mut changed = false;
+        egui::ComboBox::from_id_source("all_notes_off_strategy")
+            .selected_text(label(&strategy))
+            .show_ui(ui, |ui| {
+                for s in AllNotesOffStrategy::ALL {
+                    changed |= ui.selectable_value(&mut strategy, s, label(&s)).changed();
                 }
+            });
 
-                ui.close_menu();
+        let mut volume_mode = self
+            .output_profiles
+            .get(&name)
+            .map(|p| p.volume_mode)
+            .unwrap_or_default();
+        let volume_mode_label = |mode: &VolumeMode| -> &str {
+            match mode {
+                VolumeMode::ChannelVolume => &self.i18n.volume_channel_controller,
+                VolumeMode::Velocity => &self.i18n.volume_velocity,
             }
+        };
+
+        ui.label(&self.i18n.volume_mode);
+        let mut volume_mode_changed = false;
+        egui::ComboBox::from_id_source("volume_mode")
+            .selected_text(volume_mode_label(&volume_mode))
+            .show_ui(ui, |ui| {
+                for m in VolumeMode::ALL {
+                    volume_mode_changed |= ui
+                        .selectable_value(&mut volume_mode, m, volume_mode_label(&m))
+                        .changed();
+                }
+            });
+
+        if changed || volume_mode_changed {
+            let profile = self.output_profiles.entry(name.clone()).or_default();
+            profile.all_notes_off = strategy;
+            profile.volume_mode = volume_mode;
+            // the current play is kept, the profile is used from the next one
+            if self.appplayer.output_name.as_ref() == Some(&name) {
+                self.appplayer.set_output_profile(profile.clone());
+            }
+        }
+    }
+
+    /// Propose to resume the queue of the previous session
+    fn render_resume_window(&mut self, ctx: &egui::Context) {
+        let Some(saved) = &self.pending_resume else {
+            return;
+        };
+
+        let mut resume = false;
+        let mut resume_position: Option<Duration> = None;
+        let mut discard = false;
+        egui::Window::new(format!(
+            "{} {}",
+            egui_phosphor::regular::CLOCK_COUNTER_CLOCKWISE,
+            self.i18n.resume_queue
+        ))
+        .id(Id::new("resume_queue"))
+        .collapsible(false)
+        .resizable(false)
+        .anchor(Align2::CENTER_CENTER, Vec2::ZERO)
+        .show(ctx, |ui| {
+            if let Some(name) = &saved.current_name {
+                ui.label(RichText::new(name).strong());
+            }
+            ui.horizontal(|ui| {
+                if ui
+                    .button(format!(
+                        "{} {}",
+                        egui_phosphor::regular::PLAY,
+                        self.i18n.resume_queue_from_start
+                    ))
+                    .clicked()
+                {
+                    resume = true;
+                }
+                if let Some(position) = saved.position {
+                    if ui
+                        .button(format!(
+                            "{} {} {}",
+                            egui_phosphor::regular::FAST_FORWARD,
+                            self.i18n.resume_queue_at,
+                            duration_to_mm_ss(&position)
+                        ))
+                        .clicked()
+                    {
+                        resume = true;
+                        resume_position = Some(position);
+                    }
+                }
+                if ui
+                    .button(format!(
+                        "{} {}",
+                        egui_phosphor::regular::X,
+                        self.i18n.discard_queue
+                    ))
+                    .clicked()
+                {
+                    discard = true;
+                }
+            });
+        });
+
+        if resume {
+            self.appplayer
+                .playlist
+                .lock()
+                .expect("fail to lock playlist")
+                .restore_queue(saved);
+            self.appplayer.resume_position = resume_position;
+            self.pending_resume = None;
+            self.appplayer.play_file_on_top();
+        } else if discard {
+            self.pending_resume = None;
         }
     }
 
@@ -1171,6 +1902,33 @@ impl VirtualBookApp {
         });
     }
 
+    /// Render the next event of the schedule
+    fn render_next_scheduled_event(&self, ui: &mut egui::Ui) {
+        let Some(event) = self.schedule.next_event(Local::now().naive_local()) else {
+            return;
+        };
+        let what = match event.action {
+            ScheduledAction::Close => self.i18n.closing_time.clone(),
+            ScheduledAction::Start(index) => self
+                .schedule
+                .programs
+                .get(index)
+                .and_then(|p| p.path.file_name())
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default(),
+        };
+        ui.label(
+            RichText::new(format!(
+                "{} {} {}",
+                egui_phosphor::regular::ALARM,
+                event.at.format("%H:%M"),
+                what
+            ))
+            .weak(),
+        )
+        .on_hover_text_at_pointer(&self.i18n.next_scheduled_event);
+    }
+
     /// Render play time and title (centered)
     fn render_play_time_and_title(&mut self, ui: &mut egui::Ui) {
         if self.appplayer.is_playing() {
@@ -1304,6 +2062,18 @@ impl VirtualBookApp {
                                         },
                                     );
 
+                                    if ui
+                                        .button(format!(
+                                            "{} {}",
+                                            egui_phosphor::regular::CALENDAR,
+                                            &self.i18n.schedule
+                                        ))
+                                        .clicked()
+                                    {
+                                        self.schedule_window_open = true;
+                                        ui.close_menu();
+                                    }
+
                                     ui.separator();
 
                                     self.render_midi_devices(ui);
@@ -1322,6 +2092,9 @@ impl VirtualBookApp {
                                 self.current_devices = devices;
                             }
                         }
+
+                        self.render_volume_menu(ui);
+                        self.render_next_scheduled_event(ui);
                     });
 
                     // Center: Play time and title (centered)
@@ -1392,6 +2165,8 @@ impl VirtualBookApp {
                         &no,
                         &mut self.current_typed_no,
                         &mut self.file_store,
+                        &mut self.search,
+                        &mut self.catalogue,
                         &mut self.appplayer,
                         &self.extensions_filters,
                     );
@@ -1407,6 +2182,8 @@ impl VirtualBookApp {
                                 key.name(),
                                 &mut self.current_typed_no,
                                 &mut self.file_store,
+                                &mut self.search,
+                                &mut self.catalogue,
                                 &mut self.appplayer,
                                 &self.extensions_filters,
                             );
@@ -1509,6 +2286,29 @@ impl eframe::App for VirtualBookApp {
     /// Called by the frame work to save state before shutdown.
     fn save(&mut self, storage: &mut dyn eframe::Storage) {
         eframe::set_value(storage, eframe::APP_KEY, self);
+
+        // the queue of the previous session is kept until the user choice
+        if self.pending_resume.is_none() {
+            // played time reported by the player, including the wait before the play
+            let played_time = self
+                .appplayer
+                .is_playing()
+                .then_some(self.latest_duration_time);
+            let default_wait =
+                Duration::from_secs_f32(self.appplayer.waittime_between_file_play.max(0.0));
+            let saved_queue = self
+                .appplayer
+                .playlist
+                .lock()
+                .expect("fail to lock playlist")
+                .saved_queue(played_time, default_wait);
+            eframe::set_value(storage, SAVED_QUEUE_KEY, &saved_queue);
+        }
+    }
+
+    /// the queue is saved often, to be resumed after a crash or a power cut
+    fn auto_save_interval(&self) -> Duration {
+        AUTO_SAVE_INTERVAL
     }
 
     #[cfg_attr(any(feature = "profiling"), profiling::function)]
@@ -1521,8 +2321,14 @@ impl eframe::App for VirtualBookApp {
 
         self.update_player_state();
         self.handle_player_responses();
+        self.handle_output_request();
+        self.handle_schedule();
+        self.handle_generator_result();
         self.handle_file_dialog();
+        self.handle_library_scan();
+        self.handle_library_changes();
         self.update_metadata();
+        self.handle_outdated_views();
 
         #[cfg(not(target_arch = "wasm32"))] // no File->Quit on web pages!
         let top_response = self.render_top_panel(ctx);
@@ -1558,6 +2364,11 @@ impl eframe::App for VirtualBookApp {
             });
             self.render_central_panel(ctx, top_panel_bottom);
         }
+        self.render_resume_window(ctx);
+        schedule_window::render_schedule_window(self, ctx);
+        duplicates_window::render_duplicates_window(self, ctx);
+        catalogue_window::render_catalogue_confirmation(self, ctx);
+        catalogue_window::render_catalogue_export_window(self, ctx);
         ctx.request_repaint();
     }
 }
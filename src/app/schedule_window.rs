@@ -0,0 +1,130 @@
+use egui::*;
+
+use crate::{
+    scheduler::{ProgramKind, ScheduledProgram, TimeOfDay},
+    VirtualBookApp,
+};
+
+use super::i18n::I18NMessages;
+
+/// Render a time of the day, as hours and minutes
+fn render_time_of_day(ui: &mut Ui, time: &mut TimeOfDay) {
+    ui.add(DragValue::new(&mut time.hour).clamp_range(0..=23));
+    ui.label(":");
+    ui.add(DragValue::new(&mut time.minute).clamp_range(0..=59));
+}
+
+fn program_kind_label(kind: &ProgramKind, i18n: &I18NMessages) -> String {
+    match kind {
+        ProgramKind::Playlist => i18n.program_playlist.clone(),
+        ProgramKind::File => i18n.program_file.clone(),
+        ProgramKind::AttractLoop => i18n.program_attract_loop.clone(),
+    }
+}
+
+/// Render a scheduled program, returns true if the program is removed
+fn render_program(
+    ui: &mut Ui,
+    index: usize,
+    program: &mut ScheduledProgram,
+    i18n: &I18NMessages,
+) -> bool {
+    let mut removed = false;
+    ui.horizontal(|ui| {
+        if ui
+            .button(egui_phosphor::regular::TRASH)
+            .on_hover_text_at_pointer(&i18n.remove_program)
+            .clicked()
+        {
+            removed = true;
+        }
+        ui.checkbox(&mut program.enabled, "");
+        render_time_of_day(ui, &mut program.start);
+
+        ui.label(&i18n.program_repeat_every);
+        ui.add(
+            DragValue::new(&mut program.repeat_every_minutes)
+                .clamp_range(0..=720)
+                .suffix(" min"),
+        );
+
+        egui::ComboBox::from_id_source(("program_kind", index))
+            .selected_text(program_kind_label(&program.kind, i18n))
+            .show_ui(ui, |ui| {
+                for kind in ProgramKind::ALL {
+                    ui.selectable_value(&mut program.kind, kind, program_kind_label(&kind, i18n));
+                }
+            });
+
+        let mut path = program.path.to_string_lossy().to_string();
+        if ui
+            .add(TextEdit::singleline(&mut path).hint_text(&i18n.program_path_hint))
+            .changed()
+        {
+            program.path = path.into();
+        }
+    });
+    removed
+}
+
+/// Render the window editing the schedule of the programs
+pub(crate) fn render_schedule_window(app: &mut VirtualBookApp, ctx: &egui::Context) {
+    if !app.schedule_window_open {
+        return;
+    }
+
+    let i18n = &app.i18n;
+    let schedule = &mut app.schedule;
+    let mut open = true;
+    egui::Window::new(format!(
+        "{} {}",
+        egui_phosphor::regular::CALENDAR,
+        i18n.schedule
+    ))
+    .id(Id::new("schedule"))
+    .open(&mut open)
+    .collapsible(false)
+    .show(ctx, |ui| {
+        ui.checkbox(&mut schedule.enabled, &i18n.schedule_enabled);
+        ui.separator();
+
+        let mut removed: Option<usize> = None;
+        for (index, program) in schedule.programs.iter_mut().enumerate() {
+            if render_program(ui, index, program, i18n) {
+                removed = Some(index);
+            }
+        }
+        if let Some(index) = removed {
+            schedule.programs.remove(index);
+        }
+
+        if ui
+            .button(format!(
+                "{} {}",
+                egui_phosphor::regular::PLUS,
+                i18n.add_program
+            ))
+            .clicked()
+        {
+            schedule.programs.push(ScheduledProgram::default());
+        }
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            let mut has_closing_time = schedule.closing_time.is_some();
+            if ui
+                .checkbox(&mut has_closing_time, &i18n.closing_time)
+                .changed()
+            {
+                schedule.closing_time = has_closing_time.then(|| TimeOfDay::new(18, 0));
+            }
+            if let Some(closing_time) = &mut schedule.closing_time {
+                render_time_of_day(ui, closing_time);
+            }
+        });
+    });
+
+    if !open {
+        app.schedule_window_open = false;
+    }
+}
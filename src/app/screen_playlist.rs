@@ -1,14 +1,19 @@
 use log::{debug, error};
 
-use std::{cell::RefCell, rc::Rc};
+use std::{
+    cell::RefCell, collections::HashMap, path::PathBuf, rc::Rc, time::Duration, time::SystemTime,
+};
 
-use chrono::Local;
+use chrono::{DateTime, Local};
 
 use crate::{
     appplayer::AppPlayer,
+    catalogue::{CatalogueSelection, Lookup},
     duration_to_mm_ss,
-    file_store::{FileStore, FileStoreError, FileViewNode},
-    playlist,
+    file_store::{FileNodeLock, FileStore, FileStoreError, FileViewNode, ScanJob, SortMode},
+    playlist::{self, EntryKind, PlayMode, PlaylistElement},
+    playlist_generator,
+    search_index::LibrarySearch,
     virtualbookcomponent::VirtualBookComponent,
     VirtualBookApp,
 };
@@ -16,7 +21,9 @@ use egui::{epaint::Shadow, text::FontFamily, *};
 use egui_dnd::{dnd, DragDropConfig};
 use egui_extras::{Size, StripBuilder};
 
-use super::Screen;
+use player::FileInformations;
+
+use super::{file_details, Screen};
 
 pub const BACKSPACE: &str = "<-";
 pub const ENTER: &str = "Enter";
@@ -38,20 +45,9 @@ fn handle_enter_key(
             let result = view.find_first_file();
 
             if let Some(view_node) = result {
-                let file_node = Rc::clone(&view_node.borrow().node);
-                let was_empty;
-                {
-                    let mut locked_playlist = appplayer
-                        .playlist
-                        .lock()
-                        .expect("fail to lock the playlist");
-                    was_empty = locked_playlist.file_list.is_empty();
-
-                    locked_playlist.add_from_path_and_expand_playlists(&file_node.borrow().path);
-                }
-                if was_empty && appplayer.play_mod {
-                    appplayer.play_file_on_top();
-                }
+                let file_node = view_node.borrow().file_node();
+                let path = file_node.read_node().path.clone();
+                add_to_playlist(appplayer, &path);
 
                 *current_typed_no = "".into();
             }
@@ -59,19 +55,80 @@ fn handle_enter_key(
     }
 }
 
-/// Update file store filtered view based on current typed number
+/// Add a file to the playlist, the play starts if the playlist was empty
+pub(crate) fn add_to_playlist(appplayer: &mut AppPlayer, path: &PathBuf) {
+    let was_empty;
+    {
+        let mut locked_playlist = appplayer
+            .playlist
+            .lock()
+            .expect("fail to lock the playlist");
+        was_empty = locked_playlist.current().is_none();
+
+        locked_playlist.add_from_path_and_expand_playlists(path);
+    }
+    if was_empty && appplayer.play_mod {
+        appplayer.play_file_on_top();
+    }
+}
+
+/// Handle Enter key in catalogue mode, the file of the typed number is proposed
+/// for confirmation, a second Enter adds it to the playlist
+fn handle_catalogue_enter(
+    current_typed_no: &mut String,
+    catalogue: &mut CatalogueSelection,
+    appplayer: &mut AppPlayer,
+) {
+    if let Some(entry) = catalogue.confirmation.take() {
+        add_to_playlist(appplayer, &entry.path);
+        *current_typed_no = "".into();
+        return;
+    }
+    if let Lookup::Found(entry) = catalogue.catalogue.lookup_typed(current_typed_no) {
+        catalogue.confirmation = Some(entry);
+    }
+}
+
+/// Update file store filtered view with the file of the typed number, in catalogue mode
+fn update_catalogue_filter(
+    file_store: &mut Option<FileStore>,
+    catalogue: &CatalogueSelection,
+    current_typed_no: &str,
+    extensions_filter: &Option<Vec<String>>,
+) {
+    if let Some(filestore) = file_store {
+        let ranking = match catalogue.catalogue.lookup_typed(current_typed_no) {
+            Lookup::Found(entry) => HashMap::from([(entry.path, 1.0)]),
+            Lookup::Ambiguous(paths) => paths.into_iter().map(|p| (p, 1.0)).collect(),
+            Lookup::NotFound => HashMap::new(),
+        };
+        filestore.filtered_view = match filestore.ranked_view(&ranking, extensions_filter) {
+            Ok(mut new_view) => {
+                new_view.expand_all();
+                Some(new_view)
+            }
+            Err(_) => None,
+        };
+    }
+}
+
+/// Update file store filtered view with the files found for the current typed text,
+/// the best ranked files first
 fn update_file_store_filter(
     file_store: &mut Option<FileStore>,
+    search: &mut LibrarySearch,
     current_typed_no: &str,
     extensions_filter: &Option<Vec<String>>,
 ) {
     if let Some(filestore) = file_store {
-        if let Ok(mut new_view) =
-            filestore.view(&Some(current_typed_no.to_owned()), extensions_filter)
-        {
-            new_view.recurse_expand_first();
-            filestore.filtered_view = Some(new_view);
-        }
+        let ranking = search.ranking(current_typed_no);
+        filestore.filtered_view = match filestore.ranked_view(&ranking, extensions_filter) {
+            Ok(mut new_view) => {
+                new_view.recurse_expand_first();
+                Some(new_view)
+            }
+            Err(_) => None,
+        };
     }
 }
 
@@ -80,15 +137,24 @@ pub fn handling_key(
     no: &str,
     current_typed_no: &mut String,
     file_store: &mut Option<FileStore>,
+    search: &mut LibrarySearch,
+    catalogue: &mut CatalogueSelection,
     appplayer: &mut AppPlayer,
     extensions_filter: &Option<Vec<String>>,
 ) {
+    if no != ENTER {
+        // the typed number changes, the confirmation is asked again
+        catalogue.confirmation = None;
+    }
     match no {
         BACKSPACE => {
             if !current_typed_no.is_empty() {
                 *current_typed_no = current_typed_no[0..current_typed_no.len() - 1].to_string();
             }
         }
+        ENTER if catalogue.enabled => {
+            handle_catalogue_enter(current_typed_no, catalogue, appplayer);
+        }
         ENTER => {
             handle_enter_key(current_typed_no, file_store, appplayer);
         }
@@ -104,7 +170,11 @@ pub fn handling_key(
     }
 
     // filtering the treeview
-    update_file_store_filter(file_store, current_typed_no, extensions_filter);
+    if catalogue.enabled {
+        update_catalogue_filter(file_store, catalogue, current_typed_no, extensions_filter);
+    } else {
+        update_file_store_filter(file_store, search, current_typed_no, extensions_filter);
+    }
 }
 
 /// Get button text for number pad based on position
@@ -120,7 +190,10 @@ fn get_button_text(row: usize, col: usize) -> String {
 #[cfg_attr(any(feature = "profiling"), profiling::function)]
 pub(crate) fn ui_button_panel(app: &mut VirtualBookApp, _ctx: &egui::Context, ui: &mut Ui) {
     let file_store = &mut app.file_store;
+    let search = &mut app.search;
+    let catalogue = &mut app.catalogue;
     let current_typed_no = &mut app.current_typed_no;
+    let i18n = &app.i18n;
 
     // button panel - fill all available space
     ui.with_layout(egui::Layout::top_down_justified(egui::Align::Min), |ui| {
@@ -129,10 +202,22 @@ pub(crate) fn ui_button_panel(app: &mut VirtualBookApp, _ctx: &egui::Context, ui
             .sizes(Size::remainder(), 4) // 4 rows - share remaining space equally
             .vertical(|mut strip| {
                 strip.cell(|ui| {
-                    let mut rt = RichText::new(current_typed_no.clone());
-                    rt = rt.font(FontId::proportional(30.0));
-                    rt = rt.color(ui.style().visuals.selection.stroke.color);
-                    ui.label(rt);
+                    ui.horizontal(|ui| {
+                        let mut rt = RichText::new(current_typed_no.clone());
+                        rt = rt.font(FontId::proportional(30.0));
+                        rt = rt.color(ui.style().visuals.selection.stroke.color);
+                        ui.label(rt);
+
+                        // the matched title is displayed while typing
+                        if catalogue.enabled && !current_typed_no.is_empty() {
+                            let matched = match catalogue.catalogue.lookup_typed(current_typed_no) {
+                                Lookup::Found(entry) => entry.title(),
+                                Lookup::Ambiguous(_) => i18n.catalogue_ambiguous.clone(),
+                                Lookup::NotFound => i18n.catalogue_not_found.clone(),
+                            };
+                            ui.label(RichText::new(matched).font(FontId::proportional(20.0)));
+                        }
+                    });
                 });
 
                 for i in 0..4 {
@@ -153,6 +238,8 @@ pub(crate) fn ui_button_panel(app: &mut VirtualBookApp, _ctx: &egui::Context, ui
                                                     &button_text,
                                                     current_typed_no,
                                                     file_store,
+                                                    search,
+                                                    catalogue,
                                                     &mut app.appplayer,
                                                     &app.extensions_filters,
                                                 );
@@ -169,8 +256,8 @@ pub(crate) fn ui_button_panel(app: &mut VirtualBookApp, _ctx: &egui::Context, ui
 
 /// Render playlist header with controls (play, next, save)
 fn render_playlist_header(app: &mut VirtualBookApp, ui: &mut Ui) {
-    let appplayer = &mut app.appplayer;
     ui.horizontal(|ui| {
+        let appplayer = &mut app.appplayer;
         ui.add_enabled_ui(!appplayer.is_playlist_empty(), |ui| {
             ui.label(
                 RichText::new(format!(
@@ -191,6 +278,29 @@ fn render_playlist_header(app: &mut VirtualBookApp, ui: &mut Ui) {
                 appplayer.play_file_on_top();
             }
 
+            if ui
+                .button(egui_phosphor::regular::REWIND)
+                .on_hover_text_at_pointer(&app.i18n.go_to_previous_file)
+                .clicked()
+            {
+                appplayer.previous();
+            }
+
+            if appplayer.play_mod && appplayer.is_playing() {
+                let pause_icon = if appplayer.is_paused() {
+                    egui_phosphor::regular::PLAY_PAUSE
+                } else {
+                    egui_phosphor::regular::PAUSE
+                };
+                if ui
+                    .button(pause_icon)
+                    .on_hover_text_at_pointer(&app.i18n.pause_resume)
+                    .clicked()
+                {
+                    appplayer.pause();
+                }
+            }
+
             if ui
                 .button(egui_phosphor::regular::FAST_FORWARD)
                 .on_hover_text_at_pointer(&app.i18n.go_to_next_file)
@@ -198,8 +308,18 @@ fn render_playlist_header(app: &mut VirtualBookApp, ui: &mut Ui) {
             {
                 appplayer.next();
             }
+
+            if ui
+                .button(egui_phosphor::regular::ARROW_COUNTER_CLOCKWISE)
+                .on_hover_text_at_pointer(&app.i18n.replay_last)
+                .clicked()
+            {
+                appplayer.replay_last();
+            }
         });
 
+        render_play_mode_selector(&mut app.play_mode, appplayer, &app.i18n, ui);
+
         if let Some(path_buf) = &app.file_store_path {
             ui.separator();
             if ui
@@ -210,7 +330,7 @@ fn render_playlist_header(app: &mut VirtualBookApp, ui: &mut Ui) {
                 let date = Local::now();
                 let formatted_date = date.format("%Y-%m-%d_%H-%M-%S");
                 let mut pb = path_buf.clone();
-                pb.push(format!("playlist_{}.playlist", formatted_date));
+                pb.push(format!("playlist_{}.m3u8", formatted_date));
                 let locked_playlist = appplayer
                     .playlist
                     .lock()
@@ -221,9 +341,239 @@ fn render_playlist_header(app: &mut VirtualBookApp, ui: &mut Ui) {
                 }
             }
         }
+
+        ui.separator();
+        render_special_entries_menu(app, ui);
+        render_generator_menu(app, ui);
     });
 }
 
+/// Render the menu adding the special entries (pause, text card, stop marker)
+fn render_special_entries_menu(app: &mut VirtualBookApp, ui: &mut Ui) {
+    let mut added: Option<EntryKind> = None;
+    ui.menu_button(egui_phosphor::regular::PLUS_CIRCLE, |ui| {
+        ui.horizontal(|ui| {
+            let mut seconds = app.new_pause.as_secs_f64();
+            if ui
+                .add(
+                    DragValue::new(&mut seconds)
+                        .clamp_range(1.0..=3600.0)
+                        .suffix(" s"),
+                )
+                .changed()
+            {
+                app.new_pause = Duration::from_secs_f64(seconds);
+            }
+            if ui
+                .button(format!(
+                    "{} {}",
+                    egui_phosphor::regular::HOURGLASS,
+                    app.i18n.add_pause
+                ))
+                .clicked()
+            {
+                added = Some(EntryKind::Pause(app.new_pause));
+            }
+        });
+        ui.separator();
+
+        ui.add(
+            TextEdit::multiline(&mut app.new_card_text)
+                .hint_text(&app.i18n.text_card_hint)
+                .desired_rows(2),
+        );
+        render_optional_duration(
+            ui,
+            &app.i18n.text_card_duration,
+            &mut app.new_card_duration,
+            10.0,
+        );
+        ui.add_enabled_ui(!app.new_card_text.trim().is_empty(), |ui| {
+            if ui
+                .button(format!(
+                    "{} {}",
+                    egui_phosphor::regular::TEXT_T,
+                    app.i18n.add_text_card
+                ))
+                .clicked()
+            {
+                added = Some(EntryKind::TextCard(
+                    app.new_card_text.trim().to_string(),
+                    app.new_card_duration,
+                ));
+                app.new_card_text.clear();
+            }
+        });
+        ui.separator();
+
+        if ui
+            .button(format!(
+                "{} {}",
+                egui_phosphor::regular::STOP_CIRCLE,
+                app.i18n.add_stop_marker
+            ))
+            .clicked()
+        {
+            added = Some(EntryKind::Stop);
+        }
+
+        if added.is_some() {
+            ui.close_menu();
+        }
+    })
+    .response
+    .on_hover_text_at_pointer(&app.i18n.add_special_entry);
+
+    if let Some(kind) = added {
+        app.appplayer
+            .playlist
+            .lock()
+            .expect("fail to lock the playlist")
+            .file_list
+            .push(PlaylistElement::special(kind));
+    }
+}
+
+/// Render the menu generating a playlist from the play statistics
+fn render_generator_menu(app: &mut VirtualBookApp, ui: &mut Ui) {
+    if app.generator_job.is_some() {
+        ui.spinner()
+            .on_hover_text_at_pointer(&app.i18n.generating_playlist);
+        return;
+    }
+
+    let i18n = &app.i18n;
+    let settings = &mut app.generator_settings;
+    let mut generate = false;
+    ui.menu_button(egui_phosphor::regular::MAGIC_WAND, |ui| {
+        ui.horizontal(|ui| {
+            ui.label(&i18n.generator_target_duration);
+            let mut minutes = settings.target_duration.as_secs() / 60;
+            if ui
+                .add(
+                    DragValue::new(&mut minutes)
+                        .clamp_range(5..=600)
+                        .suffix(" min"),
+                )
+                .changed()
+            {
+                settings.target_duration = Duration::from_secs(minutes * 60);
+            }
+        });
+        ui.checkbox(&mut settings.favour_starred, &i18n.generator_favour_starred);
+        render_optional_value(
+            ui,
+            &i18n.generator_skip_played_within,
+            &mut settings.skip_played_within_days,
+            7,
+            1..=365,
+            &format!(" {}", i18n.days),
+        );
+
+        // the durations come from the library, no player is needed
+        ui.add_enabled_ui(app.file_store.is_some(), |ui| {
+            if ui
+                .button(format!(
+                    "{} {}",
+                    egui_phosphor::regular::MAGIC_WAND,
+                    i18n.generate_playlist
+                ))
+                .clicked()
+            {
+                generate = true;
+                ui.close_menu();
+            }
+        });
+    })
+    .response
+    .on_hover_text_at_pointer(&i18n.generate_playlist);
+
+    if !generate {
+        return;
+    }
+
+    let Some(file_store) = &app.file_store else {
+        return;
+    };
+    let files: Vec<(PathBuf, Option<Duration>)> = file_store
+        .files_durations()
+        .into_iter()
+        .filter(|(f, _)| {
+            player::midiio::formats::registry()
+                .find_by_extension(f)
+                .is_some()
+        })
+        .collect();
+    app.generator_job = Some(playlist_generator::start_generation(
+        files,
+        file_store.base_path.clone(),
+        app.generator_settings.clone(),
+    ));
+}
+
+/// Label of a playlist entry, with an icon for the special entries
+fn playlist_item_label(item: &PlaylistElement, i18n: &crate::app::i18n::I18NMessages) -> String {
+    match &item.kind {
+        EntryKind::File => item.name.clone(),
+        // the duration is displayed with the entry
+        EntryKind::Pause(_) => {
+            format!("{} {}", egui_phosphor::regular::HOURGLASS, i18n.pause_entry)
+        }
+        EntryKind::TextCard(text, _) => format!(
+            "{} {}",
+            egui_phosphor::regular::TEXT_T,
+            text.replace('\n', " ")
+        ),
+        EntryKind::Stop => format!(
+            "{} {}",
+            egui_phosphor::regular::STOP_CIRCLE,
+            i18n.stop_marker
+        ),
+    }
+}
+
+/// Render the play mode selection (once, repeat, shuffle)
+fn render_play_mode_selector(
+    current_play_mode: &mut PlayMode,
+    appplayer: &mut AppPlayer,
+    i18n: &crate::app::i18n::I18NMessages,
+    ui: &mut Ui,
+) {
+    let label = |mode: &PlayMode| -> String {
+        let (icon, text) = match mode {
+            PlayMode::Once => (egui_phosphor::regular::ARROW_RIGHT, &i18n.play_mode_once),
+            PlayMode::RepeatAll => (egui_phosphor::regular::REPEAT, &i18n.play_mode_repeat_all),
+            PlayMode::RepeatOne => (
+                egui_phosphor::regular::REPEAT_ONCE,
+                &i18n.play_mode_repeat_one,
+            ),
+            PlayMode::Shuffle => (egui_phosphor::regular::SHUFFLE, &i18n.play_mode_shuffle),
+        };
+        format!("{} {}", icon, text)
+    };
+
+    let mut play_mode = *current_play_mode;
+    let mut changed = false;
+    egui::ComboBox::from_id_source("play_mode")
+        .selected_text(label(&play_mode))
+        .show_ui(ui, |ui| {
+            for m in PlayMode::ALL {
+                changed |= ui.selectable_value(&mut play_mode, m, label(&m)).changed();
+            }
+        })
+        .response
+        .on_hover_text_at_pointer(&i18n.play_mode);
+
+    if changed {
+        *current_play_mode = play_mode;
+        appplayer
+            .playlist
+            .lock()
+            .expect("fail to lock the playlist")
+            .set_play_mode(play_mode);
+    }
+}
+
 /// Truncate display name if too long
 fn truncate_display_name(name: &str, max_length: usize) -> String {
     if name.len() > max_length {
@@ -233,15 +583,23 @@ fn truncate_display_name(name: &str, max_length: usize) -> String {
     }
 }
 
+/// action asked on a playlist item
+enum PlaylistItemAction {
+    Delete,
+    EditSettings,
+    ShowDetails,
+}
+
 /// Render a single playlist item
 fn render_playlist_item(
     ui: &mut Ui,
     item: &playlist::PlaylistElement,
     index: usize,
+    current_index: usize,
     item_size: Vec2,
     app: &VirtualBookApp,
-) -> bool {
-    let mut deleted = false;
+) -> Option<PlaylistItemAction> {
+    let mut action = None;
     ui.horizontal(|ui| {
         ui.spacing();
         if ui
@@ -249,16 +607,50 @@ fn render_playlist_item(
             .on_hover_text_at_pointer(&app.i18n.remove_file_from_list)
             .clicked()
         {
-            deleted = true;
+            action = Some(PlaylistItemAction::Delete);
+        }
+
+        // highlight the entries having specific settings
+        if item.is_file() {
+            let mut settings_button = RichText::new(egui_phosphor::regular::SLIDERS_HORIZONTAL);
+            if !item.settings.is_default() {
+                settings_button = settings_button.color(ui.visuals().selection.stroke.color);
+            }
+            if ui
+                .button(settings_button)
+                .on_hover_text_at_pointer(&app.i18n.entry_settings)
+                .clicked()
+            {
+                action = Some(PlaylistItemAction::EditSettings);
+            }
+            if ui
+                .button(egui_phosphor::regular::INFO)
+                .on_hover_text_at_pointer(&app.i18n.show_file_details)
+                .clicked()
+            {
+                action = Some(PlaylistItemAction::ShowDetails);
+            }
         }
         ui.add(Label::new(format!("{}:", index + 1)));
 
         // compute size to strip the label
         let mean_displayed_letter = (item_size.x / 10.0) as usize;
-        let display_compute_name = truncate_display_name(&item.name, mean_displayed_letter);
-
-        ui.label(&display_compute_name)
-            .on_hover_text_at_pointer(&item.name);
+        let label = playlist_item_label(item, &app.i18n);
+        let display_compute_name = truncate_display_name(&label, mean_displayed_letter);
+
+        let mut name = RichText::new(&display_compute_name);
+        if index == current_index {
+            name = RichText::new(format!(
+                "{} {}",
+                egui_phosphor::regular::PLAY,
+                display_compute_name
+            ))
+            .strong();
+        } else if index < current_index && app.play_mode == PlayMode::Once {
+            // already played
+            name = name.weak();
+        }
+        ui.label(name).on_hover_text_at_pointer(&label);
 
         if let Some(additional_informations) = &item.additional_informations {
             if let Some(duration) = additional_informations.duration {
@@ -266,12 +658,266 @@ fn render_playlist_item(
             }
         }
     });
-    deleted
+    action
+}
+
+/// Render a value that may be overridden, the checkbox activate the override
+fn render_optional_value<T: emath::Numeric>(
+    ui: &mut Ui,
+    label: &str,
+    value: &mut Option<T>,
+    default_value: T,
+    range: std::ops::RangeInclusive<T>,
+    suffix: &str,
+) -> bool {
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        let mut overridden = value.is_some();
+        if ui.checkbox(&mut overridden, label).changed() {
+            *value = overridden.then_some(default_value);
+            changed = true;
+        }
+        if let Some(v) = value {
+            changed |= ui
+                .add(DragValue::new(v).clamp_range(range).suffix(suffix))
+                .changed();
+        }
+    });
+    changed
+}
+
+/// Render a duration that may be overridden, edited in seconds
+fn render_optional_duration(
+    ui: &mut Ui,
+    label: &str,
+    value: &mut Option<Duration>,
+    default_value: f64,
+) -> bool {
+    let mut seconds = value.map(|d| d.as_secs_f64());
+    let changed = render_optional_value(ui, label, &mut seconds, default_value, 0.0..=3600.0, " s");
+    if changed {
+        *value = seconds.map(Duration::from_secs_f64);
+    }
+    changed
+}
+
+/// Render the window editing the play settings of a playlist entry
+fn render_entry_settings_window(app: &mut VirtualBookApp, ctx: &egui::Context) {
+    let Some(edited) = app.edited_entry else {
+        return;
+    };
+
+    let mut locked_playlist = app
+        .appplayer
+        .playlist
+        .lock()
+        .expect("fail to lock playlist");
+    let Some(element) = locked_playlist
+        .file_list
+        .iter_mut()
+        .find(|e| e.added_at == edited)
+    else {
+        // the entry has been removed
+        drop(locked_playlist);
+        app.edited_entry = None;
+        return;
+    };
+
+    let i18n = &app.i18n;
+    let settings = &mut element.settings;
+    let mut open = true;
+    egui::Window::new(format!(
+        "{} {}",
+        egui_phosphor::regular::SLIDERS_HORIZONTAL,
+        element.name
+    ))
+    .id(Id::new("entry_settings"))
+    .open(&mut open)
+    .collapsible(false)
+    .resizable(false)
+    .show(ctx, |ui| {
+        render_optional_duration(
+            ui,
+            &i18n.entry_wait,
+            &mut settings.wait,
+            app.play_wait as f64,
+        );
+        render_optional_value(
+            ui,
+            &i18n.entry_speed,
+            &mut settings.speed,
+            100,
+            10..=300,
+            " %",
+        );
+        render_optional_value(
+            ui,
+            &i18n.entry_transpose,
+            &mut settings.transpose,
+            0,
+            -24..=24,
+            "",
+        );
+        render_optional_duration(
+            ui,
+            &i18n.entry_start_offset,
+            &mut settings.start_offset,
+            0.0,
+        );
+        render_optional_duration(ui, &i18n.entry_end_offset, &mut settings.end_offset, 0.0);
+
+        ui.horizontal(|ui| {
+            ui.label(&i18n.entry_output);
+            let default_output = i18n.default_output.clone();
+            egui::ComboBox::from_id_source("entry_output")
+                .selected_text(settings.output.clone().unwrap_or(default_output.clone()))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut settings.output, None, default_output);
+                    for device in &app.current_devices {
+                        ui.selectable_value(
+                            &mut settings.output,
+                            Some(device.name.clone()),
+                            &device.name,
+                        );
+                    }
+                });
+        });
+    });
+
+    if !open {
+        drop(locked_playlist);
+        app.edited_entry = None;
+    }
+}
+
+/// Resolve the missing entries of the loaded playlist in the library, and display the report
+fn render_playlist_load_report(app: &mut VirtualBookApp, ui: &mut Ui) {
+    let mut locked_playlist = app
+        .appplayer
+        .playlist
+        .lock()
+        .expect("fail to lock playlist");
+
+    if let Some(file_store) = &app.file_store {
+        locked_playlist.resolve_missing_entries(&|name| file_store.find_file_by_name(name));
+    }
+
+    let Some(report) = &locked_playlist.load_report else {
+        return;
+    };
+
+    let mut closed = false;
+    ui.group(|ui| {
+        ui.horizontal(|ui| {
+            let playlist_name = report
+                .playlist
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            ui.label(
+                RichText::new(format!(
+                    "{} {}",
+                    egui_phosphor::regular::WARNING,
+                    playlist_name
+                ))
+                .strong(),
+            );
+            if ui
+                .button(egui_phosphor::regular::X)
+                .on_hover_text_at_pointer(&app.i18n.close)
+                .clicked()
+            {
+                closed = true;
+            }
+        });
+
+        if !report.resolved.is_empty() {
+            ui.label(format!(
+                "{} : {}",
+                app.i18n.playlist_entries_found_by_name,
+                report.resolved.len()
+            ));
+        }
+        if !report.missing.is_empty() {
+            ui.label(
+                RichText::new(format!(
+                    "{} : {}",
+                    app.i18n.playlist_missing_entries,
+                    report.missing.len()
+                ))
+                .color(ui.visuals().warn_fg_color),
+            );
+            for missing in &report.missing {
+                ui.label(RichText::new(missing.display().to_string()).weak());
+            }
+        }
+    });
+
+    if closed {
+        locked_playlist.load_report = None;
+    }
+}
+
+/// Render the files played during the session, the latest first
+fn render_playlist_history(app: &mut VirtualBookApp, ui: &mut Ui) {
+    let history: Vec<(usize, String, String)> = {
+        let locked_playlist = app
+            .appplayer
+            .playlist
+            .lock()
+            .expect("fail to lock playlist");
+        locked_playlist
+            .history
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(index, entry)| {
+                let played_at: DateTime<Local> = entry.played_at.into();
+                (
+                    index,
+                    played_at.format("%H:%M").to_string(),
+                    entry.element.name.clone(),
+                )
+            })
+            .collect()
+    };
+
+    let mut replayed: Option<usize> = None;
+    CollapsingHeader::new(format!(
+        "{} {} ({})",
+        egui_phosphor::regular::CLOCK_COUNTER_CLOCKWISE,
+        app.i18n.history,
+        history.len()
+    ))
+    .id_source("playlist_history")
+    .default_open(false)
+    .show(ui, |ui| {
+        egui::ScrollArea::vertical()
+            .max_height(150.0)
+            .show(ui, |ui| {
+                for (index, played_at, name) in &history {
+                    ui.horizontal(|ui| {
+                        if ui
+                            .button(egui_phosphor::regular::ARROW_COUNTER_CLOCKWISE)
+                            .on_hover_text_at_pointer(&app.i18n.replay_history_entry)
+                            .clicked()
+                        {
+                            replayed = Some(*index);
+                        }
+                        ui.label(RichText::new(played_at).weak());
+                        ui.label(name);
+                    });
+                }
+            });
+    });
+
+    if let Some(index) = replayed {
+        app.appplayer.replay_history_entry(index);
+    }
 }
 
 /// Render playlist items with drag-and-drop support
 fn render_playlist_items(app: &mut VirtualBookApp, ui: &mut Ui) {
-    let isplaying = app.appplayer.is_playing();
     let mut locked_playlist = app
         .appplayer
         .playlist
@@ -279,11 +925,11 @@ fn render_playlist_items(app: &mut VirtualBookApp, ui: &mut Ui) {
         .expect("fail to lock playlist");
 
     let mut working_list = locked_playlist.file_list.clone();
-    if isplaying && !working_list.is_empty() {
-        working_list = working_list[1..].to_vec();
-    }
+    let current_index = locked_playlist.current_index;
 
     let mut deleted: Option<usize> = None;
+    let mut edited: Option<SystemTime> = None;
+    let mut details: Option<(PathBuf, Option<FileInformations>)> = None;
     let item_size = Vec2::new(ui.available_width(), 32.0);
 
     // see https://github.com/lucasmerlin/hello_egui/blob/main/fancy-example/src/main.rs
@@ -300,8 +946,25 @@ fn render_playlist_items(app: &mut VirtualBookApp, ui: &mut Ui) {
                         item_handle.ui_sized(ui, item_size, |ui, handle, _state| {
                             ui.vertical_centered_justified(|ui| {
                                 handle.ui_sized(ui, item_size, |ui| {
-                                    if render_playlist_item(ui, item, index, item_size, app) {
-                                        deleted = Some(index);
+                                    match render_playlist_item(
+                                        ui,
+                                        item,
+                                        index,
+                                        current_index,
+                                        item_size,
+                                        app,
+                                    ) {
+                                        Some(PlaylistItemAction::Delete) => deleted = Some(index),
+                                        Some(PlaylistItemAction::EditSettings) => {
+                                            edited = Some(item.added_at)
+                                        }
+                                        Some(PlaylistItemAction::ShowDetails) => {
+                                            details = Some((
+                                                item.path.clone(),
+                                                item.additional_informations.clone(),
+                                            ))
+                                        }
+                                        None => {}
                                     }
                                     ui.end_row();
                                 });
@@ -313,21 +976,22 @@ fn render_playlist_items(app: &mut VirtualBookApp, ui: &mut Ui) {
         });
 
     response.update_vec(&mut working_list);
-
-    if isplaying && (!locked_playlist.file_list.is_empty()) {
-        locked_playlist.file_list.truncate(1);
-        locked_playlist.file_list.extend(working_list);
-    } else {
-        locked_playlist.file_list = working_list;
-    }
+    locked_playlist.set_file_list(working_list);
 
     if let Some(reason) = response.cancellation_reason() {
         debug!("Drag has been cancelled because of {:?}", reason);
     }
 
     if let Some(index) = deleted {
-        let toremove = if isplaying { index + 1 } else { index };
-        locked_playlist.file_list.remove(toremove);
+        locked_playlist.remove(index);
+    }
+
+    drop(locked_playlist);
+    if edited.is_some() {
+        app.edited_entry = edited;
+    }
+    if let Some((path, informations)) = details {
+        file_details::show_file_details(app, path, informations);
     }
 }
 
@@ -350,6 +1014,9 @@ pub(crate) fn ui_playlist_right_panel(app: &mut VirtualBookApp, ctx: &egui::Cont
                                                 |mut strip| {
                                                     strip.cell(|ui| {
                                                         render_playlist_header(app, ui);
+                                                        render_entry_settings_window(app, ctx);
+                                                        render_playlist_load_report(app, ui);
+                                                        render_playlist_history(app, ui);
                                                         ui.separator();
 
                                                         // playlist
@@ -382,10 +1049,30 @@ pub(crate) fn ui_playlist_right_panel(app: &mut VirtualBookApp, ctx: &egui::Cont
         });
 }
 
+/// Render the progress of the library scan, with a button to stop it
+fn render_scan_progress(ui: &mut Ui, scan_job: &ScanJob, i18n: &crate::app::i18n::I18NMessages) {
+    let (files, _folders) = scan_job.control.progress();
+    ui.horizontal(|ui| {
+        ui.spinner();
+        ui.label(format!(
+            "{} {} {}",
+            i18n.scanning_library, files, i18n.files_count
+        ));
+        if ui
+            .button(egui_phosphor::regular::STOP)
+            .on_hover_text_at_pointer(&i18n.stop_scan)
+            .clicked()
+        {
+            scan_job.control.cancel();
+        }
+    });
+}
+
 /// Handle folder display in tree view
 fn display_folder(
     appplayer: &mut AppPlayer,
     number_selected: &mut String,
+    details_request: &mut Option<(PathBuf, Option<FileInformations>)>,
     element: &mut Rc<RefCell<FileViewNode>>,
     element_name: String,
     ui: &mut Ui,
@@ -432,7 +1119,14 @@ fn display_folder(
         .show(ui, |ui| {
             ui.spacing_mut().item_spacing.x = 0.0;
 
-            match display_tree(appplayer, number_selected, element, ui, i18n) {
+            match display_tree(
+                appplayer,
+                number_selected,
+                details_request,
+                element,
+                ui,
+                i18n,
+            ) {
                 Err(e) => {
                     error!("error in displaying sub tree {}", e);
                 }
@@ -524,15 +1218,16 @@ fn render_star_count_badge(ui: &mut Ui, count: u32, i18n: &crate::app::i18n::I18
 fn display_file(
     appplayer: &mut AppPlayer,
     number_selected: &mut String,
+    details_request: &mut Option<(PathBuf, Option<FileInformations>)>,
     element: &mut Rc<RefCell<FileViewNode>>,
     element_name: String,
     ui: &mut Ui,
     i18n: &crate::app::i18n::I18NMessages,
 ) -> bool {
     // Get play count and star count from FileNode if available
-    let (play_count, star_count, is_folder) = {
+    let (play_count, star_count, is_folder, path, informations) = {
         let file_node = element.borrow();
-        let node = file_node.node.borrow();
+        let node = file_node.node.read_node();
         let play = node.play_count;
         let star = node.star_count;
         let is_folder = node.is_folder;
@@ -547,7 +1242,13 @@ fn display_file(
                 element_name, play, star, node.path
             );
         }
-        (play, star, is_folder)
+        (
+            play,
+            star,
+            is_folder,
+            node.path.clone(),
+            node.informations.clone(),
+        )
     };
 
     // Display file name with play count and stars in the checkbox text
@@ -598,6 +1299,15 @@ fn display_file(
             }
         }
 
+        // Add duration if computed (with smaller font)
+        if let Some(duration) = informations.as_ref().and_then(|i| i.duration) {
+            job.append(
+                &format!(" {}", duration_to_mm_ss(&duration)),
+                0.0,
+                small_format.clone(),
+            );
+        }
+
         // Add stars with yellow color if available (with smaller font, using fill variant)
         if let Some(count) = star_count {
             if count > 0 {
@@ -614,6 +1324,9 @@ fn display_file(
 
         // Check if clicked first (before consuming response for tooltip)
         let clicked = checkbox_response.clicked();
+        if checkbox_response.secondary_clicked() && !is_folder {
+            *details_request = Some((path, informations.clone()));
+        }
 
         // Add tooltip with detailed info on hover
         let mut tooltip_text = String::new();
@@ -627,8 +1340,11 @@ fn display_file(
                 tooltip_text.push_str(&format!("{}: {}", i18n.star_count_tooltip, count));
             }
         }
-        if !tooltip_text.is_empty() {
-            checkbox_response.on_hover_text_at_pointer(tooltip_text.trim_end());
+        if !is_folder {
+            tooltip_text.push_str(&format!("\n{}", i18n.right_click_for_details));
+        }
+        if !tooltip_text.trim().is_empty() {
+            checkbox_response.on_hover_text_at_pointer(tooltip_text.trim());
         }
 
         clicked
@@ -667,6 +1383,7 @@ fn display_file(
 fn display_tree(
     appplayer: &mut AppPlayer,
     number_selected: &mut String,
+    details_request: &mut Option<(PathBuf, Option<FileInformations>)>,
     files_folder: &mut Rc<RefCell<FileViewNode>>,
     ui: &mut Ui,
     i18n: &crate::app::i18n::I18NMessages,
@@ -678,12 +1395,20 @@ fn display_tree(
             let borrowed_element = &element.borrow_mut();
             let name = borrowed_element.name().clone();
             let node = &borrowed_element.node;
-            let bnode = node.borrow();
+            let bnode = node.read_node();
             (bnode.is_folder, name)
         };
 
         if node_is_folder {
-            match display_folder(appplayer, number_selected, element, element_name, ui, i18n) {
+            match display_folder(
+                appplayer,
+                number_selected,
+                details_request,
+                element,
+                element_name,
+                ui,
+                i18n,
+            ) {
                 Err(e) => {
                     error!("error in displaying folder {}", e);
                 }
@@ -695,7 +1420,15 @@ fn display_tree(
             }
         } else {
             // file and not a folder
-            if display_file(appplayer, number_selected, element, element_name, ui, i18n) {
+            if display_file(
+                appplayer,
+                number_selected,
+                details_request,
+                element,
+                element_name,
+                ui,
+                i18n,
+            ) {
                 file_selected = true;
             }
         }
@@ -703,8 +1436,43 @@ fn display_tree(
     Ok(file_selected)
 }
 
+fn sort_mode_label(mode: SortMode, i18n: &crate::app::i18n::I18NMessages) -> &str {
+    match mode {
+        SortMode::Name => &i18n.sort_by_name,
+        SortMode::PlayCount => &i18n.sort_by_play_count,
+        SortMode::Stars => &i18n.sort_by_stars,
+        SortMode::LastPlayed => &i18n.sort_by_last_played,
+        SortMode::Duration => &i18n.sort_by_duration,
+        SortMode::DateAdded => &i18n.sort_by_date_added,
+    }
+}
+
+/// Render the sort and grouping choice of the file tree
+fn render_view_order(app: &mut VirtualBookApp, ui: &mut Ui) {
+    let mut order = app.view_order;
+    ui.horizontal(|ui| {
+        egui::ComboBox::from_id_source("tree_sort")
+            .selected_text(format!(
+                "{} {}",
+                egui_phosphor::regular::SORT_ASCENDING,
+                sort_mode_label(order.sort, &app.i18n)
+            ))
+            .show_ui(ui, |ui| {
+                for mode in SortMode::ALL {
+                    ui.selectable_value(&mut order.sort, mode, sort_mode_label(mode, &app.i18n));
+                }
+            });
+        ui.toggle_value(&mut order.group_by_scale, egui_phosphor::regular::STACK)
+            .on_hover_text(&app.i18n.group_by_scale);
+    });
+    if order != app.view_order {
+        app.set_view_order(order);
+    }
+}
+
 /// Render the file tree side panel
 fn render_file_tree_panel(app: &mut VirtualBookApp, ui: &mut Ui) {
+    let mut details_request: Option<(PathBuf, Option<FileInformations>)> = None;
     egui::SidePanel::left("tree_panel")
         .resizable(true)
         .default_width(200.0)
@@ -718,6 +1486,7 @@ fn render_file_tree_panel(app: &mut VirtualBookApp, ui: &mut Ui) {
             ..Default::default()
         })
         .show_inside(ui, |ui| {
+            render_view_order(app, ui);
             egui::ScrollArea::vertical().show(ui, |ui| {
                 // The ScrollArea automatically constrains width, but we need to ensure
                 // the content inside respects it. Use available_width() inside the ScrollArea.
@@ -741,6 +1510,9 @@ fn render_file_tree_panel(app: &mut VirtualBookApp, ui: &mut Ui) {
                         strip.cell(|ui| {
                             // Ensure the tree content respects the available width
                             ui.set_width(available_width);
+                            if let Some(scan_job) = &app.scan_job {
+                                render_scan_progress(ui, scan_job, &app.i18n);
+                            }
                             if let Some(filestore) = &mut app.file_store {
                                 let current_view = match app.current_typed_no.is_empty() {
                                     true => &mut filestore.default_view,
@@ -751,6 +1523,7 @@ fn render_file_tree_panel(app: &mut VirtualBookApp, ui: &mut Ui) {
                                     match display_tree(
                                         &mut app.appplayer,
                                         &mut app.current_typed_no,
+                                        &mut details_request,
                                         &mut view.root,
                                         ui,
                                         &app.i18n,
@@ -768,6 +1541,10 @@ fn render_file_tree_panel(app: &mut VirtualBookApp, ui: &mut Ui) {
                     });
             });
         });
+
+    if let Some((path, informations)) = details_request {
+        file_details::show_file_details(app, path, informations);
+    }
 }
 
 /// Render the virtual book preview component
@@ -811,6 +1588,7 @@ fn render_book_preview(app: &mut VirtualBookApp, ctx: &egui::Context, ui: &mut U
 #[cfg_attr(any(feature = "profiling"), profiling::function)]
 pub(crate) fn ui_content(app: &mut VirtualBookApp, ctx: &egui::Context, ui: &mut Ui) {
     render_file_tree_panel(app, ui);
+    file_details::render_file_details_panel(app, ui);
 
     egui::CentralPanel::default()
         .frame(Frame {
@@ -1,9 +1,10 @@
 // Ecran de visualisation du carton
 
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
-use egui::{Rangef, Ui};
+use egui::{text::LayoutJob, Align, FontId, Layout, Rangef, TextFormat, Ui};
 use egui_extras::{Size, StripBuilder};
+use player::NotesInformations;
 
 use crate::{virtualbookcomponent::VirtualBookComponent, VirtualBookApp};
 
@@ -13,9 +14,27 @@ pub(crate) fn ui_content(app: &mut VirtualBookApp, _ctx: &egui::Context, ui: &mu
         pid_regulated_offset_ms,
         xscale,
         appplayer,
+        i18n,
+        loop_edit,
         ..
     } = app;
 
+    // text card of the playlist, displayed full screen
+    if let Some(text) = appplayer.text_card() {
+        let text = text.to_string();
+        render_text_card(ui, &text);
+        if ui
+            .button(egui_phosphor::regular::FAST_FORWARD)
+            .on_hover_text(&i18n.go_to_next_file)
+            .clicked()
+        {
+            appplayer.next();
+        }
+        return;
+    }
+
+    let notes_informations = Arc::clone(&appplayer.notes_informations.read());
+
     let opt_vb = appplayer.virtual_book.read().clone();
     if let Some(vbc) = opt_vb {
         // draw canvas
@@ -37,9 +56,45 @@ pub(crate) fn ui_content(app: &mut VirtualBookApp, _ctx: &egui::Context, ui: &mu
                                 ui.label(name);
                             }
 
+                            let pause_icon = if appplayer.is_paused() {
+                                egui_phosphor::regular::PLAY_PAUSE
+                            } else {
+                                egui_phosphor::regular::PAUSE
+                            };
+                            if ui.button(pause_icon).clicked() {
+                                appplayer.pause();
+                            }
+
                             if ui.button(egui_phosphor::regular::FAST_FORWARD).clicked() {
                                 appplayer.next();
                             }
+
+                            // jump to a section of the file
+                            if !notes_informations.sections.is_empty() {
+                                ui.menu_button(
+                                    format!("{} {}", egui_phosphor::regular::LIST, &i18n.sections),
+                                    |ui| {
+                                        for section in notes_informations.sections.iter() {
+                                            if ui.button(&section.name).clicked() {
+                                                appplayer.seek(section.start);
+                                                ui.close_menu();
+                                            }
+                                        }
+                                    },
+                                );
+                            }
+
+                            // A-B loop, the points are set by tapping the book
+                            ui.toggle_value(loop_edit, egui_phosphor::regular::REPEAT)
+                                .on_hover_text(&i18n.loop_edit);
+                            if appplayer.play_loop.is_some()
+                                && ui
+                                    .button(egui_phosphor::regular::X)
+                                    .on_hover_text(&i18n.clear_loop)
+                                    .clicked()
+                            {
+                                appplayer.clear_loop();
+                            }
                         });
                     });
                     strip.cell(|ui| {
@@ -51,12 +106,100 @@ pub(crate) fn ui_content(app: &mut VirtualBookApp, _ctx: &egui::Context, ui: &mu
 
         let foffset: f64 = *pid_regulated_offset_ms;
 
-        ui.add(
+        if !notes_informations.lyrics.is_empty() {
+            let time = Duration::from_secs_f64(foffset.max(0.0) / 1000.0);
+            render_lyrics(ui, &notes_informations, time);
+        }
+
+        let book_response = ui.add(
             VirtualBookComponent::from_some_indexedvirtualbook(Some(Arc::clone(&vbc)))
                 .offset_ms(foffset)
                 .xscale(*xscale)
                 .scrollbar_width(32.0)
+                .markers(
+                    notes_informations
+                        .sections
+                        .iter()
+                        .map(|s| (s.start.as_micros() as i64, s.name.clone()))
+                        .collect(),
+                )
+                .loop_region(appplayer.play_loop.map(|(start, end)| {
+                    (start.as_micros() as i64, end.map(|e| e.as_micros() as i64))
+                }))
                 .set_background_texture_id(app.background_textureid),
         );
+
+        if *loop_edit && book_response.clicked() {
+            if let Some(pos) = book_response.interact_pointer_pos() {
+                let timestamp =
+                    VirtualBookComponent::timestamp_at(book_response.rect, pos, foffset, *xscale);
+                appplayer.set_loop_point(Duration::from_micros(timestamp.max(0) as u64));
+            }
+        }
     }
 }
+
+/// display a text card, centered in the available space
+fn render_text_card(ui: &mut Ui, text: &str) {
+    let height = ui.available_height() - ui.spacing().interact_size.y * 2.0;
+    ui.allocate_ui_with_layout(
+        egui::vec2(ui.available_width(), height.max(0.0)),
+        Layout::centered_and_justified(egui::Direction::TopDown),
+        |ui| {
+            ui.label(
+                egui::RichText::new(text)
+                    .size(64.0)
+                    .color(ui.visuals().strong_text_color()),
+            );
+        },
+    );
+}
+
+/// display the current lyric line (with the sung syllables highlighted) and the next one
+fn render_lyrics(ui: &mut Ui, notes_informations: &NotesInformations, time: Duration) {
+    let lyrics = &notes_informations.lyrics;
+    let current = notes_informations.lyric_line_at(time);
+    let next_index = current.map_or(0, |i| i + 1);
+
+    let sung_color = ui.visuals().strong_text_color();
+    let to_sing_color = ui.visuals().weak_text_color();
+
+    ui.with_layout(Layout::top_down(Align::Center), |ui| {
+        let mut job = LayoutJob::default();
+        if let Some(line) = current.map(|i| &lyrics[i]) {
+            for syllable in &line.syllables {
+                let color = if syllable.start <= time {
+                    sung_color
+                } else {
+                    to_sing_color
+                };
+                job.append(
+                    &syllable.text,
+                    0.0,
+                    TextFormat {
+                        font_id: FontId::proportional(32.0),
+                        color,
+                        ..Default::default()
+                    },
+                );
+            }
+        }
+        // keep the place, even if no line is sung
+        job.append(
+            " ",
+            0.0,
+            TextFormat {
+                font_id: FontId::proportional(32.0),
+                ..Default::default()
+            },
+        );
+        ui.label(job);
+
+        let next_text = lyrics.get(next_index).map(|l| l.text()).unwrap_or_default();
+        ui.label(
+            egui::RichText::new(next_text)
+                .size(22.0)
+                .color(to_sing_color),
+        );
+    });
+}
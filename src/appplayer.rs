@@ -2,6 +2,7 @@
 
 use std::{
     collections::HashSet,
+    path::{Path, PathBuf},
     sync::{
         mpsc::{channel, Receiver, Sender},
         Arc, Mutex,
@@ -13,9 +14,14 @@ use std::{
 
 use bookparsing::{Hole, VirtualBook};
 use egui::mutex::RwLock;
+use player::midiio::{OutputProfile, MINIMUM_LOOP_DURATION};
 use player::{Command, FileInformationsConstructor, NotesInformations, Player, Response};
 
-use crate::{playlist::PlayList, virtualbookcomponent::IndexedVirtualBook};
+use crate::{
+    playlist::{EntryKind, PlayList, PlayMode},
+    playmetadata::{PlayMetadataDatabase, METADATA_DATABASE_FILE},
+    virtualbookcomponent::IndexedVirtualBook,
+};
 
 use log::{debug, error, info, warn};
 
@@ -42,9 +48,33 @@ pub struct AppPlayer {
     /// virtual book
     pub virtual_book: Arc<RwLock<Option<Arc<IndexedVirtualBook>>>>,
 
+    /// notes informations of the played file (lyrics, ...)
+    pub notes_informations: Arc<RwLock<Arc<NotesInformations>>>,
+
+    /// A-B loop of the current play (play times), the end is not set while only A is selected
+    pub play_loop: Option<(Duration, Option<Duration>)>,
+
     // starting time wait
     pub waittime_between_file_play: f32,
 
+    /// name of the output device of the current player
+    pub output_name: Option<String>,
+
+    /// name of the output device selected by the user, used when the entries have no routing
+    pub default_output_name: Option<String>,
+
+    /// output device asked by the entry to play, the device must be opened before the play
+    pub output_request: Option<String>,
+
+    /// special entry in progress (pause, text card), with its start time
+    pub special_entry: Option<(Instant, EntryKind)>,
+
+    /// a text card has been started, to switch to the display screen
+    pub text_card_started: bool,
+
+    /// position in the file where the next play starts, when a previous session is resumed
+    pub resume_position: Option<Duration>,
+
     // appplayer cmd sender
     applayer_sender: Sender<AppPlayerThreadCommands>,
 
@@ -61,6 +91,8 @@ enum AppPlayerEvent {}
 
 enum AppPlayerBackgroundThreadCommands {
     PlayerChanged(Arc<Mutex<Box<dyn Player>>>),
+    /// the library folder changed, its metadata database caches the file informations
+    LibraryChanged(Option<PathBuf>),
 }
 
 type PlayerTriplet = (Box<dyn Player>, Receiver<Response>, Sender<Command>);
@@ -82,13 +114,22 @@ impl AppPlayer {
             play_mod: false,
             last_response: Arc::new(Mutex::new(None)),
             virtual_book: Arc::new(RwLock::new(None)),
+            notes_informations: Arc::new(RwLock::new(Arc::new(NotesInformations::default()))),
+            play_loop: None,
             start_play_time: Instant::now() - Duration::from_millis(10_000),
             waittime_between_file_play: 0_f32,
+            output_name: None,
+            default_output_name: None,
+            output_request: None,
+            special_entry: None,
+            text_card_started: false,
+            resume_position: None,
             applayer_sender: inner_control_thread.0,
             bgthread_sender: bg_thread_communication.0,
         };
 
         let vb_access = Arc::clone(&appplayer.virtual_book);
+        let notes_informations_access = Arc::clone(&appplayer.notes_informations);
         thread::spawn(move || {
             #[cfg(feature = "profiling")]
             profiling::register_thread!("AppPlayerThreadCommands");
@@ -134,6 +175,8 @@ impl AppPlayer {
 
                             let mut wlock = vb_access.write();
                             *wlock = Some(Arc::new(IndexedVirtualBook::from(&Arc::new(virt))));
+
+                            *notes_informations_access.write() = notes;
                         }
                     }
                 }
@@ -147,6 +190,9 @@ impl AppPlayer {
             #[cfg(feature = "profiling")]
             profiling::register_thread!("AppPlayer inner player command receiver");
             let mut current_player: Option<Arc<Mutex<Box<dyn Player>>>> = None;
+            let mut library: Option<(PathBuf, PlayMetadataDatabase)> = None;
+            // files whose informations can't be computed, not retried on each poll
+            let mut failed: HashSet<PathBuf> = HashSet::new();
             loop {
                 {
                     if let Ok(command) = bg_thread_communication
@@ -157,12 +203,30 @@ impl AppPlayer {
                             AppPlayerBackgroundThreadCommands::PlayerChanged(p) => {
                                 current_player = Some(p);
                             }
+                            AppPlayerBackgroundThreadCommands::LibraryChanged(folder) => {
+                                library = folder.and_then(|folder| {
+                                    let database = PlayMetadataDatabase::new(
+                                        folder
+                                            .join(METADATA_DATABASE_FILE)
+                                            .to_string_lossy()
+                                            .to_string(),
+                                    )
+                                    .map_err(|e| {
+                                        error!("fail to open the metadata database : {}", e)
+                                    })
+                                    .ok()?;
+                                    Some((folder, database))
+                                });
+                                failed.clear();
+                            }
                         }
-                    } else if current_player.clone().is_none() {
-                        info!("no current player, watch");
-                        continue;
                     }
                 }
+                // the informations are computed by the player
+                let Some(player) = current_player.clone() else {
+                    info!("no current player, watch");
+                    continue;
+                };
 
                 let mut playlist_copy = HashSet::new();
                 // get list
@@ -180,13 +244,13 @@ impl AppPlayer {
                 let mut computed = HashSet::new();
                 {
                     for p in &playlist_copy {
-                        if p.additional_informations.is_none() {
+                        if p.additional_informations.is_none() && !failed.contains(&p.path) {
                             // compute the additional informations
                             let mut local_info_getter: Option<
                                 Box<dyn FileInformationsConstructor>,
                             > = None;
 
-                            if let Ok(player) = &current_player.clone().unwrap().lock() {
+                            if let Ok(player) = &player.lock() {
                                 if let Ok(info_getter) = player.create_information_getter() {
                                     local_info_getter = Some(info_getter);
                                 }
@@ -196,9 +260,27 @@ impl AppPlayer {
                                 info!("compute informations for {}", &p.path.display());
 
                                 let mut computed_p = p.clone();
-                                if let Ok(result) = info_getter.compute(&p.path) {
-                                    computed_p.additional_informations = Some(result.clone());
-                                    computed.insert(computed_p);
+                                let result = match &library {
+                                    Some((folder, database)) => database.cached_file_informations(
+                                        folder,
+                                        &p.path,
+                                        info_getter.as_mut(),
+                                    ),
+                                    None => info_getter.compute(&p.path),
+                                };
+                                match result {
+                                    Ok(result) => {
+                                        computed_p.additional_informations = Some(result);
+                                        computed.insert(computed_p);
+                                    }
+                                    Err(e) => {
+                                        warn!(
+                                            "fail to compute informations for {} : {}",
+                                            &p.path.display(),
+                                            e
+                                        );
+                                        failed.insert(p.path.clone());
+                                    }
                                 }
                             }
                         }
@@ -223,7 +305,7 @@ impl AppPlayer {
 
                         let mut total_duration: Duration = Duration::ZERO;
                         // update the global time of the playlist
-                        for p in &playlist.file_list {
+                        for p in playlist.remaining() {
                             if let Some(additional) = &p.additional_informations {
                                 if let Some(length) = &additional.duration {
                                     total_duration += *length;
@@ -243,6 +325,16 @@ impl AppPlayer {
         appplayer
     }
 
+    /// define the library folder, the computed file informations are cached in its metadata database
+    pub fn set_library_folder(&self, folder: Option<PathBuf>) {
+        if let Err(e) = self
+            .bgthread_sender
+            .send(AppPlayerBackgroundThreadCommands::LibraryChanged(folder))
+        {
+            error!("fail to send the library folder : {}", e);
+        }
+    }
+
     pub fn set_waittime_between_file_play(&mut self, wait_time: f32) {
         self.waittime_between_file_play = wait_time;
     }
@@ -315,19 +407,70 @@ impl AppPlayer {
     }
 
     pub fn play_file_on_top(&mut self) {
+        self.play_current(true);
+    }
+
+    /// play the current file on the current output, whatever the routing of the entry
+    pub fn play_file_on_top_on_current_output(&mut self) {
+        self.play_current(false);
+    }
+
+    fn play_current(&mut self, check_output: bool) {
+        self.special_entry = None;
         if let Some(player) = &self.player {
             let mut p = player.lock().unwrap();
             p.stop();
-            let locked_playlist = self.playlist.lock().expect("fail to get lock on playlist");
-            if !locked_playlist.file_list.is_empty() {
-                if let Some(n) = locked_playlist.file_list.first() {
-                    self.start_play_time = Instant::now(); // before play
-                    if let Err(e) = p.start_play(&n.path, Some(self.waittime_between_file_play)) {
-                        error!("error in playing file : {}", e);
-                    } else {
+            let mut locked_playlist = self.playlist.lock().expect("fail to get lock on playlist");
+            if let Some(n) = locked_playlist.current() {
+                match &n.kind {
+                    EntryKind::File => {}
+                    EntryKind::Stop => {
+                        // leave the play mode, the next play starts after the marker
+                        info!("stop marker reached");
+                        self.play_mod = false;
+                        locked_playlist.skip();
+                        return;
+                    }
+                    kind => {
+                        if let EntryKind::TextCard(..) = kind {
+                            self.text_card_started = true;
+                        }
+                        self.special_entry = Some((Instant::now(), kind.clone()));
                         self.play_mod = true;
+                        return;
                     }
                 }
+
+                let wanted_output = n
+                    .settings
+                    .output
+                    .clone()
+                    .or_else(|| self.default_output_name.clone());
+                if check_output && wanted_output.is_some() && wanted_output != self.output_name {
+                    // the play starts once the output is opened
+                    debug!("output change requested : {:?}", wanted_output);
+                    self.output_request = wanted_output;
+                    return;
+                }
+
+                let wait = n
+                    .settings
+                    .wait
+                    .map(|w| w.as_secs_f32())
+                    .unwrap_or(self.waittime_between_file_play);
+
+                let settings = n
+                    .settings
+                    .resumed_play_settings(self.resume_position.take());
+
+                self.play_loop = None;
+                self.start_play_time = Instant::now(); // before play
+                if let Err(e) = p.start_play_with_settings(&n.path, Some(wait), &settings) {
+                    error!("error in playing file : {}", e);
+                } else {
+                    self.play_mod = true;
+                    locked_playlist.record_current_play();
+                }
             }
         }
     }
@@ -348,13 +491,58 @@ impl AppPlayer {
 
     /// stop the play
     pub fn stop(&mut self) {
+        self.special_entry = None;
         if let Some(player) = &self.player {
             let mut p = player.lock().unwrap();
             p.stop();
         }
     }
 
-    /// next file, and "unpop" the currently played
+    /// text of the text card currently displayed
+    pub fn text_card(&self) -> Option<&str> {
+        match &self.special_entry {
+            Some((_, EntryKind::TextCard(text, _))) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// go to the next entry, when the time of the current pause or text card is elapsed
+    pub fn update_special_entry(&mut self) {
+        let elapsed = match &self.special_entry {
+            Some((start, EntryKind::Pause(duration)))
+            | Some((start, EntryKind::TextCard(_, Some(duration)))) => start.elapsed() >= *duration,
+            _ => false,
+        };
+        if elapsed {
+            self.special_entry = None;
+            self.file_ended();
+        }
+    }
+
+    /// replace the queue by a scheduled program (file or playlist), and play it,
+    /// the queue of the operator is restored when the program ends
+    pub fn play_program(&mut self, path: &Path, play_mode: PlayMode, single_file: bool) {
+        self.playlist
+            .lock()
+            .expect("fail to lock the playlist")
+            .start_program(path, play_mode, single_file);
+        self.play_file_on_top();
+    }
+
+    /// stop the scheduled program, and restore the queue of the operator
+    pub fn end_program(&mut self) {
+        let restored = self
+            .playlist
+            .lock()
+            .expect("fail to lock the playlist")
+            .end_program();
+        if restored {
+            info!("end of the scheduled program, the queue is restored");
+            self.play_mod = false;
+        }
+    }
+
+    /// next file, asked by the user
     pub fn next(&mut self) {
         self.playlist
             .lock()
@@ -366,6 +554,67 @@ impl AppPlayer {
         }
     }
 
+    /// go back to the previously played file
+    pub fn previous(&mut self) {
+        let moved = self
+            .playlist
+            .lock()
+            .expect("fail to get lock on playlist")
+            .previous();
+
+        if moved && self.play_mod {
+            self.play_file_on_top();
+        }
+    }
+
+    /// replay the current file from the beginning,
+    /// or the last played file if nothing is playing
+    pub fn replay_last(&mut self) {
+        if self.is_playing() {
+            if let Err(error) = self.commands.send(Command::Replay) {
+                debug!("error in sending command :{}", error);
+            }
+        } else {
+            let last = self
+                .playlist
+                .lock()
+                .expect("fail to get lock on playlist")
+                .history
+                .len()
+                .checked_sub(1);
+            if let Some(index) = last {
+                self.replay_history_entry(index);
+            }
+        }
+    }
+
+    /// play again an element of the history
+    pub fn replay_history_entry(&mut self, index: usize) {
+        self.playlist
+            .lock()
+            .expect("fail to get lock on playlist")
+            .replay_history_entry(index);
+
+        self.play_file_on_top();
+    }
+
+    /// the current file has been played, continue depending on the play mode
+    pub fn file_ended(&mut self) {
+        let finished = {
+            let mut locked_playlist = self.playlist.lock().expect("fail to get lock on playlist");
+            locked_playlist.file_ended();
+            locked_playlist.current().is_none()
+        };
+        if finished {
+            // the queue of the operator is not played after a scheduled program
+            self.end_program();
+        }
+
+        if self.play_mod {
+            self.play_file_on_top();
+        }
+    }
+
     pub fn is_playlist_empty(&self) -> bool {
         self.playlist
             .lock()
@@ -378,11 +627,7 @@ impl AppPlayer {
     /// Returns None if there's no next file or if the playlist is locked
     pub fn next_file_name(&self) -> Option<String> {
         if let Ok(playlist) = self.playlist.lock() {
-            if playlist.file_list.len() > 1 {
-                Some(playlist.file_list[1].name.clone())
-            } else {
-                None
-            }
+            playlist.upcoming().map(|e| e.name.clone())
         } else {
             None
         }
@@ -397,6 +642,92 @@ impl AppPlayer {
         }
     }
 
+    /// is the current play paused ?
+    pub fn is_paused(&self) -> bool {
+        if let Some(player) = &self.player {
+            let p = player.lock().unwrap();
+            p.is_paused()
+        } else {
+            false
+        }
+    }
+
+    /// move the current play to the given play time
+    pub fn seek(&self, position: Duration) {
+        if let Err(error) = self.commands.send(Command::Seek(position)) {
+            debug!("error in sending command :{}", error);
+        }
+    }
+
+    /// define a loop point, the first one is the start (A), the second one the end (B),
+    /// the loop is activated once both are defined, an end too close to the start is ignored
+    pub fn set_loop_point(&mut self, position: Duration) {
+        match self.play_loop {
+            Some((start, None))
+                if start.max(position) - start.min(position) <= MINIMUM_LOOP_DURATION =>
+            {
+                // the player would not loop, the start is kept
+                debug!("loop too short, the end is ignored");
+            }
+            Some((start, None)) => {
+                let (start, end) = if start <= position {
+                    (start, position)
+                } else {
+                    (position, start)
+                };
+                self.play_loop = Some((start, Some(end)));
+                if let Err(error) = self.commands.send(Command::SetLoop(start, end)) {
+                    debug!("error in sending command :{}", error);
+                }
+            }
+            Some((_, Some(_))) => {
+                // a new loop is started
+                self.clear_loop();
+                self.play_loop = Some((position, None));
+            }
+            None => {
+                self.play_loop = Some((position, None));
+            }
+        }
+    }
+
+    /// remove the loop, the play continue to the end of the file
+    pub fn clear_loop(&mut self) {
+        self.play_loop = None;
+        if let Err(error) = self.commands.send(Command::ClearLoop) {
+            debug!("error in sending command :{}", error);
+        }
+    }
+
+    /// change the master volume of the player (between 0 and 1)
+    pub fn set_volume(&self, volume: f32) {
+        if let Some(player) = &self.player {
+            player.lock().unwrap().set_volume(volume);
+        }
+    }
+
+    /// strike again the held notes when resuming from pause, the current play is kept
+    pub fn set_restrike_on_resume(&self, restrike: bool) {
+        if let Some(player) = &self.player {
+            player.lock().unwrap().set_restrike_on_resume(restrike);
+        }
+    }
+
+    /// change the fade out time when the play is stopped, the current play is kept
+    pub fn set_fade_out(&self, fade_out: Duration) {
+        if let Some(player) = &self.player {
+            player.lock().unwrap().set_fade_out(fade_out);
+        }
+    }
+
+    /// change the output options of the player, used from the next play
+    pub fn set_output_profile(&self, profile: OutputProfile) {
+        if let Some(player) = &self.player {
+            player.lock().unwrap().set_output_profile(profile);
+        }
+    }
+
+    /// toggle the pause of the current play
     pub fn pause(&self) {
         // send pause
         if let Err(error) = self.commands.send(Command::Pause) {
@@ -404,3 +735,47 @@ impl AppPlayer {
         }
     }
 }
+
+#[test]
+pub fn test_library_changed_without_player() {
+    let appplayer = AppPlayer::new();
+    appplayer
+        .playlist
+        .lock()
+        .unwrap()
+        .add_from_path(&PathBuf::from("waltz.mid"));
+
+    let folder = std::env::temp_dir().join(format!("vbp_no_player_{}", std::process::id()));
+    std::fs::create_dir_all(&folder).unwrap();
+    appplayer.set_library_folder(Some(folder.clone()));
+    // wait for the database opening, then for the informations pass
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !folder.join(METADATA_DATABASE_FILE).exists() {
+        assert!(Instant::now() < deadline, "the database is not opened");
+        thread::sleep(Duration::from_millis(10));
+    }
+    thread::sleep(Duration::from_millis(500));
+    // the background thread is still running, it receives the commands
+    assert!(appplayer
+        .bgthread_sender
+        .send(AppPlayerBackgroundThreadCommands::LibraryChanged(None))
+        .is_ok());
+    std::fs::remove_dir_all(&folder).unwrap();
+}
+
+#[test]
+pub fn test_set_loop_point() {
+    let mut appplayer = AppPlayer::new();
+    appplayer.set_loop_point(Duration::from_secs(10));
+    assert_eq!(appplayer.play_loop, Some((Duration::from_secs(10), None)));
+
+    // too short for the player, the start is kept
+    appplayer.set_loop_point(Duration::from_secs(10) + MINIMUM_LOOP_DURATION);
+    assert_eq!(appplayer.play_loop, Some((Duration::from_secs(10), None)));
+
+    appplayer.set_loop_point(Duration::from_secs(4));
+    assert_eq!(
+        appplayer.play_loop,
+        Some((Duration::from_secs(4), Some(Duration::from_secs(10))))
+    );
+}
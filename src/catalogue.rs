@@ -0,0 +1,488 @@
+//! Numeric catalogue of the library, the visitors choose the pieces by their number
+//! in a printed list
+//!
+//! the number of a file is read from its name prefix ("12 - Valse.mid"), or assigned
+//! in the catalogue file of the library folder, one "number;relative path" per line
+//!
+//! the catalogue is exported as a printable HTML page and a CSV file, in a folder
+//! chosen by the user
+//!
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::duration_to_mm_ss;
+
+/// catalogue file, in the library folder
+pub const CATALOGUE_FILE: &str = "catalogue.txt";
+
+/// names of the exported catalogue files
+pub const CATALOGUE_HTML_FILE: &str = "catalogue.html";
+pub const CATALOGUE_CSV_FILE: &str = "catalogue.csv";
+
+/// separators accepted after the number, in the file names and the catalogue file
+const NUMBER_SEPARATORS: [char; 7] = [' ', '-', '_', '.', ')', ';', '\t'];
+
+/// number at the start of a file name, followed by a separator or the end of the name,
+/// "12 - Valse" and "012_Valse" give 12, "12b Valse" and "Op.12" give nothing
+pub fn number_prefix(name: &str) -> Option<u32> {
+    let digits_end = name
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(name.len());
+    if digits_end == 0 {
+        return None;
+    }
+    match name[digits_end..].chars().next() {
+        None => name[..digits_end].parse().ok(),
+        Some(c) if NUMBER_SEPARATORS.contains(&c) => name[..digits_end].parse().ok(),
+        Some(_) => None,
+    }
+}
+
+/// file of the catalogue, with its number
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CatalogueEntry {
+    pub number: u32,
+    pub path: PathBuf,
+}
+
+impl CatalogueEntry {
+    /// displayed title, the file name without the number prefix and the extension
+    pub fn title(&self) -> String {
+        let stem = self
+            .path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if number_prefix(&stem).is_some() {
+            let title = stem
+                .trim_start_matches(|c: char| c.is_ascii_digit())
+                .trim_start_matches(|c: char| NUMBER_SEPARATORS.contains(&c));
+            if !title.is_empty() {
+                return title.to_string();
+            }
+        }
+        stem
+    }
+}
+
+/// result of the search of a number in the catalogue
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Lookup {
+    Found(CatalogueEntry),
+    NotFound,
+    /// several files have the same number prefix, none is chosen
+    Ambiguous(Vec<PathBuf>),
+}
+
+/// numbers of the library files
+#[derive(Debug, Default, Clone)]
+pub struct Catalogue {
+    entries: BTreeMap<u32, PathBuf>,
+    /// numbers given to several files
+    conflicts: BTreeMap<u32, Vec<PathBuf>>,
+}
+
+impl Catalogue {
+    /// number the files, the numbers of the catalogue file (if any) replace the name prefixes
+    pub fn build(base_path: &Path, files: &[PathBuf], catalogue_file: Option<&str>) -> Self {
+        let mut numbered: BTreeMap<u32, Vec<PathBuf>> = BTreeMap::new();
+
+        let known: HashSet<&PathBuf> = files.iter().collect();
+        let mut assigned: HashMap<PathBuf, u32> = HashMap::new();
+        for (line_no, line) in catalogue_file.unwrap_or_default().lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let digits_end = line
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(line.len());
+            let relative = line[digits_end..].trim_start_matches(NUMBER_SEPARATORS);
+            let path = base_path.join(relative);
+            match line[..digits_end].parse::<u32>() {
+                Ok(number) if !relative.is_empty() && known.contains(&path) => {
+                    assigned.insert(path, number);
+                }
+                _ => warn!(
+                    "line {} of the catalogue file is ignored : {}",
+                    line_no + 1,
+                    line
+                ),
+            }
+        }
+
+        for file in files {
+            let number = assigned.get(file).copied().or_else(|| {
+                file.file_stem()
+                    .and_then(|stem| number_prefix(&stem.to_string_lossy()))
+            });
+            if let Some(number) = number {
+                numbered.entry(number).or_default().push(file.clone());
+            }
+        }
+
+        let mut catalogue = Catalogue::default();
+        for (number, mut paths) in numbered {
+            // an assigned number wins over the name prefixes
+            let assigned_paths: Vec<PathBuf> = paths
+                .iter()
+                .filter(|p| assigned.get(*p) == Some(&number))
+                .cloned()
+                .collect();
+            if assigned_paths.len() == 1 {
+                paths = assigned_paths;
+            }
+            if paths.len() == 1 {
+                catalogue.entries.insert(number, paths.remove(0));
+            } else {
+                warn!(
+                    "the number {} is given to several files : {:?}",
+                    number, paths
+                );
+                catalogue.conflicts.insert(number, paths);
+            }
+        }
+        catalogue
+    }
+
+    /// number the files of the library folder, using its catalogue file if there is one
+    pub fn load(base_path: &Path, files: &[PathBuf]) -> Self {
+        let catalogue_file = fs::read_to_string(base_path.join(CATALOGUE_FILE)).ok();
+        let catalogue = Catalogue::build(base_path, files, catalogue_file.as_deref());
+        info!(
+            "catalogue of {} numbers, {} conflicts",
+            catalogue.entries.len(),
+            catalogue.conflicts.len()
+        );
+        catalogue
+    }
+
+    /// exact search of a number
+    pub fn lookup(&self, number: u32) -> Lookup {
+        if let Some(path) = self.entries.get(&number) {
+            return Lookup::Found(CatalogueEntry {
+                number,
+                path: path.clone(),
+            });
+        }
+        match self.conflicts.get(&number) {
+            Some(paths) => Lookup::Ambiguous(paths.clone()),
+            None => Lookup::NotFound,
+        }
+    }
+
+    /// search of a typed text, which must be a number
+    pub fn lookup_typed(&self, typed: &str) -> Lookup {
+        match typed.trim().parse::<u32>() {
+            Ok(number) => self.lookup(number),
+            Err(_) => Lookup::NotFound,
+        }
+    }
+
+    /// the numbered files, by number
+    pub fn entries(&self) -> impl Iterator<Item = CatalogueEntry> + '_ {
+        self.entries.iter().map(|(number, path)| CatalogueEntry {
+            number: *number,
+            path: path.clone(),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// lines of the exported catalogue, grouped by folder, by number in each folder
+    pub fn lines(
+        &self,
+        base_path: &Path,
+        details: &HashMap<PathBuf, EntryDetails>,
+    ) -> Vec<CatalogueLine> {
+        let mut lines: Vec<CatalogueLine> = self
+            .entries()
+            .map(|entry| {
+                let folder = entry
+                    .path
+                    .parent()
+                    .and_then(|parent| parent.strip_prefix(base_path).ok())
+                    .map(|relative| relative.to_string_lossy().replace('\\', "/"))
+                    .unwrap_or_default();
+                let entry_details = details.get(&entry.path).cloned().unwrap_or_default();
+                CatalogueLine {
+                    number: entry.number,
+                    title: entry.title(),
+                    folder,
+                    duration: entry_details.duration,
+                    star_count: entry_details.star_count,
+                }
+            })
+            .collect();
+        lines.sort_by(|a, b| a.folder.cmp(&b.folder).then(a.number.cmp(&b.number)));
+        lines
+    }
+}
+
+/// informations of a catalogue file, read from the metadata database
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EntryDetails {
+    pub duration: Option<Duration>,
+    pub star_count: u32,
+}
+
+/// line of the exported catalogue
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CatalogueLine {
+    pub number: u32,
+    pub title: String,
+    /// folder, relative to the library folder, empty for the library folder itself
+    pub folder: String,
+    pub duration: Option<Duration>,
+    pub star_count: u32,
+}
+
+/// column names of the exported HTML page, in the user language
+pub struct CatalogueHeadings<'a> {
+    pub title: &'a str,
+    pub number: &'a str,
+    pub piece: &'a str,
+    pub duration: &'a str,
+    pub stars: &'a str,
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn escape_csv(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+/// page style, a compact table per folder, the folders are not cut between two printed pages
+const HTML_STYLE: &str = "\
+body { font-family: sans-serif; margin: 2em; color: #000; }
+h1 { text-align: center; }
+h2 { border-bottom: 2px solid #444; margin-top: 1.5em; }
+section { break-inside: avoid; page-break-inside: avoid; }
+table { width: 100%; border-collapse: collapse; }
+th, td { padding: 0.2em 0.5em; border-bottom: 1px solid #ccc; }
+th { text-align: left; }
+td.number { width: 4em; font-weight: bold; font-size: 1.2em; }
+td.duration, td.stars { width: 5em; text-align: right; }
+@media print {
+  body { margin: 0; }
+  @page { margin: 1.5cm; }
+}
+";
+
+/// standalone HTML page of the catalogue, one section per folder
+pub fn to_html(headings: &CatalogueHeadings<'_>, lines: &[CatalogueLine]) -> String {
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+         <style>\n{}</style>\n</head>\n<body>\n<h1>{}</h1>\n",
+        escape_html(headings.title),
+        HTML_STYLE,
+        escape_html(headings.title)
+    );
+
+    let mut current_folder: Option<&str> = None;
+    for line in lines {
+        if current_folder != Some(line.folder.as_str()) {
+            if current_folder.is_some() {
+                html.push_str("</table>\n</section>\n");
+            }
+            current_folder = Some(line.folder.as_str());
+            html.push_str("<section>\n");
+            if !line.folder.is_empty() {
+                html.push_str(&format!("<h2>{}</h2>\n", escape_html(&line.folder)));
+            }
+            html.push_str(&format!(
+                "<table>\n<tr><th>{}</th><th>{}</th><th>{}</th><th>{}</th></tr>\n",
+                escape_html(headings.number),
+                escape_html(headings.piece),
+                escape_html(headings.duration),
+                escape_html(headings.stars)
+            ));
+        }
+        html.push_str(&format!(
+            "<tr><td class=\"number\">{}</td><td>{}</td><td class=\"duration\">{}</td>\
+             <td class=\"stars\">{}</td></tr>\n",
+            line.number,
+            escape_html(&line.title),
+            line.duration
+                .map(|d| duration_to_mm_ss(&d))
+                .unwrap_or_default(),
+            if line.star_count > 0 {
+                format!("&#9733; {}", line.star_count)
+            } else {
+                String::new()
+            }
+        ));
+    }
+    if current_folder.is_some() {
+        html.push_str("</table>\n</section>\n");
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// CSV file of the catalogue, durations in seconds
+pub fn to_csv(lines: &[CatalogueLine]) -> String {
+    let mut csv = String::from("number,title,folder,duration,stars\n");
+    for line in lines {
+        csv.push_str(&format!(
+            "{},{},{},{},{}\n",
+            line.number,
+            escape_csv(&line.title),
+            escape_csv(&line.folder),
+            line.duration
+                .map(|d| d.as_secs().to_string())
+                .unwrap_or_default(),
+            line.star_count
+        ));
+    }
+    csv
+}
+
+/// write the HTML and CSV catalogues in the folder, return the written files
+pub fn export(
+    folder: &Path,
+    headings: &CatalogueHeadings<'_>,
+    lines: &[CatalogueLine],
+) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let html_path = folder.join(CATALOGUE_HTML_FILE);
+    fs::write(&html_path, to_html(headings, lines))?;
+    let csv_path = folder.join(CATALOGUE_CSV_FILE);
+    fs::write(&csv_path, to_csv(lines))?;
+    info!("catalogue of {} files exported", lines.len());
+    Ok(vec![html_path, csv_path])
+}
+
+/// catalogue mode of the number pad, the typed number selects exactly one file,
+/// which is confirmed before being added to the playlist
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CatalogueSelection {
+    pub enabled: bool,
+    #[serde(skip)]
+    pub catalogue: Catalogue,
+    /// file matched by the typed number, waiting for the confirmation
+    #[serde(skip)]
+    pub confirmation: Option<CatalogueEntry>,
+}
+
+#[test]
+pub fn test_number_prefix() {
+    assert_eq!(number_prefix("12 - Valse"), Some(12));
+    assert_eq!(number_prefix("012_Valse"), Some(12));
+    assert_eq!(number_prefix("112"), Some(112));
+    assert_eq!(number_prefix("12b Valse"), None);
+    assert_eq!(number_prefix("Op.12"), None);
+    assert_eq!(number_prefix("Valse"), None);
+}
+
+#[test]
+pub fn test_catalogue_exact_lookup() {
+    let base = PathBuf::from("/books");
+    let files = vec![
+        base.join("12 - Valse de Paris.mid"),
+        base.join("112 - Marche.mid"),
+        base.join("Op.12 Nocturne.mid"),
+        base.join("polkas").join("7 - Polka.mid"),
+        base.join("polkas").join("7 - Mazurka.mid"),
+        base.join("Sans numero.book"),
+    ];
+    let catalogue_file = "# numbers given by hand\n\
+                          40;Sans numero.book\n\
+                          7\tpolkas/7 - Mazurka.mid\n\
+                          99;missing.mid\n";
+    let catalogue = Catalogue::build(&base, &files, Some(catalogue_file));
+
+    match catalogue.lookup_typed("12") {
+        Lookup::Found(entry) => {
+            assert_eq!(entry.path, files[0]);
+            assert_eq!(entry.title(), "Valse de Paris");
+        }
+        other => panic!("unexpected lookup {:?}", other),
+    }
+    assert!(matches!(catalogue.lookup(112), Lookup::Found(_)));
+    assert_eq!(
+        catalogue.lookup(40),
+        Lookup::Found(CatalogueEntry {
+            number: 40,
+            path: files[5].clone()
+        })
+    );
+    // the assigned number wins over the other name prefix
+    assert_eq!(
+        catalogue.lookup(7),
+        Lookup::Found(CatalogueEntry {
+            number: 7,
+            path: files[4].clone()
+        })
+    );
+    assert_eq!(catalogue.lookup(99), Lookup::NotFound);
+    assert_eq!(catalogue.lookup_typed("1 2"), Lookup::NotFound);
+    assert_eq!(catalogue.entries().count(), 4);
+
+    let ambiguous = Catalogue::build(&base, &files, None);
+    assert!(matches!(ambiguous.lookup(7), Lookup::Ambiguous(paths) if paths.len() == 2));
+}
+
+#[test]
+pub fn test_catalogue_export() {
+    let base = PathBuf::from("/books");
+    let files = vec![
+        base.join("polkas").join("7 - Polka, \"rapide\".mid"),
+        base.join("2 - Valse <lente>.mid"),
+        base.join("1 - Marche.mid"),
+    ];
+    let catalogue = Catalogue::build(&base, &files, None);
+    let mut details = HashMap::new();
+    details.insert(
+        files[1].clone(),
+        EntryDetails {
+            duration: Some(Duration::from_secs(125)),
+            star_count: 3,
+        },
+    );
+    let lines = catalogue.lines(&base, &details);
+    assert_eq!(
+        lines.iter().map(|l| l.number).collect::<Vec<u32>>(),
+        vec![1, 2, 7]
+    );
+    assert_eq!(lines[2].folder, "polkas");
+
+    assert_eq!(
+        to_csv(&lines),
+        "number,title,folder,duration,stars\n\
+         1,Marche,,,0\n\
+         2,Valse <lente>,,125,3\n\
+         7,\"Polka, \"\"rapide\"\"\",polkas,,0\n"
+    );
+
+    let headings = CatalogueHeadings {
+        title: "Catalogue",
+        number: "No",
+        piece: "Piece",
+        duration: "Duration",
+        stars: "Stars",
+    };
+    let html = to_html(&headings, &lines);
+    assert!(html.contains("<td>Valse &lt;lente&gt;</td><td class=\"duration\">02:05</td>"));
+    assert!(html.contains("<h2>polkas</h2>"));
+    assert_eq!(html.matches("<section>").count(), 2);
+}
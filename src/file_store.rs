@@ -2,13 +2,25 @@
 //! and give a tree structure to permit to :
 //!   - filter the files dynamically
 //!   - create views
+//!
+//! the file nodes are shared between threads, the tree is filled by a background scan,
+//! the views are only used by the user interface
 
 use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::error::Error;
+use std::ffi::OsStr;
 use std::fs::metadata;
-use std::rc::Weak;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard, Weak};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use log::{debug, error};
+use chrono::{DateTime, Utc};
+use log::{debug, error, info};
+use player::FileInformations;
+use serde::{Deserialize, Serialize};
 use std::fmt::{Debug, Display};
 use std::{path::PathBuf, rc::Rc};
 
@@ -47,12 +59,18 @@ pub struct FileNode {
     pub name: String,
     pub path: PathBuf,
     pub is_folder: bool,
-    pub parent_folder: Option<Weak<RefCell<FileNode>>>,
-    pub folder_files: Vec<Rc<RefCell<FileNode>>>,
+    pub parent_folder: Option<Weak<RwLock<FileNode>>>,
+    pub folder_files: Vec<SharedFileNode>,
     /// Number of times this file has been played (memoized from database)
     pub play_count: Option<u32>,
     /// Number of times this file has been starred (memoized from database)
     pub star_count: Option<u32>,
+    /// Computed informations of the file (memoized from database)
+    pub informations: Option<FileInformations>,
+    /// Last time this file has been played (memoized from database)
+    pub last_played: Option<DateTime<Utc>>,
+    /// modification time, when the file has been added to the library
+    pub modified: Option<SystemTime>,
 }
 
 impl Display for FileNode {
@@ -99,8 +117,11 @@ impl FileNode {
                         is_folder: is_dir,
                         folder_files: vec![],
                         parent_folder: None,
-                        play_count: None, // Will be populated by background thread
-                        star_count: None, // Will be populated by background thread
+                        play_count: None,   // Will be populated by background thread
+                        star_count: None,   // Will be populated by background thread
+                        informations: None, // Will be populated by background thread
+                        last_played: None,  // Will be populated by background thread
+                        modified: md.modified().ok(),
                     };
                     Ok(n)
                 }
@@ -113,16 +134,32 @@ impl FileNode {
             )),
         }
     }
-    pub fn set_parent(&mut self, parent: &Option<Rc<RefCell<FileNode>>>) {
+    pub fn set_parent(&mut self, parent: &Option<SharedFileNode>) {
         match parent {
             None => self.parent_folder = None,
-            Some(p) => self.parent_folder = Some(Rc::downgrade(p)),
+            Some(p) => self.parent_folder = Some(Arc::downgrade(p)),
         }
     }
     pub fn folder(&self) -> bool {
         self.is_folder
     }
 
+    /// folder grouping files in a view, it is not in the tree
+    fn group(name: &str) -> SharedFileNode {
+        Arc::new(RwLock::new(FileNode {
+            name: name.to_string(),
+            path: PathBuf::from(format!("{}{}", GROUP_PATH_PREFIX, name)),
+            is_folder: true,
+            parent_folder: None,
+            folder_files: vec![],
+            play_count: None,
+            star_count: None,
+            informations: None,
+            last_played: None,
+            modified: None,
+        }))
+    }
+
     #[allow(unused)]
     pub fn accept(&self, visitor: &dyn Visitor) {
         visitor.visit(self);
@@ -133,76 +170,233 @@ pub trait Visitor {
     fn visit(&self, node: &FileNode);
 }
 
+/// file node shared between the user interface and the background threads
+pub type SharedFileNode = Arc<RwLock<FileNode>>;
+
+/// access to a shared file node, a poisoned lock is not expected as the nodes
+/// are only locked for short field accesses
+pub trait FileNodeLock {
+    fn read_node(&self) -> RwLockReadGuard<'_, FileNode>;
+    fn write_node(&self) -> RwLockWriteGuard<'_, FileNode>;
+}
+
+impl FileNodeLock for SharedFileNode {
+    fn read_node(&self) -> RwLockReadGuard<'_, FileNode> {
+        self.read().expect("fail to read the file node")
+    }
+    fn write_node(&self) -> RwLockWriteGuard<'_, FileNode> {
+        self.write().expect("fail to write the file node")
+    }
+}
+
+/// progress and cancellation of a scan, shared with the scanning thread
+#[derive(Debug, Default)]
+pub struct ScanControl {
+    files: AtomicUsize,
+    folders: AtomicUsize,
+    cancelled: AtomicBool,
+    finished: AtomicBool,
+}
+
+impl ScanControl {
+    /// number of files and folders found
+    pub fn progress(&self) -> (usize, usize) {
+        (
+            self.files.load(Ordering::Relaxed),
+            self.folders.load(Ordering::Relaxed),
+        )
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::Acquire)
+    }
+}
+
+/// scan of the library running in background, the scan is cancelled when the job is dropped
+#[derive(Debug)]
+pub struct ScanJob {
+    pub control: Arc<ScanControl>,
+}
+
+impl Drop for ScanJob {
+    fn drop(&mut self) {
+        if !self.control.is_finished() {
+            self.control.cancel();
+        }
+    }
+}
+
 /// root structure for the tree of files and folders
 #[derive(Debug)]
 pub struct FileStore {
     pub base_path: PathBuf,
-    pub root: Rc<RefCell<FileNode>>,
+    pub root: SharedFileNode,
     // view displayed when the standard display is done
     pub default_view: Option<FileView>,
     // view when filtering
     pub filtered_view: Option<FileView>,
+    /// arrangement of the default view
+    pub order: ViewOrder,
 }
 
 type ExtensionsFilter = Option<Vec<String>>;
 
+/// prefix of the paths of the group folders, these paths don't exist in the library
+const GROUP_PATH_PREFIX: &str = "group:";
+
+/// name of the group of the files whose scale is not known
+pub const UNKNOWN_SCALE_GROUP: &str = "?";
+
+/// order of the files in the default view
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SortMode {
+    #[default]
+    Name,
+    /// most played first
+    PlayCount,
+    /// most starred first
+    Stars,
+    /// last played first
+    LastPlayed,
+    /// shortest first
+    Duration,
+    /// last added (or modified) first
+    DateAdded,
+}
+
+impl SortMode {
+    pub const ALL: [SortMode; 6] = [
+        SortMode::Name,
+        SortMode::PlayCount,
+        SortMode::Stars,
+        SortMode::LastPlayed,
+        SortMode::Duration,
+        SortMode::DateAdded,
+    ];
+
+    /// the order uses the play and star counts of the metadata database,
+    /// these must be known for all the files, not only the displayed ones
+    pub fn uses_play_statistics(&self) -> bool {
+        matches!(
+            self,
+            SortMode::PlayCount | SortMode::Stars | SortMode::LastPlayed
+        )
+    }
+
+    /// sort key of a file, the greatest keys come first and the files without key last,
+    /// None for all the files when sorting by name
+    fn key(&self, node: &FileNode) -> Option<i64> {
+        match self {
+            SortMode::Name => None,
+            SortMode::PlayCount => node.play_count.map(i64::from),
+            SortMode::Stars => node.star_count.map(i64::from),
+            SortMode::LastPlayed => node.last_played.map(|d| d.timestamp_millis()),
+            SortMode::Duration => node
+                .informations
+                .as_ref()
+                .and_then(|i| i.duration)
+                .map(|d| -(d.as_millis() as i64)),
+            SortMode::DateAdded => node
+                .modified
+                .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_millis() as i64),
+        }
+    }
+}
+
+/// arrangement of the default view
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ViewOrder {
+    pub sort: SortMode,
+    /// all the files in a flat list, grouped by instrument scale, instead of the folders
+    pub group_by_scale: bool,
+}
+
+/// result of the update of the tree with the changed paths
+#[derive(Debug, Default)]
+pub struct StoreChanges {
+    /// files or folders have been added or removed, the views must be constructed again
+    pub tree_changed: bool,
+    /// files added or modified, their informations must be computed again
+    pub updated_files: Vec<PathBuf>,
+}
+
 #[cfg_attr(any(feature = "profiling"), profiling::all_functions)]
 impl FileStore {
-    /// recusive descent to grab and construct the tree of FileNode
-    fn recurse_construct(
-        path: &PathBuf,
-        parent: &Option<Rc<RefCell<FileNode>>>,
-    ) -> Result<Rc<RefCell<FileNode>>, Box<dyn Error>> {
-        debug!("constructing for {:?}", &path);
-        let file_node_result = FileNode::new(path);
-        debug!("file node constructed : {:?}", &file_node_result);
-        match file_node_result {
-            Ok(file_node) => {
-                let r_file_node = Rc::new(RefCell::new(file_node));
-                let mut childs: Vec<Rc<RefCell<FileNode>>> = Vec::new();
-                {
-                    let mut bn = (r_file_node.as_ref()).borrow_mut();
-                    if bn.folder() {
-                        debug!("path :{:?}", &path);
-                        if let Ok(path_dir) = path.read_dir() {
-                            for r in path_dir {
-                                match r {
-                                    Ok(dir_entry) => {
-                                        debug!("entry : {:?}", &dir_entry);
-                                        let p = dir_entry.path();
-                                        if let Ok(child) = FileStore::recurse_construct(
-                                            &p,
-                                            &Some(Rc::clone(&r_file_node)),
-                                        ) {
-                                            childs.push(child);
-                                        } else {
-                                            error!("error in getting {:?}", &p);
-                                        }
-                                    }
-                                    Err(e) => {
-                                        error!("error getting dir entry : {}", e);
-                                    }
+    /// read the entries of a folder, the childs are attached to the folder
+    /// before the sub folders are read, so the tree can be displayed while it is filled
+    fn construct_childs(folder: &SharedFileNode, control: &ScanControl) {
+        if control.is_cancelled() {
+            return;
+        }
+        let path = folder.read_node().path.clone();
+        debug!("path :{:?}", &path);
+
+        let mut childs: Vec<SharedFileNode> = Vec::new();
+        if let Ok(path_dir) = path.read_dir() {
+            for r in path_dir {
+                if control.is_cancelled() {
+                    return;
+                }
+                match r {
+                    Ok(dir_entry) => {
+                        debug!("entry : {:?}", &dir_entry);
+                        let p = dir_entry.path();
+                        match FileNode::new(&p) {
+                            Ok(mut child) => {
+                                child.set_parent(&Some(Arc::clone(folder)));
+                                if child.is_folder {
+                                    control.folders.fetch_add(1, Ordering::Relaxed);
+                                } else {
+                                    control.files.fetch_add(1, Ordering::Relaxed);
                                 }
+                                childs.push(Arc::new(RwLock::new(child)));
+                            }
+                            Err(e) => {
+                                error!("error in getting {:?} : {}", &p, e);
                             }
                         }
-
-                        childs.sort_by(|a, b| {
-                            let ab = a.borrow();
-                            let bb = b.borrow();
-                            ab.name.partial_cmp(&bb.name).unwrap()
-                        });
-
-                        bn.folder_files = childs;
-                        bn.set_parent(parent);
+                    }
+                    Err(e) => {
+                        error!("error getting dir entry : {}", e);
                     }
                 }
-
-                Ok(r_file_node)
             }
-            Err(e) => Err(Box::new(e)),
+        }
+
+        childs.sort_by(|a, b| a.read_node().name.cmp(&b.read_node().name));
+        folder.write_node().folder_files.clone_from(&childs);
+
+        for child in childs.iter().filter(|c| c.read_node().is_folder) {
+            FileStore::construct_childs(child, control);
         }
     }
 
+    /// recusive descent to grab and construct the tree of FileNode
+    fn recurse_construct(
+        path: &PathBuf,
+        parent: &Option<SharedFileNode>,
+    ) -> Result<SharedFileNode, Box<dyn Error>> {
+        debug!("constructing for {:?}", &path);
+        let mut file_node = FileNode::new(path)?;
+        file_node.set_parent(parent);
+        let r_file_node = Arc::new(RwLock::new(file_node));
+        if r_file_node.read_node().folder() {
+            FileStore::construct_childs(&r_file_node, &ScanControl::default());
+        }
+        Ok(r_file_node)
+    }
+
+    /// construct the file store, reading the whole tree before returning
     pub fn new(path: &PathBuf) -> Result<Option<FileStore>, FileStoreError> {
         let pathbuf = path.to_path_buf();
 
@@ -212,6 +406,7 @@ impl FileStore {
                 root: data_root,
                 default_view: None,
                 filtered_view: None,
+                order: ViewOrder::default(),
             };
 
             // construct the default_view
@@ -231,6 +426,242 @@ impl FileStore {
         }
     }
 
+    /// create the file store with an empty tree, the tree is filled by a background scan,
+    /// the views must be refreshed while the scan is running
+    pub fn scan(path: &Path) -> Result<(FileStore, ScanJob), FileStoreError> {
+        FileStore::scan_with(path, Arc::new(ScanControl::default()))
+    }
+
+    /// scan the folder in background, reporting to the given control
+    fn scan_with(
+        path: &Path,
+        control: Arc<ScanControl>,
+    ) -> Result<(FileStore, ScanJob), FileStoreError> {
+        let root_node = FileNode::new(&path.to_path_buf())?;
+        if !root_node.is_folder {
+            return Err(FileStoreError::new(
+                format!("{:?} is not a folder", path).as_str(),
+            ));
+        }
+        let root = Arc::new(RwLock::new(root_node));
+
+        let thread_root = Arc::clone(&root);
+        let thread_control = Arc::clone(&control);
+        thread::Builder::new()
+            .name("library-scan".to_string())
+            .spawn(move || {
+                FileStore::construct_childs(&thread_root, &thread_control);
+                let (files, folders) = thread_control.progress();
+                info!(
+                    "library scan ended, {} files in {} folders, cancelled : {}",
+                    files,
+                    folders,
+                    thread_control.is_cancelled()
+                );
+                thread_control.finished.store(true, Ordering::Release);
+            })
+            .map_err(|e| {
+                FileStoreError::new(format!("fail to start the library scan : {}", e).as_str())
+            })?;
+
+        Ok((
+            FileStore {
+                base_path: path.to_path_buf(),
+                root,
+                default_view: None,
+                filtered_view: None,
+                order: ViewOrder::default(),
+            },
+            ScanJob { control },
+        ))
+    }
+
+    /// find the node of a path in the tree, walking down from the root
+    fn find_node(&self, path: &Path) -> Option<SharedFileNode> {
+        let relative = path.strip_prefix(&self.base_path).ok()?;
+        let mut current = Arc::clone(&self.root);
+        let mut current_path = self.base_path.clone();
+        for component in relative.components() {
+            current_path.push(component);
+            let name = component.as_os_str().to_string_lossy();
+            let child = {
+                let node = current.read_node();
+                // the childs are sorted by name, two names may be the same once made lossy
+                node.folder_files
+                    .binary_search_by(|c| c.read_node().name.as_str().cmp(&name))
+                    .ok()
+                    .map(|index| &node.folder_files[index])
+                    .filter(|c| c.read_node().path == current_path)
+                    .or_else(|| {
+                        node.folder_files
+                            .iter()
+                            .find(|c| c.read_node().path == current_path)
+                    })
+                    .map(Arc::clone)?
+            };
+            current = child;
+        }
+        Some(current)
+    }
+
+    /// set the computed informations of the files (the views share the nodes)
+    pub fn set_informations(&self, informations: &HashMap<PathBuf, FileInformations>) {
+        for (path, computed) in informations {
+            if let Some(node) = self.find_node(path) {
+                node.write_node().informations = Some(computed.clone());
+            }
+        }
+    }
+
+    /// nearest folder of the tree containing the path (or the path itself if this is a folder)
+    fn nearest_folder(&self, path: &Path) -> Option<SharedFileNode> {
+        let mut candidate = Some(path);
+        while let Some(p) = candidate {
+            if let Some(node) = self.find_node(p) {
+                if node.read_node().is_folder {
+                    return Some(node);
+                }
+            }
+            candidate = p.parent();
+        }
+        None
+    }
+
+    /// synchronize the childs of a folder with the file system, the existing nodes
+    /// (and their memoized metadata) are kept, returns true if childs are added or removed
+    fn sync_folder(folder: &SharedFileNode, added_files: &mut Vec<PathBuf>) -> bool {
+        let folder_path = folder.read_node().path.clone();
+        let entries: Vec<PathBuf> = match folder_path.read_dir() {
+            Ok(dir) => dir.filter_map(|e| e.ok()).map(|e| e.path()).collect(),
+            Err(e) => {
+                error!("fail to read the folder {:?} : {}", &folder_path, e);
+                return false;
+            }
+        };
+
+        let mut changed = false;
+        let mut childs: Vec<SharedFileNode> = vec![];
+        for child in folder.read_node().folder_files.iter() {
+            let c = child.read_node();
+            if entries.contains(&c.path) && c.path.is_dir() == c.is_folder {
+                childs.push(Arc::clone(child));
+            } else {
+                debug!("{:?} removed from the library", &c.path);
+                changed = true;
+            }
+        }
+
+        for entry in entries {
+            if childs.iter().any(|c| c.read_node().path == entry) {
+                continue;
+            }
+            match FileStore::recurse_construct(&entry, &Some(Arc::clone(folder))) {
+                Ok(child) => {
+                    debug!("{:?} added to the library", &entry);
+                    collect_files(&child, added_files);
+                    childs.push(child);
+                    changed = true;
+                }
+                Err(e) => error!("error in getting {:?} : {}", &entry, e),
+            }
+        }
+
+        if changed {
+            childs.sort_by(|a, b| a.read_node().name.cmp(&b.read_node().name));
+            folder.write_node().folder_files = childs;
+        }
+        changed
+    }
+
+    /// update the tree with the paths changed on the file system (added, removed or modified),
+    /// only the folders containing the changes are read again
+    pub fn apply_changes(&mut self, changed_paths: &[PathBuf]) -> StoreChanges {
+        let mut changes = StoreChanges::default();
+
+        let mut synced_folders: HashSet<PathBuf> = HashSet::new();
+        for path in changed_paths {
+            let Some(folder) = self.nearest_folder(path.parent().unwrap_or(path)) else {
+                continue;
+            };
+            let folder_path = folder.read_node().path.clone();
+            if synced_folders.insert(folder_path)
+                && FileStore::sync_folder(&folder, &mut changes.updated_files)
+            {
+                changes.tree_changed = true;
+            }
+        }
+
+        // modified files, already in the tree
+        for path in changed_paths {
+            if let Some(node) = self.find_node(path) {
+                let n = node.read_node();
+                if !n.is_folder && !changes.updated_files.contains(&n.path) {
+                    changes.updated_files.push(n.path.clone());
+                }
+            }
+        }
+        changes
+    }
+
+    /// construct the views again after a change in the tree, the filtered view
+    /// uses the ranking of the current search, the expanded and selected elements are kept
+    pub fn refresh_views(
+        &mut self,
+        ranking: &HashMap<PathBuf, f32>,
+        extension_filter: &ExtensionsFilter,
+    ) {
+        let default_state = self.default_view.as_ref().map(|v| v.state());
+        self.default_view = self.ordered_view(extension_filter).ok();
+        if let (Some(view), Some(state)) = (&mut self.default_view, &default_state) {
+            view.restore_state(state);
+        }
+
+        if self.filtered_view.is_some() {
+            let filtered_state = self.filtered_view.as_ref().map(|v| v.state());
+            self.filtered_view = self.ranked_view(ranking, extension_filter).ok();
+            if let (Some(view), Some(state)) = (&mut self.filtered_view, &filtered_state) {
+                view.restore_state(state);
+            }
+        }
+    }
+
+    /// find a file in the tree by its file name, the first one found is returned
+    pub fn find_file_by_name(&self, name: &OsStr) -> Option<PathBuf> {
+        fn recurse_find(node: &SharedFileNode, name: &OsStr) -> Option<PathBuf> {
+            let n = node.read_node();
+            if !n.is_folder {
+                return if n.path.file_name() == Some(name) {
+                    Some(n.path.clone())
+                } else {
+                    None
+                };
+            }
+            n.folder_files.iter().find_map(|c| recurse_find(c, name))
+        }
+        recurse_find(&self.root, name)
+    }
+
+    /// paths of all the files of the tree
+    pub fn files(&self) -> Vec<PathBuf> {
+        let mut files = vec![];
+        collect_files(&self.root, &mut files);
+        files
+    }
+
+    /// paths of all the files of the tree, with their duration if it is already known
+    pub fn files_durations(&self) -> Vec<(PathBuf, Option<std::time::Duration>)> {
+        let mut files = vec![];
+        collect_file_nodes(&self.root, &mut files);
+        files
+            .iter()
+            .map(|f| {
+                let n = f.read_node();
+                let duration = n.informations.as_ref().and_then(|i| i.duration);
+                (n.path.clone(), duration)
+            })
+            .collect()
+    }
+
     /// construct a view of the tree, using some filters :
     /// - a name filter (part of the name)
     /// - a file extension filter
@@ -238,11 +669,11 @@ impl FileStore {
     #[allow(clippy::only_used_in_recursion)]
     pub fn recurse_construct_view(
         &self,
-        node: &Rc<RefCell<FileNode>>,
+        node: &SharedFileNode,
         name_filter: &Option<String>,
         extension_filter: &ExtensionsFilter,
     ) -> Option<Rc<RefCell<FileViewNode>>> {
-        let bn = node.borrow();
+        let bn = node.read_node();
         debug!("entering {:?}", node);
         if !bn.is_folder {
             // this is file
@@ -255,24 +686,11 @@ impl FileStore {
             }
 
             // check extension filter
-            if let Some(extension) = extension_filter {
-                let mut found = false;
-                for tested_extension in extension.iter() {
-                    if bn
-                        .name
-                        .to_lowercase()
-                        .ends_with(&tested_extension.to_lowercase())
-                    {
-                        found = true;
-                        break;
-                    }
-                }
-                if !found {
-                    return None;
-                }
+            if !accept_extension(&bn.name, extension_filter) {
+                return None;
             }
 
-            Some(FileViewNode::new(Rc::clone(node), vec![]))
+            Some(FileViewNode::new(Arc::clone(node), vec![]))
         } else {
             // go to sub elements
             let mut v: Vec<Rc<RefCell<FileViewNode>>> = Vec::new();
@@ -289,12 +707,124 @@ impl FileStore {
                 None
             } else {
                 debug!("there are children, create the view");
-                let fvn = FileViewNode::new(Rc::clone(node), v);
+                let fvn = FileViewNode::new(Arc::clone(node), v);
                 Some(fvn)
             }
         }
     }
 
+    /// construct the view of the files found by a search, with their score,
+    /// the best ranked files (and folders containing them) come first
+    pub fn ranked_view(
+        &self,
+        ranking: &HashMap<PathBuf, f32>,
+        extension_filter: &ExtensionsFilter,
+    ) -> Result<FileView, Box<dyn Error>> {
+        fn recurse_ranked(
+            node: &SharedFileNode,
+            ranking: &HashMap<PathBuf, f32>,
+            extension_filter: &ExtensionsFilter,
+        ) -> Option<(Rc<RefCell<FileViewNode>>, f32)> {
+            let bn = node.read_node();
+            if !bn.is_folder {
+                let score = ranking.get(&bn.path)?;
+                if !accept_extension(&bn.name, extension_filter) {
+                    return None;
+                }
+                return Some((FileViewNode::new(Arc::clone(node), vec![]), *score));
+            }
+
+            let mut childs: Vec<(Rc<RefCell<FileViewNode>>, f32)> = bn
+                .folder_files
+                .iter()
+                .filter_map(|c| recurse_ranked(c, ranking, extension_filter))
+                .collect();
+            // stable sort, the names order is kept for the same score
+            childs.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+            let best = childs.first()?.1;
+            Some((
+                FileViewNode::new(
+                    Arc::clone(node),
+                    childs.into_iter().map(|(c, _)| c).collect(),
+                ),
+                best,
+            ))
+        }
+
+        match recurse_ranked(&self.root, ranking, extension_filter) {
+            None => Err(FileStoreError::new(
+                "fail to construct view, there is no generated elements in view",
+            ))?,
+            Some((root, _)) => Ok(FileView { root }),
+        }
+    }
+
+    /// construct the default view, in the order chosen by the user
+    pub fn ordered_view(
+        &self,
+        extension_filter: &ExtensionsFilter,
+    ) -> Result<FileView, Box<dyn Error>> {
+        let view = if self.order.group_by_scale {
+            self.scale_groups_view(extension_filter)?
+        } else {
+            self.view(&None, extension_filter)?
+        };
+        sort_view_node(&view.root, self.order.sort);
+        Ok(view)
+    }
+
+    /// construct a flat view of all the files, grouped by the scale of their instrument,
+    /// the files whose informations are not computed yet are in the unknown scale group
+    fn scale_groups_view(
+        &self,
+        extension_filter: &ExtensionsFilter,
+    ) -> Result<FileView, Box<dyn Error>> {
+        let mut files = vec![];
+        collect_file_nodes(&self.root, &mut files);
+
+        let mut groups: BTreeMap<String, Vec<SharedFileNode>> = BTreeMap::new();
+        let mut unknown: Vec<SharedFileNode> = vec![];
+        for file in files {
+            let n = file.read_node();
+            if !accept_extension(&n.name, extension_filter) {
+                continue;
+            }
+            let scale = n.informations.as_ref().and_then(|i| i.scale_name.clone());
+            drop(n);
+            match scale {
+                Some(scale) => groups.entry(scale).or_default().push(file),
+                None => unknown.push(file),
+            }
+        }
+        // the unknown scale group comes last
+        let mut groups: Vec<(String, Vec<SharedFileNode>)> = groups.into_iter().collect();
+        if !unknown.is_empty() {
+            groups.push((UNKNOWN_SCALE_GROUP.to_string(), unknown));
+        }
+        if groups.is_empty() {
+            Err(FileStoreError::new(
+                "fail to construct view, there is no generated elements in view",
+            ))?
+        }
+
+        let childs = groups
+            .into_iter()
+            .map(|(scale, mut files)| {
+                files.sort_by(|a, b| a.read_node().name.cmp(&b.read_node().name));
+                FileViewNode::new(
+                    FileNode::group(&scale),
+                    files
+                        .into_iter()
+                        .map(|f| FileViewNode::new(f, vec![]))
+                        .collect(),
+                )
+            })
+            .collect();
+        Ok(FileView {
+            root: FileViewNode::new(Arc::clone(&self.root), childs),
+        })
+    }
+
     pub fn view(
         &self,
         filter: &Option<String>,
@@ -322,6 +852,64 @@ impl FileStore {
     }
 }
 
+/// the file name has one of the extensions, if there is an extension filter
+pub fn accept_extension(name: &str, extension_filter: &ExtensionsFilter) -> bool {
+    match extension_filter {
+        Some(extensions) => extensions
+            .iter()
+            .any(|e| name.to_lowercase().ends_with(&e.to_lowercase())),
+        None => true,
+    }
+}
+
+/// sort the childs of a view node, recursively, a folder comes with its first child,
+/// the greatest key of the node is returned
+fn sort_view_node(node: &Rc<RefCell<FileViewNode>>, sort: SortMode) -> Option<i64> {
+    if sort == SortMode::Name {
+        return None;
+    }
+    let mut view_node = node.borrow_mut();
+    let is_folder = view_node.node.read_node().is_folder;
+    if !is_folder {
+        return sort.key(&view_node.node.read_node());
+    }
+    let mut childs: Vec<(Rc<RefCell<FileViewNode>>, Option<i64>)> = view_node
+        .childs
+        .drain(..)
+        .map(|c| {
+            let key = sort_view_node(&c, sort);
+            (c, key)
+        })
+        .collect();
+    // stable sort, the names order is kept for the same key
+    childs.sort_by(|(_, a), (_, b)| b.cmp(a));
+    let best = childs.first().and_then(|(_, key)| *key);
+    view_node.childs = childs.into_iter().map(|(c, _)| c).collect();
+    best
+}
+
+/// collect the file nodes under a node
+fn collect_file_nodes(node: &SharedFileNode, files: &mut Vec<SharedFileNode>) {
+    let n = node.read_node();
+    if n.is_folder {
+        n.folder_files
+            .iter()
+            .for_each(|c| collect_file_nodes(c, files));
+    } else {
+        files.push(Arc::clone(node));
+    }
+}
+
+/// collect the paths of the files under a node
+fn collect_files(node: &SharedFileNode, files: &mut Vec<PathBuf>) {
+    let n = node.read_node();
+    if n.is_folder {
+        n.folder_files.iter().for_each(|c| collect_files(c, files));
+    } else {
+        files.push(n.path.clone());
+    }
+}
+
 #[test]
 fn test_file_node() {
     #[cfg(feature = "profiling")]
@@ -354,7 +942,7 @@ fn test_file_store_and_view() {
 /// file node view, constructed from the file store
 #[derive(Debug)]
 pub struct FileViewNode {
-    pub node: Rc<RefCell<FileNode>>,
+    pub node: SharedFileNode,
     pub childs: Vec<Rc<RefCell<FileViewNode>>>,
 
     // view state for expansion
@@ -366,11 +954,11 @@ pub struct FileViewNode {
 #[allow(dead_code)]
 impl FileViewNode {
     pub fn new(
-        datanode: Rc<RefCell<FileNode>>,
+        datanode: SharedFileNode,
         childs: Vec<Rc<RefCell<FileViewNode>>>,
     ) -> Rc<RefCell<FileViewNode>> {
         let fv = FileViewNode {
-            node: Rc::clone(&datanode),
+            node: Arc::clone(&datanode),
             childs,
             // this is the expanded state for display
             expanded: false,
@@ -382,13 +970,13 @@ impl FileViewNode {
 
     /// get a new reference to the filenode
     #[allow(dead_code)]
-    pub fn file_node(&self) -> Rc<RefCell<FileNode>> {
-        Rc::clone(&self.node)
+    pub fn file_node(&self) -> SharedFileNode {
+        Arc::clone(&self.node)
     }
 
     /// get the node name of the fileviewnode
     pub fn name(&self) -> String {
-        let n = &self.node.borrow();
+        let n = &self.node.read_node();
         n.name.clone()
     }
 
@@ -414,6 +1002,13 @@ impl FileViewNode {
     }
 }
 
+/// display state of a view, kept when the view is constructed again
+#[derive(Debug, Default)]
+pub struct FileViewState {
+    expanded: HashSet<PathBuf>,
+    selected: HashSet<PathBuf>,
+}
+
 /// root structure for a tree view
 #[derive(Debug)]
 pub struct FileView {
@@ -421,6 +1016,42 @@ pub struct FileView {
 }
 
 impl FileView {
+    /// paths of the expanded and selected elements
+    pub fn state(&self) -> FileViewState {
+        fn recurse_state(node: &Rc<RefCell<FileViewNode>>, state: &mut FileViewState) {
+            let view_node = node.borrow();
+            let path = view_node.node.read_node().path.clone();
+            if view_node.expanded {
+                state.expanded.insert(path.clone());
+            }
+            if view_node.selected {
+                state.selected.insert(path);
+            }
+            view_node
+                .childs
+                .iter()
+                .for_each(|c| recurse_state(c, state));
+        }
+        let mut state = FileViewState::default();
+        recurse_state(&self.root, &mut state);
+        state
+    }
+
+    /// expand and select the elements of a previous state
+    pub fn restore_state(&mut self, state: &FileViewState) {
+        fn recurse_restore(node: &Rc<RefCell<FileViewNode>>, state: &FileViewState) {
+            let mut view_node = node.borrow_mut();
+            let path = view_node.node.read_node().path.clone();
+            view_node.expanded = state.expanded.contains(&path);
+            view_node.selected = state.selected.contains(&path);
+            view_node
+                .childs
+                .iter()
+                .for_each(|c| recurse_restore(c, state));
+        }
+        recurse_restore(&self.root, state);
+    }
+
     #[allow(dead_code)]
     pub fn expand_all(&mut self) {
         let mut e = self.root.borrow_mut();
@@ -443,7 +1074,7 @@ impl FileView {
 
     fn recurse_find_first(node: &Rc<RefCell<FileViewNode>>) -> Option<Rc<RefCell<FileViewNode>>> {
         let view_node = node.borrow();
-        let file_node = view_node.node.borrow();
+        let file_node = view_node.node.read_node();
         if !file_node.is_folder {
             return Some(Rc::clone(node));
         }
@@ -462,3 +1093,154 @@ impl FileView {
         FileView::recurse_find_first(&self.root)
     }
 }
+
+#[test]
+fn test_apply_changes_keeps_view_state() {
+    let base = std::env::temp_dir().join(format!("vbp_file_store_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&base);
+    std::fs::create_dir_all(base.join("waltzes")).unwrap();
+    std::fs::write(base.join("waltzes").join("01 - waltz.mid"), b"").unwrap();
+    std::fs::write(base.join("02 - march.mid"), b"").unwrap();
+
+    let mut store = FileStore::new(&base).unwrap().unwrap();
+    let waltzes = base.join("waltzes");
+    {
+        let view = store.default_view.as_mut().unwrap();
+        let root = view.root.borrow();
+        let folder = root
+            .childs
+            .iter()
+            .find(|c| c.borrow().node.read_node().path == waltzes)
+            .unwrap();
+        folder.borrow_mut().expanded = true;
+    }
+
+    // a file copied in the sub folder, a file removed at the root
+    let added = waltzes.join("03 - valse.mid");
+    std::fs::write(&added, b"").unwrap();
+    std::fs::remove_file(base.join("02 - march.mid")).unwrap();
+    let changes = store.apply_changes(&[added.clone(), base.join("02 - march.mid")]);
+    assert!(changes.tree_changed);
+    assert_eq!(changes.updated_files, vec![added.clone()]);
+    assert_eq!(store.files().len(), 2);
+
+    store.refresh_views(&HashMap::new(), &None);
+    let state = store.default_view.as_ref().unwrap().state();
+    assert!(state.expanded.contains(&waltzes));
+
+    // nothing changed on the file system
+    let changes = store.apply_changes(std::slice::from_ref(&added));
+    assert!(!changes.tree_changed);
+    assert_eq!(changes.updated_files, vec![added]);
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
+
+#[test]
+fn test_background_scan() {
+    fn assert_shareable<T: Send + Sync>(_: &T) {}
+    fn wait_finished(control: &ScanControl) {
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+        while !control.is_finished() {
+            assert!(
+                std::time::Instant::now() < deadline,
+                "the scan did not finish"
+            );
+            thread::sleep(std::time::Duration::from_millis(10));
+        }
+    }
+
+    let base = std::env::temp_dir().join(format!("vbp_file_scan_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&base);
+    for folder in ["a", "b/c"] {
+        std::fs::create_dir_all(base.join(folder)).unwrap();
+        for i in 0..3 {
+            std::fs::write(base.join(folder).join(format!("{}.mid", i)), b"").unwrap();
+        }
+    }
+
+    let (mut store, job) = FileStore::scan(&base).unwrap();
+    assert_shareable(&store.root);
+    wait_finished(&job.control);
+    assert_eq!(job.control.progress(), (6, 3));
+    assert_eq!(store.files().len(), 6);
+    store.refresh_views(&HashMap::new(), &None);
+    assert!(store.default_view.is_some());
+
+    // the best ranked file comes first, with its folder
+    let ranking = HashMap::from([
+        (base.join("a").join("2.mid"), 1.0),
+        (base.join("b").join("c").join("1.mid"), 5.0),
+    ]);
+    let view = store.ranked_view(&ranking, &None).unwrap();
+    let first = view.find_first_file().unwrap();
+    assert_eq!(
+        first.borrow().node.read_node().path,
+        base.join("b").join("c").join("1.mid")
+    );
+
+    // a scan cancelled before the walk stops without reading the folders
+    let control = Arc::new(ScanControl::default());
+    control.cancel();
+    let (store, job) = FileStore::scan_with(&base, control).unwrap();
+    wait_finished(&job.control);
+    assert!(job.control.is_cancelled());
+    assert_eq!(job.control.progress(), (0, 0));
+    assert!(store.files().is_empty());
+
+    assert!(FileStore::scan(&base.join("a").join("0.mid")).is_err());
+    std::fs::remove_dir_all(&base).unwrap();
+}
+
+#[test]
+fn test_ordered_view() {
+    fn names(node: &Rc<RefCell<FileViewNode>>) -> Vec<String> {
+        node.borrow()
+            .childs
+            .iter()
+            .map(|c| c.borrow().name())
+            .collect()
+    }
+
+    let base = std::env::temp_dir().join(format!("vbp_file_order_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&base);
+    std::fs::create_dir_all(base.join("waltzes")).unwrap();
+    std::fs::write(base.join("waltzes").join("a.book"), b"").unwrap();
+    std::fs::write(base.join("waltzes").join("b.book"), b"").unwrap();
+    std::fs::write(base.join("c.book"), b"").unwrap();
+
+    let mut store = FileStore::new(&base).unwrap().unwrap();
+    let root = store.default_view.as_ref().unwrap().root.clone();
+    assert_eq!(names(&root), vec!["c.book", "waltzes"]);
+
+    let mut files = vec![];
+    collect_file_nodes(&store.root, &mut files);
+    for file in &files {
+        let mut n = file.write_node();
+        let (play_count, scale) = match n.name.as_str() {
+            "a.book" => (Some(2), Some("45 limonaire")),
+            "b.book" => (Some(7), Some("45 limonaire")),
+            _ => (Some(3), None),
+        };
+        n.play_count = play_count;
+        n.informations = Some(FileInformations {
+            scale_name: scale.map(|s| s.to_string()),
+            ..Default::default()
+        });
+    }
+
+    // the folder having the most played file comes first
+    store.order.sort = SortMode::PlayCount;
+    store.refresh_views(&HashMap::new(), &None);
+    let root = store.default_view.as_ref().unwrap().root.clone();
+    assert_eq!(names(&root), vec!["waltzes", "c.book"]);
+    assert_eq!(names(&root.borrow().childs[0]), vec!["b.book", "a.book"]);
+
+    store.order.group_by_scale = true;
+    store.refresh_views(&HashMap::new(), &None);
+    let root = store.default_view.as_ref().unwrap().root.clone();
+    assert_eq!(names(&root), vec!["45 limonaire", UNKNOWN_SCALE_GROUP]);
+    assert_eq!(names(&root.borrow().childs[0]), vec!["b.book", "a.book"]);
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
@@ -4,10 +4,16 @@
 
 mod app;
 pub mod appplayer;
+pub mod catalogue;
 pub mod file_store;
+pub mod library_watcher;
 pub mod playlist;
+pub mod playlist_generator;
 pub mod playmetadata;
 pub mod playmetadata_manager;
+pub mod random;
+pub mod scheduler;
+pub mod search_index;
 pub mod virtualbookcomponent;
 
 use std::time::Duration;
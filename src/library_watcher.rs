@@ -0,0 +1,226 @@
+//! Library watcher, report the files added, removed or modified in the library folder,
+//! the changes are collected until the folder is quiet, a copy of many files gives a single report
+//!
+//! the native notifications of the system are used, network (and some fuse) mounts
+//! don't report the changes made by other machines, these are polled
+//!
+//! the watches are set up in a background thread, as they walk the whole folder
+//!
+use std::{
+    collections::HashSet,
+    error::Error,
+    path::{Path, PathBuf},
+    sync::mpsc::{channel, Receiver, Sender, TryRecvError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::{debug, error, info, warn};
+use notify::{Config, EventKind, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::{
+    catalogue::{CATALOGUE_CSV_FILE, CATALOGUE_HTML_FILE},
+    playmetadata::METADATA_DATABASE_FILE,
+};
+
+/// the changes are reported when no event has been received for this duration
+const QUIET_DURATION: Duration = Duration::from_secs(1);
+
+/// interval between two scans of the folder, when polled
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// file systems on which the native notifications miss the remote changes
+const POLLED_FILESYSTEMS: [&str; 9] = [
+    "nfs",
+    "nfs4",
+    "cifs",
+    "smb3",
+    "smbfs",
+    "9p",
+    "davfs",
+    "fuse.sshfs",
+    "fuse.gvfsd-fuse",
+];
+
+/// file system type of the mount containing the path, from the content of /proc/mounts
+fn mount_filesystem_type(mounts: &str, path: &Path) -> Option<String> {
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let _device = fields.next()?;
+            // spaces in the mount point are escaped as \040
+            let mount_point = PathBuf::from(fields.next()?.replace("\\040", " "));
+            let filesystem = fields.next()?;
+            path.starts_with(&mount_point)
+                .then(|| (mount_point, filesystem.to_string()))
+        })
+        .max_by_key(|(mount_point, _)| mount_point.components().count())
+        .map(|(_, filesystem)| filesystem)
+}
+
+/// the folder is on a file system that must be polled
+fn needs_polling(folder: &Path) -> bool {
+    let folder = folder
+        .canonicalize()
+        .unwrap_or_else(|_| folder.to_path_buf());
+    match std::fs::read_to_string("/proc/mounts") {
+        Ok(mounts) => mount_filesystem_type(&mounts, &folder)
+            .map(|filesystem| POLLED_FILESYSTEMS.contains(&filesystem.as_str()))
+            .unwrap_or(false),
+        // no mount informations on this platform
+        Err(_) => false,
+    }
+}
+
+/// the metadata database is written while playing, and the catalogue may be exported
+/// in the library, their changes are not library changes
+fn is_ignored(path: &Path) -> bool {
+    path.file_name()
+        .map(|name| {
+            let name = name.to_string_lossy();
+            name.starts_with(METADATA_DATABASE_FILE)
+                || name == CATALOGUE_HTML_FILE
+                || name == CATALOGUE_CSV_FILE
+        })
+        .unwrap_or(false)
+}
+
+type BoxedWatcher = Box<dyn Watcher + Send>;
+
+/// watch the library folder, and its sub folders
+pub struct LibraryWatcher {
+    /// the watcher, once set up by the background thread
+    _watcher: Option<BoxedWatcher>,
+    setup: Option<Receiver<Result<(BoxedWatcher, bool), String>>>,
+    receiver: Receiver<notify::Result<notify::Event>>,
+    /// paths changed since the last report
+    pending: HashSet<PathBuf>,
+    last_event: Instant,
+    /// the folder is polled, the native notifications are not used
+    pub polled: bool,
+}
+
+/// create the watcher and watch the folder, this walks the whole folder,
+/// return the watcher and if the folder is polled
+fn watch(
+    folder: &Path,
+    sender: Sender<notify::Result<notify::Event>>,
+) -> Result<(BoxedWatcher, bool), Box<dyn Error>> {
+    let mut native: Option<BoxedWatcher> = None;
+    if !needs_polling(folder) {
+        match RecommendedWatcher::new(sender.clone(), Config::default()) {
+            Ok(mut watcher) => match watcher.watch(folder, RecursiveMode::Recursive) {
+                Ok(()) => native = Some(Box::new(watcher)),
+                Err(e) => warn!("fail to watch {:?}, the folder is polled : {}", folder, e),
+            },
+            Err(e) => warn!("no native watcher, the folder is polled : {}", e),
+        }
+    }
+
+    let polled = native.is_none();
+    let watcher: BoxedWatcher = match native {
+        Some(watcher) => watcher,
+        None => {
+            let mut watcher =
+                PollWatcher::new(sender, Config::default().with_poll_interval(POLL_INTERVAL))?;
+            watcher.watch(folder, RecursiveMode::Recursive)?;
+            Box::new(watcher)
+        }
+    };
+    info!("watching the library {:?}, polled : {}", folder, polled);
+    Ok((watcher, polled))
+}
+
+impl LibraryWatcher {
+    /// start watching the folder, the native notifications are used when possible,
+    /// the watches are set up in background, the changes made meanwhile are reported
+    pub fn start(folder: &Path) -> Result<LibraryWatcher, Box<dyn Error>> {
+        let (sender, receiver) = channel();
+        let (setup_sender, setup) = channel();
+
+        let folder = folder.to_path_buf();
+        thread::Builder::new()
+            .name("library-watch".to_string())
+            .spawn(move || {
+                let watcher = watch(&folder, sender).map_err(|e| e.to_string());
+                // the library may have been closed meanwhile
+                let _ = setup_sender.send(watcher);
+            })?;
+
+        Ok(LibraryWatcher {
+            _watcher: None,
+            setup: Some(setup),
+            receiver,
+            pending: HashSet::new(),
+            last_event: Instant::now(),
+            polled: false,
+        })
+    }
+
+    /// paths changed, once the folder is quiet, empty if there is nothing to report yet
+    pub fn changes(&mut self) -> Vec<PathBuf> {
+        if let Some(setup) = &self.setup {
+            match setup.try_recv() {
+                Ok(Ok((watcher, polled))) => {
+                    self._watcher = Some(watcher);
+                    self.polled = polled;
+                    self.setup = None;
+                }
+                Ok(Err(e)) => {
+                    error!("fail to watch the library : {}", e);
+                    self.setup = None;
+                }
+                Err(TryRecvError::Empty) => {}
+                Err(TryRecvError::Disconnected) => self.setup = None,
+            }
+        }
+
+        while let Ok(received) = self.receiver.try_recv() {
+            match received {
+                Ok(event) => {
+                    if matches!(event.kind, EventKind::Access(_)) {
+                        continue;
+                    }
+                    debug!("library event : {:?}", event);
+                    let mut relevant = false;
+                    for path in event.paths.into_iter().filter(|p| !is_ignored(p)) {
+                        self.pending.insert(path);
+                        relevant = true;
+                    }
+                    if relevant {
+                        self.last_event = Instant::now();
+                    }
+                }
+                Err(e) => error!("error while watching the library : {}", e),
+            }
+        }
+
+        if self.pending.is_empty() || self.last_event.elapsed() < QUIET_DURATION {
+            return vec![];
+        }
+        self.pending.drain().collect()
+    }
+}
+
+#[test]
+pub fn test_mount_filesystem_type() {
+    let mounts = "/dev/sda1 / ext4 rw,relatime 0 0\n\
+                  server:/music /mnt/music nfs4 rw 0 0\n\
+                  /dev/sdb1 /media/usb\\040key vfat rw 0 0\n";
+    assert_eq!(
+        mount_filesystem_type(mounts, Path::new("/mnt/music/books")),
+        Some("nfs4".to_string())
+    );
+    assert_eq!(
+        mount_filesystem_type(mounts, Path::new("/media/usb key/books")),
+        Some("vfat".to_string())
+    );
+    assert_eq!(
+        mount_filesystem_type(mounts, Path::new("/home/organ")),
+        Some("ext4".to_string())
+    );
+    assert!(is_ignored(Path::new("/books/.playmetadata.db-journal")));
+    assert!(is_ignored(Path::new("/books/catalogue.html")));
+    assert!(!is_ignored(Path::new("/books/waltz.mid")));
+}
@@ -2,23 +2,261 @@
 //!
 use std::{
     cell::RefCell,
+    collections::HashSet,
     error::Error,
+    ffi::OsStr,
     fs,
     hash::Hash,
-    path::PathBuf,
+    path::{Component, Path, PathBuf},
     rc::Rc,
+    sync::Mutex,
     time::{Duration, SystemTime},
 };
 
-use player::FileInformations;
+use log::{error, info, warn};
+use player::{FileInformations, PlaySettings};
+use serde::{Deserialize, Serialize};
 
-use crate::file_store::{FileNode, FileViewNode};
+use crate::{
+    file_store::{FileNodeLock, FileViewNode, SharedFileNode},
+    random::XorShift64,
+};
+
+/// how the playlist continues when a file has been played
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PlayMode {
+    /// the files are played once, in the playlist order
+    #[default]
+    Once,
+    /// the playlist restarts from the beginning when finished
+    RepeatAll,
+    /// the current file is played again
+    RepeatOne,
+    /// the files are played in a random order, all the files are played
+    /// before a new shuffle
+    Shuffle,
+}
+
+impl PlayMode {
+    pub const ALL: [PlayMode; 4] = [
+        PlayMode::Once,
+        PlayMode::RepeatAll,
+        PlayMode::RepeatOne,
+        PlayMode::Shuffle,
+    ];
+}
+
+/// maximum number of entries kept in the play history
+const MAX_HISTORY_LENGTH: usize = 500;
+
+/// Element played during the session
+#[derive(Clone)]
+pub struct HistoryEntry {
+    /// system time when the play has started
+    pub played_at: SystemTime,
+    pub element: PlaylistElement,
+}
+
+/// report of a playlist loading, with the entries not found
+#[derive(Debug, Clone, Default)]
+pub struct LoadReport {
+    /// loaded playlist file
+    pub playlist: PathBuf,
+    /// entries not found
+    pub missing: Vec<PathBuf>,
+    /// entries found in the library by their file name (entry path, found path)
+    pub resolved: Vec<(PathBuf, PathBuf)>,
+    /// the missing entries have been searched in the library
+    pub resolution_done: bool,
+}
+
+/// queue saved periodically, to be resumed after a restart
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedQueue {
+    /// entries, in the M3U format with absolute paths
+    pub entries: String,
+    pub current_index: usize,
+    /// name of the current entry
+    pub current_name: Option<String>,
+    /// play position in the current entry (in the file time, without the start wait),
+    /// if it was playing
+    pub position: Option<Duration>,
+}
+
+impl SavedQueue {
+    /// the queue has an entry to play
+    pub fn is_resumable(&self) -> bool {
+        self.current_name.is_some()
+    }
+}
 
 /// Play list structure, holding the list of file
 pub struct PlayList {
     pub file_list: Vec<PlaylistElement>,
+    /// index of the current element, equals the list length when the playlist is finished
+    pub current_index: usize,
+    pub play_mode: PlayMode,
     pub is_dirty: bool,
     pub computed_length: Option<Duration>,
+    /// elements played during the session, the latest at the end
+    pub history: Vec<HistoryEntry>,
+    /// report of the last playlist file loaded, if some entries were not found
+    pub load_report: Option<LoadReport>,
+    /// elements already played in the current shuffle round (by added time)
+    shuffle_played: HashSet<SystemTime>,
+    random: XorShift64,
+    /// queue and play mode of the operator, put aside while a scheduled program is played
+    operator_queue: Option<(SavedQueue, PlayMode)>,
+}
+
+/// prefix of the playlist entries settings, in the M3U files
+const ENTRY_SETTINGS_DIRECTIVE: &str = "#EXTVBP:";
+
+/// play settings of a playlist entry, overriding the global settings
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EntrySettings {
+    /// wait time before the play
+    pub wait: Option<Duration>,
+    /// play speed, in percent of the file tempo
+    pub speed: Option<u16>,
+    /// transposition, in semitones
+    pub transpose: Option<i8>,
+    /// position in the file where the play starts
+    pub start_offset: Option<Duration>,
+    /// position in the file where the play ends
+    pub end_offset: Option<Duration>,
+    /// name of the output device used for the play
+    pub output: Option<String>,
+}
+
+impl EntrySettings {
+    pub fn is_default(&self) -> bool {
+        *self == EntrySettings::default()
+    }
+
+    /// settings for the player
+    pub fn play_settings(&self) -> PlaySettings {
+        PlaySettings {
+            speed: self.speed.map(|s| s as f32 / 100.0).unwrap_or(1.0),
+            transpose: self.transpose.unwrap_or(0),
+            start_offset: self.start_offset,
+            end_offset: self.end_offset,
+        }
+    }
+
+    /// settings for the player, the play is resumed at a position of the file
+    pub fn resumed_play_settings(&self, position: Option<Duration>) -> PlaySettings {
+        let mut settings = self.play_settings();
+        if let Some(position) = position {
+            settings.start_offset = Some(position);
+        }
+        settings
+    }
+
+    /// settings directive line, for the M3U files
+    /// (#EXTVBP:wait=2.5;speed=90;transpose=-2;start=10;end=185.5;output=device)
+    pub fn to_directive(&self) -> Option<String> {
+        if self.is_default() {
+            return None;
+        }
+        let seconds = |d: &Duration| format!("{}", d.as_secs_f64());
+        let values: Vec<String> = [
+            ("wait", self.wait.as_ref().map(seconds)),
+            ("speed", self.speed.map(|s| s.to_string())),
+            ("transpose", self.transpose.map(|t| t.to_string())),
+            ("start", self.start_offset.as_ref().map(seconds)),
+            ("end", self.end_offset.as_ref().map(seconds)),
+            ("output", self.output.clone()),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| value.map(|v| format!("{}={}", key, v)))
+        .collect();
+        Some(format!("{}{}", ENTRY_SETTINGS_DIRECTIVE, values.join(";")))
+    }
+
+    /// read the settings from the directive line content (without the prefix),
+    /// unknown or malformed values are ignored
+    pub fn from_directive(directive: &str) -> EntrySettings {
+        let seconds = |v: &str| {
+            v.parse::<f64>()
+                .ok()
+                .filter(|s| *s >= 0.0)
+                .map(Duration::from_secs_f64)
+        };
+        let mut settings = EntrySettings::default();
+        for (key, value) in directive.split(';').filter_map(|kv| kv.split_once('=')) {
+            let value = value.trim();
+            match key.trim() {
+                "wait" => settings.wait = seconds(value),
+                "speed" => settings.speed = value.parse().ok().filter(|s| *s > 0),
+                "transpose" => settings.transpose = value.parse().ok(),
+                "start" => settings.start_offset = seconds(value),
+                "end" => settings.end_offset = seconds(value),
+                "output" => settings.output = Some(value.to_string()).filter(|o| !o.is_empty()),
+                k => warn!("unknown playlist entry setting {}", k),
+            }
+        }
+        settings
+    }
+}
+
+/// prefixes of the special entries, in the M3U files
+const PAUSE_DIRECTIVE: &str = "#EXTVBP-PAUSE:";
+const TEXT_CARD_DIRECTIVE: &str = "#EXTVBP-TEXT:";
+const STOP_DIRECTIVE: &str = "#EXTVBP-STOP";
+
+/// kind of playlist entry, files or special entries
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum EntryKind {
+    /// file to play
+    #[default]
+    File,
+    /// silence between the files
+    Pause(Duration),
+    /// text displayed full screen, during the given time,
+    /// or until the next entry is asked
+    TextCard(String, Option<Duration>),
+    /// the play mode is left when this entry is reached
+    Stop,
+}
+
+impl EntryKind {
+    /// directive line of the special entries, for the M3U files
+    fn to_directive(&self) -> Option<String> {
+        match self {
+            EntryKind::File => None,
+            EntryKind::Pause(d) => Some(format!("{}{}", PAUSE_DIRECTIVE, d.as_secs_f64())),
+            EntryKind::TextCard(text, d) => Some(format!(
+                "{}{},{}",
+                TEXT_CARD_DIRECTIVE,
+                d.map(|d| d.as_secs_f64().to_string()).unwrap_or_default(),
+                text.replace('\n', "\\n")
+            )),
+            EntryKind::Stop => Some(STOP_DIRECTIVE.into()),
+        }
+    }
+
+    /// read a special entry from a M3U line
+    fn from_directive(line: &str) -> Option<EntryKind> {
+        let seconds = |v: &str| {
+            v.trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|s| *s >= 0.0)
+                .map(Duration::from_secs_f64)
+        };
+        if let Some(pause) = line.strip_prefix(PAUSE_DIRECTIVE) {
+            return seconds(pause).map(EntryKind::Pause);
+        }
+        if let Some(card) = line.strip_prefix(TEXT_CARD_DIRECTIVE) {
+            let (duration, text) = card.split_once(',')?;
+            return Some(EntryKind::TextCard(
+                text.replace("\\n", "\n"),
+                seconds(duration),
+            ));
+        }
+        (line == STOP_DIRECTIVE).then_some(EntryKind::Stop)
+    }
 }
 
 /// Element in the play list (with additional informations)
@@ -29,6 +267,38 @@ pub struct PlaylistElement {
     pub name: String,
     pub path: PathBuf,
     pub additional_informations: Option<FileInformations>,
+    /// play settings of the entry
+    pub settings: EntrySettings,
+    /// file or special entry
+    pub kind: EntryKind,
+}
+
+impl PlaylistElement {
+    /// create a special entry (pause, text card, stop marker), without file
+    pub fn special(kind: EntryKind) -> Self {
+        let (name, duration) = match &kind {
+            EntryKind::File => (String::new(), None),
+            EntryKind::Pause(d) => (String::new(), Some(*d)),
+            EntryKind::TextCard(text, d) => (text.clone(), *d),
+            EntryKind::Stop => (String::new(), None),
+        };
+        PlaylistElement {
+            added_at: unique_added_time(),
+            name,
+            path: PathBuf::new(),
+            // no information to compute on special entries
+            additional_informations: Some(FileInformations {
+                duration,
+                ..Default::default()
+            }),
+            settings: EntrySettings::default(),
+            kind,
+        }
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.kind == EntryKind::File
+    }
 }
 
 /// hash implementation for playlist element
@@ -50,6 +320,22 @@ impl Hash for PlaylistElement {
     }
 }
 
+/// time of addition of a new element, distinct from the previous ones
+/// as it is used to identify the elements
+fn unique_added_time() -> SystemTime {
+    static LAST_ADDED_TIME: Mutex<Option<SystemTime>> = Mutex::new(None);
+    let mut now = SystemTime::now();
+    if let Ok(mut last) = LAST_ADDED_TIME.lock() {
+        if let Some(last_time) = *last {
+            if now <= last_time {
+                now = last_time + Duration::from_nanos(1);
+            }
+        }
+        *last = Some(now);
+    }
+    now
+}
+
 impl From<&PathBuf> for PlaylistElement {
     fn from(value: &PathBuf) -> Self {
         let mut name: String = String::new();
@@ -59,10 +345,12 @@ impl From<&PathBuf> for PlaylistElement {
         }
 
         PlaylistElement {
-            added_at: SystemTime::now(),
+            added_at: unique_added_time(),
             name,
             path: value.clone(),
             additional_informations: None,
+            settings: EntrySettings::default(),
+            kind: EntryKind::File,
         }
     }
 }
@@ -73,49 +361,324 @@ impl PlayList {
     pub fn new() -> Self {
         Self {
             file_list: vec![],
+            current_index: 0,
+            play_mode: PlayMode::default(),
             is_dirty: false,
             computed_length: None,
+            history: vec![],
+            load_report: None,
+            shuffle_played: HashSet::new(),
+            random: XorShift64::from_time(),
+            operator_queue: None,
         }
     }
 
+    /// go to the next element, asked by the user
     pub fn skip(&mut self) {
-        if !self.file_list.is_empty() {
-            self.file_list.remove(0);
-        }
+        self.advance(false);
+    }
+
+    /// go to the next element, when the current file has been played
+    pub fn file_ended(&mut self) {
+        self.advance(true);
     }
 
     pub fn current(&self) -> Option<PlaylistElement> {
-        if self.file_list.is_empty() {
-            None
+        self.file_list.get(self.current_index).cloned()
+    }
+
+    /// element that will be played after the current one, if any
+    pub fn upcoming(&self) -> Option<&PlaylistElement> {
+        let mut random = self.random;
+        self.file_list.get(self.next_index(&mut random, true))
+    }
+
+    /// change the play mode, a new shuffle round is started
+    pub fn set_play_mode(&mut self, play_mode: PlayMode) {
+        self.play_mode = play_mode;
+        self.shuffle_played.clear();
+    }
+
+    /// indices of the elements that can be played next in shuffle mode,
+    /// and if a new shuffle round starts
+    fn shuffle_candidates(&self) -> (Vec<usize>, bool) {
+        let current = self.file_list.get(self.current_index).map(|e| e.added_at);
+        let others = || {
+            self.file_list
+                .iter()
+                .enumerate()
+                .filter(move |(_, e)| Some(e.added_at) != current)
+        };
+
+        let not_played: Vec<usize> = others()
+            .filter(|(_, e)| !self.shuffle_played.contains(&e.added_at))
+            .map(|(i, _)| i)
+            .collect();
+        if !not_played.is_empty() {
+            return (not_played, false);
+        }
+
+        let all: Vec<usize> = others().map(|(i, _)| i).collect();
+        if all.is_empty() {
+            // only one element, played again
+            ((0..self.file_list.len()).collect(), true)
+        } else {
+            (all, true)
+        }
+    }
+
+    /// index of the next element, the list length is returned when the play is finished
+    fn next_index(&self, random: &mut XorShift64, end_of_file: bool) -> usize {
+        let len = self.file_list.len();
+        if len == 0 {
+            return 0;
+        }
+        let following = self.current_index + 1;
+        match self.play_mode {
+            PlayMode::Once => following.min(len),
+            PlayMode::RepeatOne if end_of_file && self.current_index < len => self.current_index,
+            PlayMode::RepeatAll | PlayMode::RepeatOne => {
+                if following >= len {
+                    0
+                } else {
+                    following
+                }
+            }
+            PlayMode::Shuffle => {
+                let (candidates, _) = self.shuffle_candidates();
+                candidates[random.next_below(candidates.len())]
+            }
+        }
+    }
+
+    /// move the cursor to the next element, depending on the play mode
+    fn advance(&mut self, end_of_file: bool) {
+        let mut random = self.random;
+        let next = self.next_index(&mut random, end_of_file);
+        self.random = random;
+
+        if self.play_mode == PlayMode::Shuffle {
+            let (_, new_round) = self.shuffle_candidates();
+            if new_round {
+                // the last element of the round can be played again in the new round
+                self.shuffle_played.clear();
+            } else if let Some(e) = self.file_list.get(self.current_index) {
+                self.shuffle_played.insert(e.added_at);
+            }
+        }
+
+        self.current_index = next;
+    }
+
+    /// remove an element, the cursor stays on the current element
+    pub fn remove(&mut self, index: usize) {
+        if index >= self.file_list.len() {
+            return;
+        }
+        let removed = self.file_list.remove(index);
+        self.shuffle_played.remove(&removed.added_at);
+        if index < self.current_index {
+            self.current_index -= 1;
+        }
+    }
+
+    /// replace the elements (reordering), the cursor follows the current element
+    pub fn set_file_list(&mut self, file_list: Vec<PlaylistElement>) {
+        let current = self.file_list.get(self.current_index).map(|e| e.added_at);
+        self.file_list = file_list;
+        self.current_index = match current {
+            Some(added_at) => self
+                .file_list
+                .iter()
+                .position(|e| e.added_at == added_at)
+                .unwrap_or(self.current_index.min(self.file_list.len())),
+            None => self.file_list.len(),
+        };
+    }
+
+    /// remove all the elements
+    pub fn clear(&mut self) {
+        self.file_list.clear();
+        self.current_index = 0;
+        self.shuffle_played.clear();
+        self.is_dirty = true;
+    }
+
+    /// record the play of the current element in the history
+    pub fn record_current_play(&mut self) {
+        if let Some(element) = self.current() {
+            self.history.push(HistoryEntry {
+                played_at: SystemTime::now(),
+                element,
+            });
+            if self.history.len() > MAX_HISTORY_LENGTH {
+                self.history.remove(0);
+            }
+        }
+    }
+
+    /// go back to the element played before the current one,
+    /// the history is rewinded, returns false if there is no previous element
+    pub fn previous(&mut self) -> bool {
+        let current = self.current().map(|e| e.added_at);
+        if current.is_some() && self.history.last().map(|h| h.element.added_at) == current {
+            // the current element is played again when going back
+            self.history.pop();
+        }
+
+        match self.history.pop() {
+            Some(entry) => {
+                self.select(entry.element);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// make an history entry the current element, to play it again
+    pub fn replay_history_entry(&mut self, index: usize) {
+        if let Some(entry) = self.history.get(index) {
+            self.select(entry.element.clone());
+        }
+    }
+
+    /// the element becomes the current one, it is inserted at the cursor
+    /// if it is no more in the list
+    fn select(&mut self, element: PlaylistElement) {
+        match self
+            .file_list
+            .iter()
+            .position(|e| e.added_at == element.added_at)
+        {
+            Some(index) => self.current_index = index,
+            None => {
+                let index = self.current_index.min(self.file_list.len());
+                self.file_list.insert(index, element);
+                self.current_index = index;
+            }
+        }
+    }
+
+    /// save the queue, with the play position of the current entry,
+    /// the played time reported by the player includes the wait before the play,
+    /// a queue saved during the wait is resumed at the start of the entry
+    pub fn saved_queue(&self, played_time: Option<Duration>, default_wait: Duration) -> SavedQueue {
+        let current = self.current();
+        let wait = current
+            .as_ref()
+            .and_then(|e| e.settings.wait)
+            .unwrap_or(default_wait);
+        SavedQueue {
+            entries: to_m3u(&self.file_list, Path::new("")),
+            current_index: self.current_index,
+            current_name: current.map(|e| e.name),
+            position: played_time
+                .map(|t| t.saturating_sub(wait))
+                .filter(|p| !p.is_zero()),
+        }
+    }
+
+    /// replace the queue by a scheduled program, with its own play mode, the queue
+    /// and the play mode of the operator are put aside until the program ends,
+    /// a single file program does not expand the playlist files
+    pub fn start_program(&mut self, path: &Path, play_mode: PlayMode, single_file: bool) {
+        // a program following another one keeps the queue of the operator
+        if self.operator_queue.is_none() {
+            self.operator_queue = Some((self.saved_queue(None, Duration::ZERO), self.play_mode));
+        }
+        self.clear();
+        self.set_play_mode(play_mode);
+        if single_file {
+            self.add_from_path(&path.to_path_buf());
         } else {
-            Some(self.file_list[0].clone())
+            self.add_from_path_and_expand_playlists(&path.to_path_buf());
+        }
+    }
+
+    /// restore the queue and the play mode of the operator, at the end of a scheduled program,
+    /// return false if no program was played
+    pub fn end_program(&mut self) -> bool {
+        match self.operator_queue.take() {
+            Some((queue, play_mode)) => {
+                self.restore_queue(&queue);
+                self.set_play_mode(play_mode);
+                true
+            }
+            None => false,
         }
     }
 
+    /// replace the queue by a saved one
+    pub fn restore_queue(&mut self, saved: &SavedQueue) {
+        self.file_list = parse_entries(&saved.entries, Path::new(""));
+        self.current_index = saved.current_index.min(self.file_list.len());
+        self.shuffle_played.clear();
+        self.is_dirty = true;
+    }
+
+    /// elements remaining to be played, including the current one
+    pub fn remaining(&self) -> &[PlaylistElement] {
+        &self.file_list[self.current_index.min(self.file_list.len())..]
+    }
+
     /// add a lonely file node to the playlist
     #[allow(dead_code)]
-    pub fn add(&mut self, node: &Rc<RefCell<FileNode>>) {
-        let cell = node.borrow();
+    pub fn add(&mut self, node: &SharedFileNode) {
+        let cell = node.read_node();
         self.add_from_path(&cell.path);
     }
 
     /// add the file, and if the file is a playlist, read all the elements
     pub fn add_from_path_and_expand_playlists(&mut self, path: &PathBuf) {
-        let extension_result = path.extension();
-
-        if let Some(ext) = extension_result {
-            if ext == "playlist" {
-                if let Ok(result) = load(path) {
+        if is_playlist_file(path) {
+            match load(path) {
+                Ok(result) => {
                     self.file_list.extend(result.file_list);
+                    if let Some(report) = result.load_report {
+                        if !report.missing.is_empty() {
+                            self.load_report = Some(report);
+                        }
+                    }
                 }
-            } else {
-                self.add_from_path(path);
+                Err(e) => error!("fail to load playlist {} : {}", path.display(), e),
             }
         } else {
             self.add_from_path(path);
         }
     }
 
+    /// try to find the missing entries of the last loaded playlist, using their file name,
+    /// the resolution is done once
+    pub fn resolve_missing_entries(&mut self, find_by_name: &dyn Fn(&OsStr) -> Option<PathBuf>) {
+        let Some(report) = &mut self.load_report else {
+            return;
+        };
+        if report.resolution_done {
+            return;
+        }
+        report.resolution_done = true;
+
+        let mut still_missing = vec![];
+        for missing in report.missing.drain(..) {
+            let found = missing.file_name().and_then(find_by_name);
+            match found {
+                Some(found) => {
+                    for e in self.file_list.iter_mut().filter(|e| e.path == missing) {
+                        e.path.clone_from(&found);
+                    }
+                    report.resolved.push((missing, found));
+                }
+                None => still_missing.push(missing),
+            }
+        }
+        report.missing = still_missing;
+        info!(
+            "playlist resolution, {} entries found by name, {} missing",
+            report.resolved.len(),
+            report.missing.len()
+        );
+    }
+
     /// add file from path
     pub fn add_from_path(&mut self, path: &PathBuf) {
         let playlist_element: PlaylistElement = path.into();
@@ -124,42 +687,486 @@ impl PlayList {
 
     pub fn add_fileviewnode_and_read_playlists(&mut self, node: &Rc<RefCell<FileViewNode>>) {
         let filenode = node.borrow();
-        let path = &filenode.node.borrow().path;
+        let path = &filenode.node.read_node().path;
         self.add_from_path_and_expand_playlists(path);
     }
 }
 
-/// save a playlist to a file
-pub fn save(p: &PlayList, filepath: &PathBuf) -> Result<(), Box<dyn Error>> {
-    let content = p
-        .file_list
+/// extensions of the playlist files that can be loaded
+pub const PLAYLIST_EXTENSIONS: [&str; 3] = ["playlist", "m3u", "m3u8"];
+
+/// is the file a playlist file (from its extension) ?
+pub fn is_playlist_file(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| {
+            let ext = ext.to_string_lossy().to_lowercase();
+            PLAYLIST_EXTENSIONS.contains(&ext.as_str())
+        })
+        .unwrap_or(false)
+}
+
+/// path of the target, relative to the base folder,
+/// the target is returned unchanged if there is no common root
+fn relative_path(base_folder: &Path, target: &Path) -> PathBuf {
+    let base: Vec<Component<'_>> = base_folder.components().collect();
+    let target_components: Vec<Component<'_>> = target.components().collect();
+
+    let common = base
         .iter()
-        .map(|f| f.path.clone())
-        .fold(String::new(), |s, f| {
-            s + "\n" + &f.as_os_str().to_string_lossy()
-        });
+        .zip(target_components.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let has_common_root = base
+        .first()
+        .zip(target_components.first())
+        .map(|(a, b)| a == b && matches!(a, Component::RootDir | Component::Prefix(_)))
+        .unwrap_or(false);
+    if common == 0 || (target.is_absolute() && !has_common_root) {
+        return target.to_path_buf();
+    }
+
+    let mut result = PathBuf::new();
+    for _ in common..base.len() {
+        result.push("..");
+    }
+    for c in &target_components[common..] {
+        result.push(c);
+    }
+    result
+}
+
+/// path written in the playlist file, with / separators to be shared between systems
+fn playlist_entry_path(base_folder: &Path, target: &Path) -> String {
+    let relative = relative_path(base_folder, target);
+    if relative.is_absolute() {
+        relative.to_string_lossy().to_string()
+    } else {
+        relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().to_string())
+            .collect::<Vec<String>>()
+            .join("/")
+    }
+}
+
+/// path of a playlist entry, relative entries are relative to the playlist folder
+fn resolve_entry_path(base_folder: &Path, entry: &str) -> PathBuf {
+    #[cfg(not(target_os = "windows"))]
+    let entry = entry.replace('\\', "/");
+    let path = PathBuf::from(entry);
+    if path.is_absolute() {
+        path
+    } else {
+        base_folder.join(path)
+    }
+}
+
+/// save a playlist to a file, in the M3U format (with the entries settings), or the
+/// legacy format (one path per line, without settings) for the .playlist files
+pub fn save(p: &PlayList, filepath: &Path) -> Result<(), Box<dyn Error>> {
+    let base_folder = filepath.parent().unwrap_or(Path::new(""));
+
+    let content = if filepath
+        .extension()
+        .map(|e| e == "playlist")
+        .unwrap_or(false)
+    {
+        // the special entries are not supported in the legacy format
+        p.file_list
+            .iter()
+            .filter(|f| f.is_file())
+            .map(|f| playlist_entry_path(base_folder, &f.path))
+            .fold(String::new(), |s, f| s + "\n" + &f)
+    } else {
+        to_m3u(&p.file_list, base_folder)
+    };
 
     fs::write(filepath, content)?;
 
     Ok(())
 }
 
-/// load a playlist from a file
-pub fn load(filepath: &PathBuf) -> Result<PlayList, Box<dyn Error>> {
-    let contents = fs::read_to_string(filepath)?;
+/// M3U content of the entries, the paths are written relative to the base folder
+fn to_m3u(file_list: &[PlaylistElement], base_folder: &Path) -> String {
+    let mut content = String::from("#EXTM3U\n");
+    for f in file_list {
+        if let Some(directive) = f.kind.to_directive() {
+            content += &directive;
+            content += "\n";
+            continue;
+        }
+        let duration = f
+            .additional_informations
+            .as_ref()
+            .and_then(|i| i.duration)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(-1);
+        content += &format!("#EXTINF:{},{}\n", duration, f.name);
+        if let Some(directive) = f.settings.to_directive() {
+            content += &directive;
+            content += "\n";
+        }
+        content += &playlist_entry_path(base_folder, &f.path);
+        content += "\n";
+    }
+    content
+}
+
+/// read the playlist content, M3U files may use the latin1 encoding
+fn read_playlist_content(filepath: &Path) -> Result<String, Box<dyn Error>> {
+    let bytes = fs::read(filepath)?;
+    Ok(match String::from_utf8(bytes) {
+        Ok(s) => s,
+        Err(e) => e.as_bytes().iter().map(|b| *b as char).collect(),
+    })
+}
 
-    let file_list: Vec<PlaylistElement> = contents
-        .split('\n')
-        .filter(|e| !e.is_empty())
-        .map(|p| (&PathBuf::from(p)).into())
+/// load a playlist from a file (M3U, M3U8 or legacy .playlist),
+/// the entries not found are reported in the load report of the returned playlist
+pub fn load(filepath: &Path) -> Result<PlayList, Box<dyn Error>> {
+    let contents = read_playlist_content(filepath)?;
+    let base_folder = filepath.parent().unwrap_or(Path::new(""));
+    let file_list = parse_entries(&contents, base_folder);
+
+    let missing: Vec<PathBuf> = file_list
+        .iter()
+        .filter(|e| e.is_file() && !e.path.exists())
+        .map(|e| e.path.clone())
         .collect();
+    if !missing.is_empty() {
+        warn!(
+            "{} entries of the playlist {} are not found",
+            missing.len(),
+            filepath.display()
+        );
+    }
 
     Ok(PlayList {
         file_list,
         is_dirty: true,
-        computed_length: None,
+        load_report: Some(LoadReport {
+            playlist: filepath.to_path_buf(),
+            missing,
+            resolved: vec![],
+            resolution_done: false,
+        }),
+        ..PlayList::new()
     })
 }
 
+/// read the entries of a playlist content (M3U or legacy format),
+/// relative entries are relative to the base folder
+fn parse_entries(contents: &str, base_folder: &Path) -> Vec<PlaylistElement> {
+    let mut file_list: Vec<PlaylistElement> = vec![];
+    // duration of the next entry, from the #EXTINF directive
+    let mut entry_duration: Option<Duration> = None;
+    // settings of the next entry
+    let mut entry_settings = EntrySettings::default();
+
+    for line in contents.lines() {
+        let line = line.trim_start_matches('\u{feff}').trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(extinf) = line.strip_prefix("#EXTINF:") {
+            entry_duration = extinf
+                .split(',')
+                .next()
+                .and_then(|d| d.trim().parse::<f64>().ok())
+                .filter(|d| *d >= 0.0)
+                .map(Duration::from_secs_f64);
+            continue;
+        }
+        if let Some(kind) = EntryKind::from_directive(line) {
+            file_list.push(PlaylistElement::special(kind));
+            continue;
+        }
+        if let Some(directive) = line.strip_prefix(ENTRY_SETTINGS_DIRECTIVE) {
+            entry_settings = EntrySettings::from_directive(directive);
+            continue;
+        }
+        if line.starts_with('#') {
+            // other directives and comments
+            continue;
+        }
+
+        let mut element: PlaylistElement = (&resolve_entry_path(base_folder, line)).into();
+        if let Some(duration) = entry_duration.take() {
+            element.additional_informations = Some(FileInformations {
+                duration: Some(duration),
+                ..Default::default()
+            });
+        }
+        element.settings = std::mem::take(&mut entry_settings);
+        file_list.push(element);
+    }
+    file_list
+}
+
+#[cfg(test)]
+fn test_list(count: usize) -> PlayList {
+    let mut playlist = PlayList::new();
+    for i in 0..count {
+        let mut element: PlaylistElement = (&PathBuf::from(format!("{}.mid", i))).into();
+        element.added_at = SystemTime::UNIX_EPOCH + Duration::from_secs(i as u64);
+        playlist.file_list.push(element);
+    }
+    playlist
+}
+
+#[test]
+pub fn test_playlist() {
+    let mut playlist = test_list(3);
+    assert_eq!(playlist.current().unwrap().name, "0.mid");
+    assert_eq!(playlist.upcoming().unwrap().name, "1.mid");
+
+    playlist.file_ended();
+    playlist.file_ended();
+    assert_eq!(playlist.current().unwrap().name, "2.mid");
+    assert!(playlist.upcoming().is_none());
+    playlist.file_ended();
+    assert!(playlist.current().is_none());
+    assert_eq!(playlist.file_list.len(), 3);
+
+    // a file added after the end becomes the current one
+    playlist.add_from_path(&PathBuf::from("3.mid"));
+    assert_eq!(playlist.current().unwrap().name, "3.mid");
+}
+
+#[test]
+pub fn test_playlist_repeat_modes() {
+    let mut playlist = test_list(2);
+    playlist.set_play_mode(PlayMode::RepeatAll);
+    playlist.file_ended();
+    playlist.file_ended();
+    assert_eq!(playlist.current_index, 0);
+
+    playlist.set_play_mode(PlayMode::RepeatOne);
+    playlist.file_ended();
+    assert_eq!(playlist.current_index, 0);
+    // next asked by the user
+    playlist.skip();
+    assert_eq!(playlist.current_index, 1);
+}
+
+#[test]
+pub fn test_playlist_shuffle() {
+    let mut playlist = test_list(5);
+    playlist.set_play_mode(PlayMode::Shuffle);
+
+    // each round plays all the elements
+    for _ in 0..3 {
+        let mut played = HashSet::new();
+        for _ in 0..5 {
+            played.insert(playlist.current_index);
+            let upcoming = playlist.upcoming().map(|e| e.added_at);
+            playlist.file_ended();
+            assert_eq!(upcoming, playlist.current().map(|e| e.added_at));
+        }
+        assert_eq!(played.len(), 5);
+    }
+}
+
+#[test]
+pub fn test_playlist_history() {
+    let mut playlist = test_list(3);
+    assert!(!playlist.previous());
+
+    for _ in 0..3 {
+        playlist.record_current_play();
+        playlist.file_ended();
+    }
+    assert!(playlist.current().is_none());
+    assert_eq!(playlist.history.len(), 3);
+
+    // replay the last one
+    assert!(playlist.previous());
+    assert_eq!(playlist.current().unwrap().name, "2.mid");
+    playlist.record_current_play();
+
+    // previous skip the current play
+    assert!(playlist.previous());
+    assert_eq!(playlist.current().unwrap().name, "1.mid");
+    assert_eq!(playlist.history.len(), 1);
+
+    // removed elements are inserted back
+    playlist.remove(0);
+    playlist.replay_history_entry(0);
+    assert_eq!(playlist.current().unwrap().name, "0.mid");
+    assert_eq!(playlist.file_list.len(), 3);
+}
+
+#[test]
+pub fn test_playlist_edit_keeps_cursor() {
+    let mut playlist = test_list(4);
+    playlist.skip();
+    playlist.skip();
+    playlist.remove(0);
+    assert_eq!(playlist.current().unwrap().name, "2.mid");
+
+    let mut reordered = playlist.file_list.clone();
+    reordered.reverse();
+    playlist.set_file_list(reordered);
+    assert_eq!(playlist.current().unwrap().name, "2.mid");
+    assert_eq!(playlist.remaining().len(), 2);
+}
+
+#[test]
+pub fn test_relative_path() {
+    assert_eq!(
+        relative_path(Path::new("/music/lists"), Path::new("/music/pieces/a.mid")),
+        PathBuf::from("../pieces/a.mid")
+    );
+    assert_eq!(
+        playlist_entry_path(Path::new("/music"), Path::new("/music/pieces/a.mid")),
+        "pieces/a.mid"
+    );
+}
+
 #[test]
-pub fn test_playlist() {}
+pub fn test_m3u_save_and_load() {
+    let folder = std::env::temp_dir().join(format!("vbp_m3u_test_{}", std::process::id()));
+    fs::create_dir_all(folder.join("pieces")).unwrap();
+    let existing = folder.join("pieces").join("valse.mid");
+    fs::write(&existing, b"").unwrap();
+
+    let mut playlist = PlayList::new();
+    playlist.add_from_path(&existing);
+    playlist.add_from_path(&folder.join("pieces").join("moved.mid"));
+    playlist.file_list[0].additional_informations = Some(FileInformations {
+        duration: Some(Duration::from_secs(125)),
+        ..Default::default()
+    });
+    playlist.file_list[1].settings.transpose = Some(-2);
+    playlist
+        .file_list
+        .push(PlaylistElement::special(EntryKind::TextCard(
+            "Intermission".into(),
+            Some(Duration::from_secs(600)),
+        )));
+    playlist
+        .file_list
+        .push(PlaylistElement::special(EntryKind::Stop));
+
+    let playlist_file = folder.join("evening.m3u8");
+    save(&playlist, &playlist_file).unwrap();
+    let content = fs::read_to_string(&playlist_file).unwrap();
+    assert!(content.starts_with("#EXTM3U\n#EXTINF:125,valse.mid\npieces/valse.mid\n"));
+
+    let mut loaded = load(&playlist_file).unwrap();
+    assert_eq!(loaded.file_list.len(), 4);
+    assert_eq!(loaded.file_list[2].kind, playlist.file_list[2].kind);
+    assert_eq!(loaded.file_list[3].kind, EntryKind::Stop);
+    assert_eq!(loaded.file_list[0].path, existing);
+    assert_eq!(
+        loaded.file_list[0]
+            .additional_informations
+            .as_ref()
+            .and_then(|i| i.duration),
+        Some(Duration::from_secs(125))
+    );
+    assert_eq!(loaded.load_report.as_ref().unwrap().missing.len(), 1);
+    assert!(loaded.file_list[0].settings.is_default());
+    assert_eq!(loaded.file_list[1].settings.transpose, Some(-2));
+
+    let found = PathBuf::from("/library/moved.mid");
+    loaded.resolve_missing_entries(&|name| {
+        (name == "moved.mid").then(|| PathBuf::from("/library/moved.mid"))
+    });
+    let report = loaded.load_report.as_ref().unwrap();
+    assert!(report.missing.is_empty());
+    assert_eq!(report.resolved.len(), 1);
+    assert_eq!(loaded.file_list[1].path, found);
+
+    fs::remove_dir_all(&folder).unwrap();
+}
+
+#[test]
+pub fn test_entry_settings_directive() {
+    let settings = EntrySettings {
+        wait: Some(Duration::from_millis(2500)),
+        speed: Some(90),
+        transpose: Some(-2),
+        start_offset: Some(Duration::from_secs(10)),
+        end_offset: None,
+        output: Some("Organ port".into()),
+    };
+    let directive = settings.to_directive().unwrap();
+    assert_eq!(
+        directive,
+        "#EXTVBP:wait=2.5;speed=90;transpose=-2;start=10;output=Organ port"
+    );
+    let read =
+        EntrySettings::from_directive(directive.strip_prefix(ENTRY_SETTINGS_DIRECTIVE).unwrap());
+    assert_eq!(read, settings);
+    assert!(EntrySettings::default().to_directive().is_none());
+    assert_eq!(settings.play_settings().speed, 0.9);
+}
+
+#[test]
+pub fn test_saved_queue() {
+    let mut playlist = test_list(3);
+    playlist.file_list[1].settings.transpose = Some(2);
+    playlist
+        .file_list
+        .push(PlaylistElement::special(EntryKind::Stop));
+    playlist.skip();
+
+    playlist.file_list[1].settings.wait = Some(Duration::from_secs(5));
+    playlist.file_list[1].settings.start_offset = Some(Duration::from_secs(10));
+
+    let saved = playlist.saved_queue(Some(Duration::from_secs(42)), Duration::from_secs(2));
+    assert!(saved.is_resumable());
+    assert_eq!(saved.current_name.as_deref(), Some("1.mid"));
+    // the position is in the file time, the wait of the entry is removed
+    assert_eq!(saved.position, Some(Duration::from_secs(37)));
+
+    let mut restored = PlayList::new();
+    restored.restore_queue(&saved);
+    assert_eq!(restored.file_list.len(), 4);
+    assert_eq!(restored.current_index, 1);
+    assert_eq!(restored.file_list[1].path, playlist.file_list[1].path);
+    assert_eq!(restored.file_list[1].settings.transpose, Some(2));
+    assert_eq!(restored.file_list[3].kind, EntryKind::Stop);
+    let resumed = restored
+        .current()
+        .unwrap()
+        .settings
+        .resumed_play_settings(saved.position);
+    assert_eq!(resumed.start_offset, Some(Duration::from_secs(37)));
+    assert_eq!(resumed.transpose, 2);
+
+    // saved during the wait, the entry is resumed at its start offset
+    let saved = playlist.saved_queue(Some(Duration::from_secs(3)), Duration::from_secs(2));
+    assert_eq!(saved.position, None);
+    let resumed = playlist.file_list[1]
+        .settings
+        .resumed_play_settings(saved.position);
+    assert_eq!(resumed.start_offset, Some(Duration::from_secs(10)));
+
+    // finished queue, nothing to resume
+    let finished = PlayList::new().saved_queue(None, Duration::ZERO);
+    assert!(!finished.is_resumable());
+}
+
+#[test]
+pub fn test_scheduled_program() {
+    let mut playlist = test_list(3);
+    playlist.skip();
+    playlist.set_play_mode(PlayMode::Shuffle);
+
+    playlist.start_program(Path::new("program.mid"), PlayMode::RepeatAll, true);
+    assert_eq!(playlist.file_list.len(), 1);
+    assert_eq!(playlist.play_mode, PlayMode::RepeatAll);
+    // a second program does not replace the queue of the operator
+    playlist.start_program(Path::new("other.mid"), PlayMode::Once, true);
+    playlist.file_ended();
+    assert!(playlist.current().is_none());
+
+    assert!(playlist.end_program());
+    assert_eq!(playlist.file_list.len(), 3);
+    assert_eq!(playlist.current().unwrap().name, "1.mid");
+    assert_eq!(playlist.play_mode, PlayMode::Shuffle);
+    assert!(!playlist.end_program());
+}
@@ -0,0 +1,251 @@
+//! Smart playlist generator, build a playlist of a target duration from the library,
+//! using the play statistics of the metadata database
+//!
+use std::{
+    path::PathBuf,
+    sync::mpsc::{channel, Receiver},
+    thread,
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    playmetadata::{
+        relative_database_path, FileSignature, PlayMetadataDatabase, METADATA_DATABASE_FILE,
+    },
+    random::XorShift64,
+};
+
+/// the playlist is considered complete when it is this close to the target duration,
+/// the last file may also exceed the target by this margin
+const TARGET_TOLERANCE: Duration = Duration::from_secs(60);
+
+/// rules of the generation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GeneratorSettings {
+    /// wanted duration of the playlist
+    pub target_duration: Duration,
+    /// the starred files are chosen more often
+    pub favour_starred: bool,
+    /// the files played in the last days are not chosen
+    pub skip_played_within_days: Option<u32>,
+}
+
+impl Default for GeneratorSettings {
+    fn default() -> Self {
+        GeneratorSettings {
+            target_duration: Duration::from_secs(45 * 60),
+            favour_starred: true,
+            skip_played_within_days: Some(7),
+        }
+    }
+}
+
+/// file of the library that may be chosen, with its statistics
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub path: PathBuf,
+    pub duration: Duration,
+    pub play_count: u32,
+    pub star_count: u32,
+    /// None if the file has never been played
+    pub latest_play_time: Option<DateTime<Utc>>,
+}
+
+impl Candidate {
+    /// weight in the random choice, the less played files are preferred
+    fn weight(&self, settings: &GeneratorSettings) -> f64 {
+        let mut weight = 1.0 / (1.0 + self.play_count as f64);
+        if settings.favour_starred {
+            weight *= 1.0 + self.star_count as f64;
+        }
+        weight
+    }
+}
+
+/// choose the files of the playlist, with a weighted random, until the target duration is reached
+pub fn generate(
+    candidates: &[Candidate],
+    settings: &GeneratorSettings,
+    now: DateTime<Utc>,
+    random: &mut XorShift64,
+) -> Vec<PathBuf> {
+    let recent_limit = settings
+        .skip_played_within_days
+        .map(|days| now - chrono::Duration::days(days as i64));
+
+    let mut remaining: Vec<&Candidate> = candidates
+        .iter()
+        .filter(|c| !c.duration.is_zero())
+        .filter(|c| match (recent_limit, c.latest_play_time) {
+            (Some(limit), Some(played)) => played < limit,
+            _ => true,
+        })
+        .collect();
+
+    let mut chosen = vec![];
+    let mut total = Duration::ZERO;
+    while total + TARGET_TOLERANCE < settings.target_duration {
+        let left = settings.target_duration - total + TARGET_TOLERANCE;
+        let fitting: Vec<usize> = remaining
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| c.duration <= left)
+            .map(|(i, _)| i)
+            .collect();
+        if fitting.is_empty() {
+            break;
+        }
+
+        let weights: Vec<f64> = fitting
+            .iter()
+            .map(|i| remaining[*i].weight(settings))
+            .collect();
+        let mut drawn = random.next_f64() * weights.iter().sum::<f64>();
+        let mut index = fitting[fitting.len() - 1];
+        for (i, weight) in fitting.iter().zip(weights.iter()) {
+            if drawn < *weight {
+                index = *i;
+                break;
+            }
+            drawn -= weight;
+        }
+
+        let candidate = remaining.swap_remove(index);
+        total += candidate.duration;
+        chosen.push(candidate.path.clone());
+    }
+    chosen
+}
+
+/// generate a playlist in a background thread, the files are given with their duration when
+/// it is known in the tree, the others are read in the informations cache of the metadata
+/// database (the files are not parsed, those not computed yet are not chosen),
+/// the chosen files are sent in the returned receiver
+pub fn start_generation(
+    files: Vec<(PathBuf, Option<Duration>)>,
+    library_folder: PathBuf,
+    settings: GeneratorSettings,
+) -> Receiver<Vec<PathBuf>> {
+    let (sender, receiver) = channel();
+    let spawned = thread::Builder::new()
+        .name("playlist-generator".to_string())
+        .spawn(move || {
+            let database = PlayMetadataDatabase::new(
+                library_folder
+                    .join(METADATA_DATABASE_FILE)
+                    .to_string_lossy()
+                    .to_string(),
+            )
+            .map_err(|e| error!("fail to open the metadata database : {}", e))
+            .ok();
+
+            let candidates: Vec<Candidate> = files
+                .into_iter()
+                .filter_map(|(path, known_duration)| {
+                    let duration = match known_duration {
+                        Some(duration) => duration,
+                        None => {
+                            let relative_path = relative_database_path(&library_folder, &path)?;
+                            let signature = FileSignature::of(&path)?;
+                            database
+                                .as_ref()?
+                                .get_file_informations(&relative_path, &signature)
+                                .ok()??
+                                .duration?
+                        }
+                    };
+                    let stats = database.as_ref().and_then(|db| {
+                        db.get_played_file_stats_with_statistics(relative_database_path(
+                            &library_folder,
+                            &path,
+                        )?)
+                        .ok()
+                        .flatten()
+                    });
+                    Some(Candidate {
+                        path,
+                        duration,
+                        play_count: stats.as_ref().map(|s| s.total_play_number).unwrap_or(0),
+                        star_count: stats.as_ref().map(|s| s.total_star_count).unwrap_or(0),
+                        latest_play_time: stats
+                            .filter(|s| s.total_play_number > 0)
+                            .map(|s| s.latest_play_time),
+                    })
+                })
+                .collect();
+
+            let playlist = generate(
+                &candidates,
+                &settings,
+                Utc::now(),
+                &mut XorShift64::from_time(),
+            );
+            info!(
+                "{} files chosen in {} candidates",
+                playlist.len(),
+                candidates.len()
+            );
+            // the receiver may have been dropped
+            let _ = sender.send(playlist);
+        });
+    if let Err(e) = spawned {
+        error!("fail to start the playlist generation : {}", e);
+    }
+    receiver
+}
+
+#[cfg(test)]
+fn test_candidate(name: &str, minutes: u64, play_count: u32, star_count: u32) -> Candidate {
+    Candidate {
+        path: PathBuf::from(name),
+        duration: Duration::from_secs(minutes * 60),
+        play_count,
+        star_count,
+        latest_play_time: None,
+    }
+}
+
+#[test]
+pub fn test_generate_target_duration() {
+    let candidates: Vec<Candidate> = (0..40)
+        .map(|i| test_candidate(&format!("{}.mid", i), 3 + i % 4, i as u32 % 3, 0))
+        .collect();
+    let settings = GeneratorSettings::default();
+
+    let playlist = generate(&candidates, &settings, Utc::now(), &mut XorShift64::new(7));
+    let total: Duration = playlist
+        .iter()
+        .map(|p| candidates.iter().find(|c| &c.path == p).unwrap().duration)
+        .sum();
+    assert!(total + TARGET_TOLERANCE >= settings.target_duration);
+    assert!(total <= settings.target_duration + TARGET_TOLERANCE);
+
+    // no file chosen twice
+    let mut unique = playlist.clone();
+    unique.sort();
+    unique.dedup();
+    assert_eq!(unique.len(), playlist.len());
+}
+
+#[test]
+pub fn test_generate_skip_recently_played() {
+    let now = Utc::now();
+    let mut recent = test_candidate("recent.mid", 5, 1, 10);
+    recent.latest_play_time = Some(now - chrono::Duration::days(2));
+    let mut old = test_candidate("old.mid", 5, 1, 0);
+    old.latest_play_time = Some(now - chrono::Duration::days(30));
+    let candidates = vec![recent, old, test_candidate("never.mid", 5, 0, 0)];
+
+    let settings = GeneratorSettings {
+        target_duration: Duration::from_secs(60 * 60),
+        ..Default::default()
+    };
+    let playlist = generate(&candidates, &settings, now, &mut XorShift64::new(1));
+    assert_eq!(playlist.len(), 2);
+    assert!(!playlist.contains(&PathBuf::from("recent.mid")));
+}
@@ -9,7 +9,11 @@ use log::{debug, error, info};
 //    the latest play time history
 //    the total play time
 //    some "stars"/rank (1..5) hits from the user
-use rusqlite::Connection;
+use rusqlite::{Connection, OptionalExtension};
+use std::path::Path;
+use std::time::{Duration, UNIX_EPOCH};
+
+use player::{ChannelInformations, FileInformations, FileInformationsConstructor};
 
 /// Helper function to convert DateTime<Utc> to SQLite DATE format (YYYY-MM-DD HH:MM:SS)
 /// SQLite DATE columns work best with this format for date functions
@@ -62,8 +66,67 @@ pub struct PlayedFileStarsHistoryEntry {
     rank: u8,
 }
 
+/// size and modification time of a file, the cached informations of the file
+/// are invalidated when they change
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileSignature {
+    pub size: u64,
+    /// modification time, in milliseconds since the epoch
+    pub modified: i64,
+}
+
+impl FileSignature {
+    /// signature of the file on disk
+    pub fn of(path: &Path) -> Option<Self> {
+        let metadata = std::fs::metadata(path).ok()?;
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(FileSignature {
+            size: metadata.len(),
+            modified: modified.as_millis() as i64,
+        })
+    }
+}
+
+/// relative path of a file in the database, with normalized separators,
+/// None if the file is not in the library folder
+pub fn relative_database_path(library_folder: &Path, path: &Path) -> Option<String> {
+    path.strip_prefix(library_folder)
+        .ok()
+        .map(|relative| relative.to_string_lossy().replace('\\', "/"))
+}
+
+/// channels stored as a text, "channel:program" separated by commas (program is empty if not set)
+fn channels_to_text(channels: &[ChannelInformations]) -> String {
+    channels
+        .iter()
+        .map(|c| {
+            format!(
+                "{}:{}",
+                c.channel,
+                c.program.map(|p| p.to_string()).unwrap_or_default()
+            )
+        })
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+fn channels_from_text(text: &str) -> Vec<ChannelInformations> {
+    text.split(',')
+        .filter_map(|c| {
+            let (channel, program) = c.split_once(':')?;
+            Some(ChannelInformations {
+                channel: channel.parse().ok()?,
+                program: program.parse().ok(),
+            })
+        })
+        .collect()
+}
+
 const CURRENT_MODEL_VERSION: &str = "1.0.0";
 
+/// name of the database file, in the library folder
+pub const METADATA_DATABASE_FILE: &str = ".playmetadata.db";
+
 // implement the database operations
 impl PlayMetadataDatabase {
     pub fn new(db_file_path: String) -> Result<Self, Box<dyn std::error::Error>> {
@@ -302,6 +365,62 @@ impl PlayMetadataDatabase {
             )
             .map_err(Box::new)?;
 
+        // create the file_informations table
+        // cache of the informations computed by reading the files,
+        // valid while the size and modification time of the file are the same
+        self.connection
+            .execute(
+                "CREATE TABLE IF NOT EXISTS file_informations (
+            relative_file_path TEXT PRIMARY KEY,
+            file_size INTEGER,
+            file_modified INTEGER,
+            duration_ms INTEGER,
+            title TEXT,
+            channels TEXT,
+            note_low INTEGER,
+            note_high INTEGER,
+            polyphony_peak INTEGER,
+            tempo INTEGER,
+            time_signature_numerator INTEGER,
+            time_signature_denominator INTEGER,
+            scale_name TEXT,
+            track_count INTEGER
+        )",
+                (),
+            )
+            .map_err(Box::new)?;
+
+        // create the file_checksums table
+        // md5 checksums of the library files, valid while the size and modification time are the same,
+        // used to follow the renamed or moved files and find the duplicates
+        self.connection
+            .execute(
+                "CREATE TABLE IF NOT EXISTS file_checksums (
+            relative_file_path TEXT PRIMARY KEY,
+            file_size INTEGER,
+            file_modified INTEGER,
+            file_md5_checksum TEXT
+        )",
+                (),
+            )
+            .map_err(Box::new)?;
+
+        self.connection
+            .execute(
+                "CREATE INDEX IF NOT EXISTS idx_file_checksums_md5 \
+                ON file_checksums(file_md5_checksum)",
+                (),
+            )
+            .map_err(Box::new)?;
+
+        self.connection
+            .execute(
+                "CREATE INDEX IF NOT EXISTS idx_played_file_stats_md5 \
+                ON played_file_stats(file_md5_checksum)",
+                (),
+            )
+            .map_err(Box::new)?;
+
         Ok(())
     }
 
@@ -319,8 +438,12 @@ impl PlayMetadataDatabase {
                 (relative_file_path, file_md5_checksum, user_comments_or_notes) \
                 VALUES (?, ?, ?) \
                 ON CONFLICT(relative_file_path) DO UPDATE SET \
-                file_md5_checksum = excluded.file_md5_checksum, \
-                user_comments_or_notes = excluded.user_comments_or_notes",
+                file_md5_checksum = CASE WHEN excluded.file_md5_checksum = '' \
+                    THEN played_file_stats.file_md5_checksum \
+                    ELSE excluded.file_md5_checksum END, \
+                user_comments_or_notes = CASE WHEN excluded.user_comments_or_notes = '' \
+                    THEN played_file_stats.user_comments_or_notes \
+                    ELSE excluded.user_comments_or_notes END",
                 (
                     played_file_stats.relative_file_path,
                     played_file_stats.file_md5_checksum,
@@ -610,6 +733,291 @@ impl PlayMetadataDatabase {
             }
         }
     }
+
+    /// Get the cached informations of a file, None if they are not known
+    /// or if the file has changed since they have been computed
+    pub fn get_file_informations(
+        &self,
+        relative_file_path: &str,
+        signature: &FileSignature,
+    ) -> Result<Option<FileInformations>, Box<dyn std::error::Error>> {
+        let sql = "\
+            SELECT file_size, file_modified, duration_ms, title, channels, note_low, note_high, \
+                polyphony_peak, tempo, time_signature_numerator, time_signature_denominator, \
+                scale_name, track_count \
+            FROM file_informations \
+            WHERE relative_file_path = ?";
+
+        let result = self
+            .connection
+            .query_row(sql, [relative_file_path], |row| {
+                let cached_signature = FileSignature {
+                    size: row.get::<_, i64>(0)? as u64,
+                    modified: row.get(1)?,
+                };
+                let note_low: Option<u8> = row.get(5)?;
+                let note_high: Option<u8> = row.get(6)?;
+                let numerator: Option<u8> = row.get(9)?;
+                let denominator: Option<u8> = row.get(10)?;
+                let informations = FileInformations {
+                    duration: row
+                        .get::<_, Option<i64>>(2)?
+                        .map(|ms| Duration::from_millis(ms as u64)),
+                    title: row.get(3)?,
+                    channels: channels_from_text(&row.get::<_, String>(4)?),
+                    note_range: note_low.zip(note_high),
+                    polyphony_peak: row.get::<_, i64>(7)? as usize,
+                    tempo: row.get(8)?,
+                    time_signature: numerator.zip(denominator),
+                    scale_name: row.get(11)?,
+                    track_count: row.get::<_, Option<i64>>(12)?.map(|c| c as usize),
+                };
+                Ok((cached_signature, informations))
+            })
+            .optional()
+            .map_err(|e| {
+                error!(
+                    "Database error querying informations of '{}': {}",
+                    relative_file_path, e
+                );
+                Box::new(e)
+            })?;
+
+        Ok(result
+            .filter(|(cached_signature, _)| cached_signature == signature)
+            .map(|(_, informations)| informations))
+    }
+
+    /// Store the computed informations of a file, replacing the previous ones
+    pub fn store_file_informations(
+        &self,
+        relative_file_path: &str,
+        signature: &FileSignature,
+        informations: &FileInformations,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.connection
+            .execute(
+                "INSERT OR REPLACE INTO file_informations \
+                (relative_file_path, file_size, file_modified, duration_ms, title, channels, \
+                note_low, note_high, polyphony_peak, tempo, time_signature_numerator, \
+                time_signature_denominator, scale_name, track_count) \
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                rusqlite::params![
+                    relative_file_path,
+                    signature.size as i64,
+                    signature.modified,
+                    informations.duration.map(|d| d.as_millis() as i64),
+                    informations.title,
+                    channels_to_text(&informations.channels),
+                    informations.note_range.map(|(low, _)| low),
+                    informations.note_range.map(|(_, high)| high),
+                    informations.polyphony_peak as i64,
+                    informations.tempo,
+                    informations.time_signature.map(|(numerator, _)| numerator),
+                    informations
+                        .time_signature
+                        .map(|(_, denominator)| denominator),
+                    informations.scale_name,
+                    informations.track_count.map(|c| c as i64),
+                ],
+            )
+            .map_err(|e| {
+                error!(
+                    "Error storing informations of '{}': {}",
+                    relative_file_path, e
+                );
+                Box::new(e)
+            })?;
+        Ok(())
+    }
+
+    /// Informations of a file of the library, read from the cache if the file has not changed,
+    /// computed and stored otherwise (the files outside the library are only computed)
+    pub fn cached_file_informations(
+        &self,
+        library_folder: &Path,
+        path: &Path,
+        info_getter: &mut dyn FileInformationsConstructor,
+    ) -> Result<FileInformations, Box<dyn std::error::Error>> {
+        let key = relative_database_path(library_folder, path).zip(FileSignature::of(path));
+        if let Some((relative_file_path, signature)) = &key {
+            if let Some(informations) = self.get_file_informations(relative_file_path, signature)? {
+                return Ok(informations);
+            }
+        }
+
+        let informations = info_getter.compute(&path.to_path_buf())?;
+        if let Some((relative_file_path, signature)) = &key {
+            self.store_file_informations(relative_file_path, signature, &informations)?;
+        }
+        Ok(informations)
+    }
+
+    /// Checksum of a file of the library, read from the cache if the file has not changed,
+    /// computed and stored otherwise, None if the file is not in the library
+    pub fn cached_file_checksum(
+        &self,
+        library_folder: &Path,
+        path: &Path,
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let (Some(relative_file_path), Some(signature)) = (
+            relative_database_path(library_folder, path),
+            FileSignature::of(path),
+        ) else {
+            return Ok(None);
+        };
+
+        let cached: Option<(i64, i64, String)> = self
+            .connection
+            .query_row(
+                "SELECT file_size, file_modified, file_md5_checksum FROM file_checksums \
+                WHERE relative_file_path = ?",
+                [&relative_file_path],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()
+            .map_err(Box::new)?;
+        if let Some((size, modified, checksum)) = cached {
+            if size as u64 == signature.size && modified == signature.modified {
+                return Ok(Some(checksum));
+            }
+        }
+
+        let checksum = format!("{:x}", md5::compute(std::fs::read(path)?));
+        self.connection
+            .execute(
+                "INSERT OR REPLACE INTO file_checksums \
+                (relative_file_path, file_size, file_modified, file_md5_checksum) \
+                VALUES (?, ?, ?, ?)",
+                (
+                    &relative_file_path,
+                    signature.size as i64,
+                    signature.modified,
+                    &checksum,
+                ),
+            )
+            .map_err(Box::new)?;
+        Ok(Some(checksum))
+    }
+
+    /// Compute the checksum of a library file, and re-link the play history of a file
+    /// having the same checksum, and no more existing (renamed or moved file)
+    /// returns the previous path of the file if the history has been re-linked
+    pub fn follow_file(
+        &self,
+        library_folder: &Path,
+        path: &Path,
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let (Some(relative_file_path), Some(checksum)) = (
+            relative_database_path(library_folder, path),
+            self.cached_file_checksum(library_folder, path)?,
+        ) else {
+            return Ok(None);
+        };
+
+        // the file already has a history, its checksum is kept up to date
+        let updated = self
+            .connection
+            .execute(
+                "UPDATE played_file_stats SET file_md5_checksum = ? WHERE relative_file_path = ?",
+                (&checksum, &relative_file_path),
+            )
+            .map_err(Box::new)?;
+        if updated > 0 {
+            return Ok(None);
+        }
+
+        let mut stmt = self
+            .connection
+            .prepare(
+                "SELECT id, relative_file_path FROM played_file_stats \
+                WHERE file_md5_checksum = ? AND relative_file_path <> ?",
+            )
+            .map_err(Box::new)?;
+        let candidates: Vec<(i64, String)> = stmt
+            .query_map((&checksum, &relative_file_path), |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .map_err(Box::new)?
+            .collect::<Result<_, _>>()
+            .map_err(Box::new)?;
+
+        // the history of a still existing file is a copy, not a move
+        let Some((id, previous_path)) = candidates
+            .into_iter()
+            .find(|(_, previous)| !library_folder.join(previous).exists())
+        else {
+            return Ok(None);
+        };
+
+        info!(
+            "File '{}' moved to '{}', the play history follows it",
+            previous_path, relative_file_path
+        );
+        self.connection
+            .execute(
+                "UPDATE played_file_stats SET relative_file_path = ? WHERE id = ?",
+                (&relative_file_path, id),
+            )
+            .map_err(Box::new)?;
+        for table in ["file_checksums", "file_informations"] {
+            self.connection
+                .execute(
+                    &format!("DELETE FROM {} WHERE relative_file_path = ?", table),
+                    [&previous_path],
+                )
+                .map_err(Box::new)?;
+        }
+        Ok(Some(previous_path))
+    }
+
+    /// User comments (or tags) of the files, by relative path, the files without comment are skipped
+    pub fn user_comments(&self) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
+        let mut stmt = self
+            .connection
+            .prepare(
+                "SELECT relative_file_path, user_comments_or_notes FROM played_file_stats \
+                WHERE user_comments_or_notes IS NOT NULL AND user_comments_or_notes <> ''",
+            )
+            .map_err(Box::new)?;
+        let comments = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(Box::new)?
+            .collect::<Result<_, _>>()
+            .map_err(Box::new)?;
+        Ok(comments)
+    }
+
+    /// Groups of files having the same checksum, among the files whose checksum is computed
+    pub fn duplicate_files(&self) -> Result<Vec<Vec<String>>, Box<dyn std::error::Error>> {
+        let mut stmt = self
+            .connection
+            .prepare(
+                "SELECT file_md5_checksum, relative_file_path FROM file_checksums \
+                WHERE file_md5_checksum IN (SELECT file_md5_checksum FROM file_checksums \
+                    GROUP BY file_md5_checksum HAVING COUNT(*) > 1) \
+                ORDER BY file_md5_checksum, relative_file_path",
+            )
+            .map_err(Box::new)?;
+        let rows: Vec<(String, String)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(Box::new)?
+            .collect::<Result<_, _>>()
+            .map_err(Box::new)?;
+
+        let mut groups: Vec<Vec<String>> = vec![];
+        let mut current_checksum: Option<String> = None;
+        for (checksum, path) in rows {
+            if current_checksum.as_ref() != Some(&checksum) {
+                groups.push(vec![]);
+                current_checksum = Some(checksum);
+            }
+            if let Some(group) = groups.last_mut() {
+                group.push(path);
+            }
+        }
+        Ok(groups)
+    }
 }
 
 #[cfg(test)]
@@ -1124,6 +1532,134 @@ mod tests {
         assert!(retrieved.is_some());
     }
 
+    #[test]
+    fn test_file_informations_cache() {
+        let db = create_test_db();
+        let signature = FileSignature {
+            size: 1024,
+            modified: 1_700_000_000_000,
+        };
+        let informations = FileInformations {
+            duration: Some(Duration::from_millis(125_500)),
+            title: Some("Song".to_string()),
+            channels: vec![
+                ChannelInformations {
+                    channel: 0,
+                    program: Some(20),
+                },
+                ChannelInformations {
+                    channel: 9,
+                    program: None,
+                },
+            ],
+            note_range: Some((36, 84)),
+            polyphony_peak: 6,
+            tempo: Some(500_000),
+            time_signature: Some((3, 4)),
+            scale_name: None,
+            track_count: None,
+        };
+
+        assert!(db
+            .get_file_informations("test/file.mid", &signature)
+            .unwrap()
+            .is_none());
+        db.store_file_informations("test/file.mid", &signature, &informations)
+            .expect("Failed to store informations");
+        assert_eq!(
+            db.get_file_informations("test/file.mid", &signature)
+                .unwrap(),
+            Some(informations)
+        );
+
+        // the file has changed, the cache is invalidated
+        let changed = FileSignature {
+            size: 1030,
+            ..signature
+        };
+        assert!(db
+            .get_file_informations("test/file.mid", &changed)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_user_comments() {
+        let db = create_test_db();
+        db.insert_or_update_played_file_stats(create_test_stats("waltz.mid"))
+            .unwrap();
+        let mut without_comment = create_test_stats("march.mid");
+        without_comment.user_comments_or_notes = String::new();
+        db.insert_or_update_played_file_stats(without_comment)
+            .unwrap();
+
+        let comments = db.user_comments().unwrap();
+        assert_eq!(
+            comments,
+            vec![("waltz.mid".to_string(), "Notes for waltz.mid".to_string())]
+        );
+
+        // the play events record the file without comment, the comment is kept
+        let mut played = create_test_stats("waltz.mid");
+        played.user_comments_or_notes = String::new();
+        db.insert_or_update_played_file_stats(played).unwrap();
+        assert_eq!(db.user_comments().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_follow_moved_file_and_duplicates() {
+        use std::time::{SystemTime, UNIX_EPOCH};
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let folder =
+            std::path::PathBuf::from(format!("/tmp/test_playmetadata_follow_{}", timestamp));
+        fs::create_dir_all(&folder).unwrap();
+        let db = PlayMetadataDatabase::new(
+            folder
+                .join(METADATA_DATABASE_FILE)
+                .to_string_lossy()
+                .to_string(),
+        )
+        .expect("Failed to create test database");
+
+        fs::write(folder.join("a.mid"), b"MThd content").unwrap();
+        let mut stats = create_test_stats("a.mid");
+        stats.file_md5_checksum = String::new();
+        db.insert_or_update_played_file_stats(stats).unwrap();
+        db.add_play_event("a.mid".to_string(), Utc::now()).unwrap();
+        assert_eq!(
+            db.follow_file(&folder, &folder.join("a.mid")).unwrap(),
+            None
+        );
+
+        // the renamed file keeps its history
+        fs::rename(folder.join("a.mid"), folder.join("b.mid")).unwrap();
+        assert_eq!(
+            db.follow_file(&folder, &folder.join("b.mid")).unwrap(),
+            Some("a.mid".to_string())
+        );
+        let moved = db
+            .get_played_file_stats_with_statistics("b.mid".to_string())
+            .unwrap()
+            .expect("history not followed");
+        assert_eq!(moved.total_play_number, 1);
+
+        // a copy is a duplicate, the history stays on the original file
+        fs::write(folder.join("c.mid"), b"MThd content").unwrap();
+        assert_eq!(
+            db.follow_file(&folder, &folder.join("c.mid")).unwrap(),
+            None
+        );
+        assert_eq!(
+            db.duplicate_files().unwrap(),
+            vec![vec!["b.mid".to_string(), "c.mid".to_string()]]
+        );
+
+        let _ = fs::remove_dir_all(&folder);
+    }
+
     #[test]
     fn test_date_ordering() {
         let db = create_test_db();
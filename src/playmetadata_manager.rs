@@ -1,17 +1,25 @@
 //! Play metadata manager - handles background thread for play count queries
 //! and recording play events with low priority to not interfere with playback
 
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
 use std::sync::mpsc::{channel, Receiver, Sender};
 use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use log::{debug, error, info, warn};
+use player::midiio::MidiFileInformationsConstructor;
+use player::FileInformations;
 
-use crate::playmetadata::PlayMetadataDatabase;
+use crate::catalogue::EntryDetails;
+use crate::playmetadata::{
+    relative_database_path, FileSignature, PlayMetadataDatabase, PlayedFileStats,
+};
+
+/// maximum time spent computing file informations between two commands
+const INFORMATIONS_BATCH_TIME: Duration = Duration::from_millis(50);
 
 /// Commands sent to the background metadata thread
 #[derive(Debug, Clone)]
@@ -25,13 +33,37 @@ pub enum MetadataCommand {
     RecordStarEvent(PathBuf),
     /// Update the database path (when folder changes)
     UpdateDatabasePath(PathBuf),
+    /// Compute (or read from the cache) the informations of the files, with low priority
+    ComputeInformations(Vec<PathBuf>),
+    /// Compute the checksums of the files, with low priority (after the informations),
+    /// the play history of the moved files is re-linked
+    ComputeChecksums(Vec<PathBuf>),
+    /// Search the files having the same checksum
+    FindDuplicates,
+    /// Read the user comments of the files, for the search
+    LoadComments,
+    /// Read the durations and star counts of the catalogue files, for its export,
+    /// the missing durations are computed first
+    LoadCatalogueDetails(Vec<PathBuf>),
 }
 
-/// File metadata (play count and star count)
+/// File metadata (play count, star count and last play time)
 #[derive(Debug, Clone)]
 pub struct FileMetadata {
     pub play_count: u32,
     pub star_count: u32,
+    pub last_played: Option<DateTime<Utc>>,
+}
+
+impl FileMetadata {
+    pub fn of(stats: &PlayedFileStats) -> Self {
+        FileMetadata {
+            play_count: stats.total_play_number,
+            star_count: stats.total_star_count,
+            // the play time is the epoch when the file has never been played
+            last_played: (stats.total_play_number > 0).then_some(stats.latest_play_time),
+        }
+    }
 }
 
 /// Results sent back from the background thread
@@ -39,6 +71,14 @@ pub struct FileMetadata {
 pub enum MetadataResult {
     /// Play counts and star counts for files (path -> metadata)
     PlayCounts(HashMap<PathBuf, FileMetadata>),
+    /// Computed informations of files (duration, title ...)
+    Informations(HashMap<PathBuf, FileInformations>),
+    /// Groups of identical files, and the number of files whose checksum is not computed yet
+    Duplicates(Vec<Vec<PathBuf>>, usize),
+    /// User comments (or tags) of the files
+    Comments(HashMap<PathBuf, String>),
+    /// Durations and star counts of the catalogue files
+    CatalogueDetails(HashMap<PathBuf, EntryDetails>),
 }
 
 /// Play metadata manager - coordinates background thread for metadata operations
@@ -95,10 +135,22 @@ impl PlayMetadataManager {
     ) {
         let mut database: Option<PlayMetadataDatabase> = None;
         let mut folder_path: Option<PathBuf> = None; // Store folder path separately
+                                                     // files waiting for their informations, computed between the commands
+        let mut pending_informations: VecDeque<PathBuf> = VecDeque::new();
+        let mut pending_checksums: VecDeque<PathBuf> = VecDeque::new();
+        // catalogue export waiting for the informations of some of its files
+        let mut pending_catalogue: Option<(Vec<PathBuf>, HashSet<PathBuf>)> = None;
+        let mut info_getter = MidiFileInformationsConstructor {};
 
         loop {
             // Use try_recv with timeout to allow periodic checks and low CPU usage
-            match command_receiver.recv_timeout(Duration::from_millis(100)) {
+            // (the timeout is short while file informations are pending)
+            let timeout = if pending_informations.is_empty() && pending_checksums.is_empty() {
+                Duration::from_millis(100)
+            } else {
+                Duration::from_millis(1)
+            };
+            match command_receiver.recv_timeout(timeout) {
                 Ok(command) => {
                     match command {
                         MetadataCommand::UpdateDatabasePath(new_path) => {
@@ -109,6 +161,10 @@ impl PlayMetadataManager {
                             // Close old database if exists
                             drop(database);
                             database = None;
+                            pending_informations.clear();
+                            pending_checksums.clear();
+                            // the export of the previous library is abandoned
+                            pending_catalogue = None;
                             *database_ready.lock().unwrap() = false;
 
                             // Extract folder path (parent of database file)
@@ -196,10 +252,7 @@ impl PlayMetadataManager {
                                                     debug!("Background thread: Found metadata for {}: play_count={}, star_count={}", relative_str, stats.total_play_number, stats.total_star_count);
                                                     file_metadata.insert(
                                                         path.clone(),
-                                                        FileMetadata {
-                                                            play_count: stats.total_play_number,
-                                                            star_count: stats.total_star_count,
-                                                        },
+                                                        FileMetadata::of(&stats),
                                                     );
                                                 }
                                                 Ok(None) => {
@@ -210,6 +263,7 @@ impl PlayMetadataManager {
                                                         FileMetadata {
                                                             play_count: 0,
                                                             star_count: 0,
+                                                            last_played: None,
                                                         },
                                                     );
                                                 }
@@ -282,7 +336,11 @@ impl PlayMetadataManager {
                                         match db.insert_or_update_played_file_stats(
                                             crate::playmetadata::PlayedFileStats {
                                                 relative_file_path: relative_str.clone(),
-                                                file_md5_checksum: String::new(), // Not computed for performance
+                                                file_md5_checksum: db
+                                                    .cached_file_checksum(folder, &path)
+                                                    .ok()
+                                                    .flatten()
+                                                    .unwrap_or_default(),
                                                 latest_play_time: Utc::now(),
                                                 total_play_number: 0, // Will be computed from history
                                                 total_star_count: 0, // Will be computed from star history
@@ -332,10 +390,7 @@ impl PlayMetadataManager {
                                                     let mut file_metadata = HashMap::new();
                                                     file_metadata.insert(
                                                         path.clone(),
-                                                        FileMetadata {
-                                                            play_count: stats.total_play_number,
-                                                            star_count: stats.total_star_count,
-                                                        },
+                                                        FileMetadata::of(&stats),
                                                     );
                                                     // Send updated metadata back immediately
                                                     if let Err(e) = result_sender.send(
@@ -385,7 +440,11 @@ impl PlayMetadataManager {
                                         match db.insert_or_update_played_file_stats(
                                             crate::playmetadata::PlayedFileStats {
                                                 relative_file_path: relative_str.clone(),
-                                                file_md5_checksum: String::new(), // Not computed for performance
+                                                file_md5_checksum: db
+                                                    .cached_file_checksum(folder, &path)
+                                                    .ok()
+                                                    .flatten()
+                                                    .unwrap_or_default(),
                                                 latest_play_time: Utc::now(),
                                                 total_play_number: 0, // Will be computed from history
                                                 total_star_count: 0, // Will be computed from star history
@@ -418,10 +477,7 @@ impl PlayMetadataManager {
                                                         let mut file_metadata = HashMap::new();
                                                         file_metadata.insert(
                                                             path.clone(),
-                                                            FileMetadata {
-                                                                play_count: stats.total_play_number,
-                                                                star_count: stats.total_star_count,
-                                                            },
+                                                            FileMetadata::of(&stats),
                                                         );
                                                         if let Err(e) = result_sender.send(
                                                             MetadataResult::PlayCounts(
@@ -455,6 +511,83 @@ impl PlayMetadataManager {
                             }
                             // If no database, silently ignore (don't break the app)
                         }
+                        MetadataCommand::ComputeInformations(paths) => {
+                            debug!(
+                                "Background thread: {} files added for informations computation",
+                                paths.len()
+                            );
+                            pending_informations.extend(paths);
+                        }
+                        MetadataCommand::ComputeChecksums(paths) => {
+                            debug!(
+                                "Background thread: {} files added for checksum computation",
+                                paths.len()
+                            );
+                            pending_checksums.extend(paths);
+                        }
+                        MetadataCommand::FindDuplicates => {
+                            let mut duplicates = vec![];
+                            if let (Some(db), Some(folder)) = (&database, &folder_path) {
+                                match db.duplicate_files() {
+                                    Ok(groups) => {
+                                        // the removed files may still be in the checksums
+                                        duplicates = groups
+                                            .into_iter()
+                                            .map(|group| {
+                                                group
+                                                    .into_iter()
+                                                    .map(|p| folder.join(p))
+                                                    .filter(|p| p.exists())
+                                                    .collect::<Vec<PathBuf>>()
+                                            })
+                                            .filter(|group| group.len() > 1)
+                                            .collect();
+                                    }
+                                    Err(e) => error!("Error searching the duplicate files: {}", e),
+                                }
+                            }
+                            let _ = result_sender.send(MetadataResult::Duplicates(
+                                duplicates,
+                                pending_checksums.len(),
+                            ));
+                        }
+                        MetadataCommand::LoadComments => {
+                            if let (Some(db), Some(folder)) = (&database, &folder_path) {
+                                match db.user_comments() {
+                                    Ok(comments) => {
+                                        let comments = comments
+                                            .into_iter()
+                                            .map(|(path, comment)| (folder.join(path), comment))
+                                            .collect();
+                                        let _ =
+                                            result_sender.send(MetadataResult::Comments(comments));
+                                    }
+                                    Err(e) => error!("Error reading the user comments: {}", e),
+                                }
+                            }
+                        }
+                        MetadataCommand::LoadCatalogueDetails(paths) => {
+                            if let (Some(db), Some(folder)) = (&database, &folder_path) {
+                                // the informations not computed yet are computed first,
+                                // in batches, the details are sent once they are known
+                                let missing: HashSet<PathBuf> = paths
+                                    .iter()
+                                    .filter(|path| cached_informations(db, folder, path).is_none())
+                                    .cloned()
+                                    .collect();
+                                debug!(
+                                    "Background thread: {} catalogue files to compute",
+                                    missing.len()
+                                );
+                                for path in &missing {
+                                    pending_informations.push_front(path.clone());
+                                }
+                                pending_catalogue = Some((paths, missing));
+                            } else {
+                                let _ = result_sender
+                                    .send(MetadataResult::CatalogueDetails(HashMap::new()));
+                            }
+                        }
                     }
                 }
                 Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
@@ -467,6 +600,53 @@ impl PlayMetadataManager {
                     break;
                 }
             }
+
+            // Compute a batch of pending file informations, the commands are handled in between
+            if let (Some(db), Some(folder)) = (&database, &folder_path) {
+                let started = Instant::now();
+                let mut informations = HashMap::new();
+                while started.elapsed() < INFORMATIONS_BATCH_TIME {
+                    if let Some(path) = pending_informations.pop_front() {
+                        if let Some((_, missing)) = &mut pending_catalogue {
+                            missing.remove(&path);
+                        }
+                        match db.cached_file_informations(folder, &path, &mut info_getter) {
+                            Ok(computed) => {
+                                informations.insert(path, computed);
+                            }
+                            Err(e) => {
+                                debug!(
+                                    "Background thread: fail to compute informations of {:?}: {}",
+                                    path, e
+                                );
+                            }
+                        }
+                    } else if let Some(path) = pending_checksums.pop_front() {
+                        // the history of a moved file is re-linked, the play counts
+                        // are refreshed with the next periodic query
+                        if let Err(e) = db.follow_file(folder, &path) {
+                            debug!(
+                                "Background thread: fail to compute checksum of {:?}: {}",
+                                path, e
+                            );
+                        }
+                    } else {
+                        break;
+                    }
+                }
+                if !informations.is_empty() {
+                    if let Err(e) = result_sender.send(MetadataResult::Informations(informations)) {
+                        warn!("Background thread: Failed to send informations: {}", e);
+                    }
+                }
+
+                if matches!(&pending_catalogue, Some((_, missing)) if missing.is_empty()) {
+                    if let Some((paths, _)) = pending_catalogue.take() {
+                        let details = catalogue_details(db, folder, paths);
+                        let _ = result_sender.send(MetadataResult::CatalogueDetails(details));
+                    }
+                }
+            }
         }
     }
 
@@ -519,6 +699,71 @@ impl PlayMetadataManager {
         }
     }
 
+    /// Compute the informations of the files (durations ...) in background,
+    /// the results are returned with the other results
+    pub fn compute_informations(&self, paths: Vec<PathBuf>) {
+        debug!(
+            "MetadataManager: Sending informations computation for {} files",
+            paths.len()
+        );
+        if let Err(e) = self
+            .command_sender
+            .send(MetadataCommand::ComputeInformations(paths))
+        {
+            error!(
+                "MetadataManager: Failed to send informations computation command: {}",
+                e
+            );
+        }
+    }
+
+    /// Compute the checksums of the files in background, to follow the moved files
+    pub fn compute_checksums(&self, paths: Vec<PathBuf>) {
+        if let Err(e) = self
+            .command_sender
+            .send(MetadataCommand::ComputeChecksums(paths))
+        {
+            error!(
+                "MetadataManager: Failed to send checksum computation command: {}",
+                e
+            );
+        }
+    }
+
+    /// Search the duplicate files, the result is returned with the other results
+    pub fn find_duplicates(&self) {
+        if let Err(e) = self.command_sender.send(MetadataCommand::FindDuplicates) {
+            error!(
+                "MetadataManager: Failed to send find duplicates command: {}",
+                e
+            );
+        }
+    }
+
+    /// Read the user comments of the files, the result is sent back as Comments
+    pub fn load_comments(&self) {
+        if let Err(e) = self.command_sender.send(MetadataCommand::LoadComments) {
+            error!(
+                "MetadataManager: Failed to send load comments command: {}",
+                e
+            );
+        }
+    }
+
+    /// Read the durations and star counts of the catalogue files,
+    /// the result is sent back as CatalogueDetails
+    pub fn load_catalogue_details(&self, paths: Vec<PathBuf>) {
+        if let Err(e) = self
+            .command_sender
+            .send(MetadataCommand::LoadCatalogueDetails(paths))
+        {
+            error!(
+                "MetadataManager: Failed to send load catalogue details command: {}",
+                e
+            );
+        }
+    }
+
     /// Record a play event for a file
     pub fn record_play_event(&self, path: PathBuf) {
         debug!("MetadataManager: Recording play event for file: {:?}", path);
@@ -548,16 +793,18 @@ impl PlayMetadataManager {
     }
 
     /// Check for and process any results from background thread
-    /// Returns file metadata (play counts and star counts) if available
-    pub fn process_results(&self) -> Option<HashMap<PathBuf, FileMetadata>> {
+    /// Returns file metadata (play counts and star counts) or file informations if available
+    pub fn process_results(&self) -> Option<MetadataResult> {
         // Use try_recv to avoid blocking
         match self.result_receiver.try_recv() {
-            Ok(MetadataResult::PlayCounts(metadata)) => {
-                debug!(
-                    "MetadataManager: Received {} metadata results from background thread",
-                    metadata.len()
-                );
-                Some(metadata)
+            Ok(result) => {
+                if let MetadataResult::PlayCounts(metadata) = &result {
+                    debug!(
+                        "MetadataManager: Received {} metadata results from background thread",
+                        metadata.len()
+                    );
+                }
+                Some(result)
             }
             Err(std::sync::mpsc::TryRecvError::Empty) => {
                 // No results yet - this is normal
@@ -571,6 +818,49 @@ impl PlayMetadataManager {
     }
 }
 
+/// informations of a library file, if they are in the cache and the file has not changed
+fn cached_informations(
+    db: &PlayMetadataDatabase,
+    folder: &Path,
+    path: &Path,
+) -> Option<FileInformations> {
+    let relative_path = relative_database_path(folder, path)?;
+    let signature = FileSignature::of(path)?;
+    db.get_file_informations(&relative_path, &signature)
+        .ok()
+        .flatten()
+}
+
+/// durations (from the cache) and star counts of the catalogue files
+fn catalogue_details(
+    db: &PlayMetadataDatabase,
+    folder: &Path,
+    paths: Vec<PathBuf>,
+) -> HashMap<PathBuf, EntryDetails> {
+    let mut details = HashMap::new();
+    for path in paths {
+        let Some(relative_path) = relative_database_path(folder, &path) else {
+            continue;
+        };
+        let star_count = match db.get_played_file_stats_with_statistics(relative_path) {
+            Ok(stats) => stats.map(|s| s.total_star_count).unwrap_or(0),
+            Err(e) => {
+                warn!("Error reading the stars of {:?}: {}", path, e);
+                0
+            }
+        };
+        let duration = cached_informations(db, folder, &path).and_then(|i| i.duration);
+        details.insert(
+            path,
+            EntryDetails {
+                duration,
+                star_count,
+            },
+        );
+    }
+    details
+}
+
 impl Default for PlayMetadataManager {
     fn default() -> Self {
         Self::new()
@@ -0,0 +1,72 @@
+//! Small pseudo random generator (xorshift), used for the shuffle and generation of the playlists,
+//! no cryptographic quality is needed here
+//!
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// xorshift64 generator, the state must never be 0
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct XorShift64 {
+    state: u64,
+}
+
+impl XorShift64 {
+    /// create a generator from a seed, a 0 seed is replaced
+    pub fn new(seed: u64) -> Self {
+        XorShift64 {
+            state: if seed == 0 {
+                0x9E37_79B9_7F4A_7C15
+            } else {
+                seed
+            },
+        }
+    }
+
+    /// create a generator seeded by the current time
+    pub fn from_time() -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default();
+        Self::new(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.state = x;
+        x
+    }
+
+    /// random value in [0, 1[
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// random value in [0, bound[, bound must be > 0
+    pub fn next_below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+}
+
+impl Default for XorShift64 {
+    fn default() -> Self {
+        Self::from_time()
+    }
+}
+
+#[test]
+pub fn test_xorshift_bounds() {
+    let mut r = XorShift64::new(0);
+    for _ in 0..1000 {
+        assert!(r.next_below(7) < 7);
+        let f = r.next_f64();
+        assert!((0.0..1.0).contains(&f));
+    }
+    // same seed, same sequence
+    assert_eq!(
+        XorShift64::new(42).next_u64(),
+        XorShift64::new(42).next_u64()
+    );
+}
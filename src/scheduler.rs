@@ -0,0 +1,268 @@
+//! Scheduler module, start programs (playlist, file, attract loop) at given times of day,
+//! and stop the play at closing time
+//!
+//! a program replaces the queue while it is played, the queue and the play mode
+//! of the operator are restored when it ends (or at closing time)
+//!
+use std::{fmt::Display, path::PathBuf};
+
+use chrono::{Days, NaiveDateTime, NaiveTime};
+use serde::{Deserialize, Serialize};
+
+use crate::playlist::PlayMode;
+
+/// minutes in a day
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+/// time of the day, with a minute precision
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct TimeOfDay {
+    pub hour: u8,
+    pub minute: u8,
+}
+
+impl TimeOfDay {
+    pub fn new(hour: u8, minute: u8) -> Self {
+        TimeOfDay { hour, minute }
+    }
+
+    /// minutes since midnight
+    fn minutes(&self) -> u32 {
+        self.hour.min(23) as u32 * 60 + self.minute.min(59) as u32
+    }
+}
+
+impl Display for TimeOfDay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:0>2}:{:0>2}", self.hour.min(23), self.minute.min(59))
+    }
+}
+
+/// what is played by a scheduled program
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProgramKind {
+    /// the playlist is played once
+    #[default]
+    Playlist,
+    /// a single file is played
+    File,
+    /// the playlist (or file) is played in loop, until the next program or the closing time
+    AttractLoop,
+}
+
+impl ProgramKind {
+    pub const ALL: [ProgramKind; 3] = [
+        ProgramKind::Playlist,
+        ProgramKind::File,
+        ProgramKind::AttractLoop,
+    ];
+
+    /// play mode of the program, the play mode chosen by the operator is restored
+    /// once the program ends
+    pub fn play_mode(&self) -> PlayMode {
+        match self {
+            ProgramKind::AttractLoop => PlayMode::RepeatAll,
+            ProgramKind::Playlist | ProgramKind::File => PlayMode::Once,
+        }
+    }
+}
+
+/// program started at a given time of the day
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScheduledProgram {
+    pub enabled: bool,
+    pub start: TimeOfDay,
+    /// the program is started again every given minutes until the closing time,
+    /// 0 for a single start in the day
+    pub repeat_every_minutes: u32,
+    pub kind: ProgramKind,
+    /// file or playlist played, relative to the library folder
+    pub path: PathBuf,
+}
+
+impl Default for ScheduledProgram {
+    fn default() -> Self {
+        ScheduledProgram {
+            enabled: true,
+            start: TimeOfDay::new(10, 0),
+            repeat_every_minutes: 0,
+            kind: ProgramKind::default(),
+            path: PathBuf::new(),
+        }
+    }
+}
+
+/// action triggered by the scheduler
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScheduledAction {
+    /// start the program, at the given index
+    Start(usize),
+    /// closing time, the play is stopped
+    Close,
+}
+
+/// occurrence of a scheduled action
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScheduledEvent {
+    pub at: NaiveDateTime,
+    pub action: ScheduledAction,
+}
+
+/// programs of the day, the same every day
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Schedule {
+    pub enabled: bool,
+    pub programs: Vec<ScheduledProgram>,
+    /// the play is stopped at closing time, no program is started after it
+    pub closing_time: Option<TimeOfDay>,
+}
+
+impl Schedule {
+    /// actions of a day, in minutes since midnight, sorted by time
+    fn day_actions(&self) -> Vec<(u32, ScheduledAction)> {
+        let end = self
+            .closing_time
+            .map(|c| c.minutes())
+            .unwrap_or(MINUTES_PER_DAY);
+
+        let mut actions = vec![];
+        for (index, program) in self.programs.iter().enumerate() {
+            if !program.enabled || program.path.as_os_str().is_empty() {
+                continue;
+            }
+            let mut minutes = program.start.minutes();
+            while minutes < end {
+                actions.push((minutes, ScheduledAction::Start(index)));
+                if program.repeat_every_minutes == 0 {
+                    break;
+                }
+                minutes += program.repeat_every_minutes;
+            }
+        }
+        if let Some(closing_time) = self.closing_time {
+            actions.push((closing_time.minutes(), ScheduledAction::Close));
+        }
+        actions.sort_by_key(|(minutes, _)| *minutes);
+        actions
+    }
+
+    /// first event strictly after the given time
+    pub fn next_event(&self, after: NaiveDateTime) -> Option<ScheduledEvent> {
+        if !self.enabled {
+            return None;
+        }
+        let actions = self.day_actions();
+        // the actions are the same every day, the next one is today or tomorrow
+        for days in 0..2 {
+            let date = after.date().checked_add_days(Days::new(days))?;
+            for (minutes, action) in &actions {
+                let at = date.and_time(NaiveTime::from_num_seconds_from_midnight_opt(
+                    minutes * 60,
+                    0,
+                )?);
+                if at > after {
+                    return Some(ScheduledEvent {
+                        at,
+                        action: *action,
+                    });
+                }
+            }
+        }
+        None
+    }
+
+    /// latest event in ]from, to], the previous ones are superseded by it
+    pub fn due_event(&self, from: NaiveDateTime, to: NaiveDateTime) -> Option<ScheduledEvent> {
+        let mut due = None;
+        let mut time = from;
+        while let Some(event) = self.next_event(time) {
+            if event.at > to {
+                break;
+            }
+            time = event.at;
+            due = Some(event);
+        }
+        due
+    }
+}
+
+#[cfg(test)]
+fn test_time(hour: u32, minute: u32) -> NaiveDateTime {
+    chrono::NaiveDate::from_ymd_opt(2024, 5, 18)
+        .unwrap()
+        .and_hms_opt(hour, minute, 0)
+        .unwrap()
+}
+
+#[test]
+pub fn test_schedule_next_event() {
+    let mut schedule = Schedule {
+        enabled: true,
+        programs: vec![ScheduledProgram {
+            start: TimeOfDay::new(10, 0),
+            repeat_every_minutes: 60,
+            path: PathBuf::from("demo.m3u8"),
+            ..Default::default()
+        }],
+        closing_time: Some(TimeOfDay::new(12, 30)),
+    };
+
+    let next = schedule.next_event(test_time(9, 15)).unwrap();
+    assert_eq!(next.at, test_time(10, 0));
+    assert_eq!(next.action, ScheduledAction::Start(0));
+    assert_eq!(
+        schedule.next_event(test_time(10, 0)).unwrap().at,
+        test_time(11, 0)
+    );
+
+    // no start after the closing time
+    let closing = schedule.next_event(test_time(12, 10)).unwrap();
+    assert_eq!(closing.at, test_time(12, 30));
+    assert_eq!(closing.action, ScheduledAction::Close);
+
+    // next day
+    let tomorrow = schedule.next_event(test_time(13, 0)).unwrap();
+    assert_eq!(tomorrow.at, test_time(10, 0) + Days::new(1));
+
+    schedule.enabled = false;
+    assert!(schedule.next_event(test_time(9, 0)).is_none());
+}
+
+#[test]
+pub fn test_schedule_due_event() {
+    let schedule = Schedule {
+        enabled: true,
+        programs: vec![
+            ScheduledProgram {
+                start: TimeOfDay::new(10, 0),
+                path: PathBuf::from("a.mid"),
+                ..Default::default()
+            },
+            ScheduledProgram {
+                start: TimeOfDay::new(10, 5),
+                path: PathBuf::from("b.mid"),
+                ..Default::default()
+            },
+            ScheduledProgram {
+                enabled: false,
+                start: TimeOfDay::new(10, 7),
+                path: PathBuf::from("c.mid"),
+                ..Default::default()
+            },
+        ],
+        closing_time: None,
+    };
+
+    assert!(schedule
+        .due_event(test_time(9, 0), test_time(9, 59))
+        .is_none());
+    // the latest due event is kept
+    assert_eq!(
+        schedule
+            .due_event(test_time(9, 59), test_time(10, 8))
+            .unwrap()
+            .action,
+        ScheduledAction::Start(1)
+    );
+}
@@ -0,0 +1,473 @@
+//! Search index of the library, the files are found by the words of their name, folder,
+//! MIDI title, book scale and user comments
+//!
+//! the words are compared without accents and case, each word typed must start a word
+//! of the file, the results are ranked by the fields matched
+//!
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use player::FileInformations;
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+use crate::file_store::{FileNodeLock, FileStore, SharedFileNode};
+
+/// field of the file containing a word, the fields have different weights in the ranking
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Field {
+    Name,
+    Title,
+    Comment,
+    Scale,
+    Folder,
+}
+
+impl Field {
+    fn weight(&self) -> f32 {
+        match self {
+            Field::Name => 10.0,
+            Field::Title => 8.0,
+            Field::Comment => 5.0,
+            Field::Scale => 4.0,
+            Field::Folder => 3.0,
+        }
+    }
+}
+
+/// a word typed starting a word of the file scores less than the whole word
+const PREFIX_MATCH_FACTOR: f32 = 0.6;
+
+/// text without accents, in lower case
+pub fn normalize(text: &str) -> String {
+    text.nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .collect::<String>()
+        .to_lowercase()
+}
+
+/// normalized words of a text
+pub fn tokenize(text: &str) -> Vec<String> {
+    normalize(text)
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_string())
+        .collect()
+}
+
+/// indexed file, with its words
+#[derive(Debug, Clone)]
+struct Document {
+    path: PathBuf,
+    name: String,
+    folder: String,
+    title: Option<String>,
+    scale: Option<String>,
+    words: Vec<(String, Field)>,
+}
+
+impl Document {
+    fn new(path: PathBuf, folder: String) -> Self {
+        let name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        Document {
+            path,
+            name,
+            folder,
+            title: None,
+            scale: None,
+            words: vec![],
+        }
+    }
+
+    fn index_words(&mut self, comment: Option<&String>) {
+        let mut words: Vec<(String, Field)> = vec![];
+        let mut add = |text: Option<&String>, field: Field| {
+            for word in text.map(|t| tokenize(t)).unwrap_or_default() {
+                if !words.contains(&(word.clone(), field)) {
+                    words.push((word, field));
+                }
+            }
+        };
+        add(Some(&self.name), Field::Name);
+        add(self.title.as_ref(), Field::Title);
+        add(comment, Field::Comment);
+        add(self.scale.as_ref(), Field::Scale);
+        add(Some(&self.folder), Field::Folder);
+        self.words = words;
+    }
+
+    /// score of the document for the typed words, None if a word is not found
+    fn score(&self, query: &[String]) -> Option<f32> {
+        let mut total = 0.0;
+        for typed in query {
+            let best = self
+                .words
+                .iter()
+                .filter(|(word, _)| word.starts_with(typed.as_str()))
+                .map(|(word, field)| {
+                    field.weight()
+                        * if word == typed {
+                            1.0
+                        } else {
+                            PREFIX_MATCH_FACTOR
+                        }
+                })
+                .fold(None, |best: Option<f32>, s| {
+                    Some(best.map_or(s, |b| b.max(s)))
+                })?;
+            total += best;
+        }
+        Some(total)
+    }
+}
+
+/// file found by a search
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchHit {
+    pub path: PathBuf,
+    pub score: f32,
+}
+
+/// index of the words of the library files
+#[derive(Debug, Default)]
+pub struct SearchIndex {
+    documents: Vec<Option<Document>>,
+    free_slots: Vec<usize>,
+    by_path: HashMap<PathBuf, usize>,
+    /// documents containing each word
+    postings: BTreeMap<String, HashSet<usize>>,
+    /// user comments, by file, kept for the files not indexed yet
+    comments: HashMap<PathBuf, String>,
+    /// changed on each modification, the incremental searches are then done again
+    generation: u64,
+}
+
+impl SearchIndex {
+    pub fn len(&self) -> usize {
+        self.by_path.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_path.is_empty()
+    }
+
+    fn unlink(&mut self, id: usize) {
+        if let Some(document) = &self.documents[id] {
+            for (word, _) in &document.words {
+                if let Some(ids) = self.postings.get_mut(word) {
+                    ids.remove(&id);
+                    if ids.is_empty() {
+                        self.postings.remove(word);
+                    }
+                }
+            }
+        }
+    }
+
+    fn link(&mut self, id: usize) {
+        if let Some(document) = &mut self.documents[id] {
+            document.index_words(self.comments.get(&document.path));
+            for (word, _) in &document.words {
+                self.postings.entry(word.clone()).or_default().insert(id);
+            }
+        }
+        self.generation += 1;
+    }
+
+    /// change an indexed document, its words are indexed again
+    fn update(&mut self, path: &Path, change: impl FnOnce(&mut Document)) {
+        let Some(id) = self.by_path.get(path).copied() else {
+            return;
+        };
+        self.unlink(id);
+        if let Some(document) = &mut self.documents[id] {
+            change(document);
+        }
+        self.link(id);
+    }
+
+    /// add a file, its folder is relative to the library folder
+    pub fn insert(
+        &mut self,
+        path: PathBuf,
+        folder: String,
+        informations: Option<&FileInformations>,
+    ) {
+        self.remove(&path);
+        let mut document = Document::new(path.clone(), folder);
+        if let Some(informations) = informations {
+            document.title.clone_from(&informations.title);
+            document.scale.clone_from(&informations.scale_name);
+        }
+        let id = match self.free_slots.pop() {
+            Some(id) => {
+                self.documents[id] = Some(document);
+                id
+            }
+            None => {
+                self.documents.push(Some(document));
+                self.documents.len() - 1
+            }
+        };
+        self.by_path.insert(path, id);
+        self.link(id);
+    }
+
+    pub fn remove(&mut self, path: &Path) {
+        if let Some(id) = self.by_path.remove(path) {
+            self.unlink(id);
+            self.documents[id] = None;
+            self.free_slots.push(id);
+            self.generation += 1;
+        }
+    }
+
+    /// index the MIDI title and book scale of a file
+    pub fn set_informations(&mut self, path: &Path, informations: &FileInformations) {
+        let unchanged = self
+            .by_path
+            .get(path)
+            .and_then(|id| self.documents[*id].as_ref())
+            .map(|d| d.title == informations.title && d.scale == informations.scale_name)
+            .unwrap_or(true);
+        if unchanged {
+            return;
+        }
+        self.update(path, |document| {
+            document.title.clone_from(&informations.title);
+            document.scale.clone_from(&informations.scale_name);
+        });
+    }
+
+    /// index the user comments (or tags) of a file
+    pub fn set_comment(&mut self, path: &Path, comment: String) {
+        if self.comments.get(path) == Some(&comment) {
+            return;
+        }
+        if comment.is_empty() {
+            self.comments.remove(path);
+        } else {
+            self.comments.insert(path.to_path_buf(), comment);
+        }
+        self.update(path, |_| {});
+    }
+
+    /// add the files of the store not indexed yet, and remove the files no more in the store
+    pub fn sync_with_store(&mut self, file_store: &FileStore) {
+        fn collect(node: &SharedFileNode, files: &mut Vec<(PathBuf, Option<FileInformations>)>) {
+            let n = node.read_node();
+            if n.is_folder {
+                n.folder_files.iter().for_each(|c| collect(c, files));
+            } else {
+                files.push((n.path.clone(), n.informations.clone()));
+            }
+        }
+        let mut files = vec![];
+        collect(&file_store.root, &mut files);
+
+        let present: HashSet<&PathBuf> = files.iter().map(|(p, _)| p).collect();
+        let removed: Vec<PathBuf> = self
+            .by_path
+            .keys()
+            .filter(|p| !present.contains(p))
+            .cloned()
+            .collect();
+        for path in removed {
+            self.remove(&path);
+        }
+
+        for (path, informations) in files {
+            if self.by_path.contains_key(&path) {
+                continue;
+            }
+            let folder = path
+                .parent()
+                .and_then(|p| p.strip_prefix(&file_store.base_path).ok())
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or_default();
+            self.insert(path, folder, informations.as_ref());
+        }
+    }
+
+    /// documents having a word starting with the typed word
+    fn candidates(&self, typed: &str) -> HashSet<usize> {
+        self.postings
+            .range(typed.to_string()..)
+            .take_while(|(word, _)| word.starts_with(typed))
+            .flat_map(|(_, ids)| ids.iter().copied())
+            .collect()
+    }
+
+    /// files matching all the typed words, the best ranked first,
+    /// the search is restricted to the given documents if any
+    fn search_in(&self, query: &[String], restricted: Option<&[usize]>) -> Vec<(usize, f32)> {
+        // the longest word is the most selective
+        let Some(selective) = query.iter().max_by_key(|w| w.len()) else {
+            return vec![];
+        };
+        let candidates: Vec<usize> = match restricted {
+            Some(ids) => ids.to_vec(),
+            None => self.candidates(selective).into_iter().collect(),
+        };
+
+        let mut hits: Vec<(usize, f32)> = candidates
+            .into_iter()
+            .filter_map(|id| {
+                let document = self.documents.get(id)?.as_ref()?;
+                document.score(query).map(|score| (id, score))
+            })
+            .collect();
+        hits.sort_by(|(a, sa), (b, sb)| {
+            sb.total_cmp(sa).then_with(|| {
+                let name = |id: &usize| self.documents[*id].as_ref().map(|d| &d.name);
+                name(a).cmp(&name(b))
+            })
+        });
+        hits
+    }
+
+    /// files matching all the words of the query, the best ranked first
+    pub fn search(&self, query: &str) -> Vec<SearchHit> {
+        self.search_in(&tokenize(query), None)
+            .into_iter()
+            .filter_map(|(id, score)| {
+                let path = self.documents[id].as_ref()?.path.clone();
+                Some(SearchHit { path, score })
+            })
+            .collect()
+    }
+}
+
+/// search updated as the user types, when the query is extended the previous results
+/// are filtered instead of searching the whole index
+#[derive(Debug, Default)]
+pub struct LibrarySearch {
+    pub index: SearchIndex,
+    last_query: Vec<String>,
+    last_generation: u64,
+    last_hits: Vec<(usize, f32)>,
+}
+
+impl LibrarySearch {
+    /// the typed words refine the previous ones, each previous word is kept
+    /// or extended in the same position
+    fn refines(&self, query: &[String]) -> bool {
+        !self.last_query.is_empty()
+            && query.len() >= self.last_query.len()
+            && self
+                .last_query
+                .iter()
+                .zip(query)
+                .all(|(previous, typed)| typed.starts_with(previous.as_str()))
+    }
+
+    /// files matching the query, the best ranked first
+    pub fn search(&mut self, query: &str) -> Vec<SearchHit> {
+        let words = tokenize(query);
+        let restricted = if self.index.generation == self.last_generation && self.refines(&words) {
+            Some(
+                self.last_hits
+                    .iter()
+                    .map(|(id, _)| *id)
+                    .collect::<Vec<usize>>(),
+            )
+        } else {
+            None
+        };
+
+        self.last_hits = self.index.search_in(&words, restricted.as_deref());
+        self.last_query = words;
+        self.last_generation = self.index.generation;
+
+        self.last_hits
+            .iter()
+            .filter_map(|(id, score)| {
+                let path = self.index.documents[*id].as_ref()?.path.clone();
+                Some(SearchHit {
+                    path,
+                    score: *score,
+                })
+            })
+            .collect()
+    }
+
+    /// score of the files matching the query, to construct a ranked view
+    pub fn ranking(&mut self, query: &str) -> HashMap<PathBuf, f32> {
+        self.search(query)
+            .into_iter()
+            .map(|hit| (hit.path, hit.score))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+fn test_index() -> SearchIndex {
+    let mut index = SearchIndex::default();
+    index.insert(
+        PathBuf::from("/books/Été/12 - Valse de Paris.mid"),
+        "Été".into(),
+        None,
+    );
+    index.insert(
+        PathBuf::from("/books/marches/Marche lorraine.book"),
+        "marches".into(),
+        Some(&FileInformations {
+            title: Some("La Lorraine".into()),
+            scale_name: Some("Limonaire 45".into()),
+            ..Default::default()
+        }),
+    );
+    index.insert(
+        PathBuf::from("/books/divers/Été indien.mid"),
+        "divers".into(),
+        None,
+    );
+    index
+}
+
+#[test]
+pub fn test_search_accents_and_ranking() {
+    assert_eq!(tokenize("Valse de l'Été"), vec!["valse", "de", "l", "ete"]);
+
+    let index = test_index();
+    // the name scores more than the folder, the accents are ignored
+    let hits = index.search("ete");
+    assert_eq!(hits.len(), 2);
+    assert_eq!(hits[0].path, PathBuf::from("/books/divers/Été indien.mid"));
+
+    // all the words must be found, in any field
+    let hits = index.search("lorr limon");
+    assert_eq!(hits.len(), 1);
+    assert!(index.search("valse lorraine").is_empty());
+
+    // numbers are words, not substrings
+    assert_eq!(index.search("12").len(), 1);
+    assert!(index.search("2").is_empty());
+}
+
+#[test]
+pub fn test_incremental_search_and_comments() {
+    let mut search = LibrarySearch {
+        index: test_index(),
+        ..Default::default()
+    };
+    assert_eq!(search.search("v").len(), 1);
+    assert_eq!(search.search("va").len(), 1);
+    assert!(search.search("vax").is_empty());
+    assert_eq!(search.search("v").len(), 1);
+
+    // the comments are indexed, even if received before the file
+    let path = PathBuf::from("/books/new/Polka.mid");
+    search.index.set_comment(&path, "mariage, fête".into());
+    assert!(search.search("mariage").is_empty());
+    search.index.insert(path.clone(), "new".into(), None);
+    assert_eq!(search.search("fete")[0].path, path);
+
+    search.index.remove(&path);
+    assert!(search.search("fete").is_empty());
+    assert_eq!(search.index.len(), 3);
+}
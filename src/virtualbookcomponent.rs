@@ -89,6 +89,12 @@ pub struct VirtualBookComponent {
     background_texture_id: Option<egui::TextureId>,
 
     virtual_book: Option<Arc<IndexedVirtualBook>>,
+
+    /// labelled positions, timestamps in microseconds
+    markers: Vec<(i64, String)>,
+
+    /// loop region (start, optional end), timestamps in microseconds
+    loop_region: Option<(i64, Option<i64>)>,
 }
 
 impl Default for VirtualBookComponent {
@@ -102,6 +108,8 @@ impl Default for VirtualBookComponent {
             scrollbats_width: 12.0,
             background_texture_id: None,
             virtual_book: None,
+            markers: vec![],
+            loop_region: None,
         }
     }
 }
@@ -183,6 +191,24 @@ impl VirtualBookComponent {
         self
     }
 
+    /// labelled positions to display (timestamp in microseconds, label)
+    pub fn markers(mut self, markers: Vec<(i64, String)>) -> Self {
+        self.markers = markers;
+        self
+    }
+
+    /// loop region to display (start, optional end), timestamps in microseconds
+    pub fn loop_region(mut self, loop_region: Option<(i64, Option<i64>)>) -> Self {
+        self.loop_region = loop_region;
+        self
+    }
+
+    /// timestamp in microseconds of a position in the component,
+    /// given the display parameters used for the rendering
+    pub fn timestamp_at(rect: Rect, pos: Pos2, offset_ms: f64, xscale: f64) -> i64 {
+        ((pos.x - rect.left() - rect.width() / 2.0) as f64 * xscale + offset_ms * 1000.0) as i64
+    }
+
     pub fn ui_content(&mut self, ui: &mut Ui) -> egui::Response {
         let Self {
             offset_ms: offset_in_millis,
@@ -381,6 +407,56 @@ impl VirtualBookComponent {
                         painter.add(RectShape::filled(*r, Rounding::from(1.0), *c));
                     }
 
+                    // loop region
+                    let to_x = |timestamp: i64| {
+                        (((timestamp as f64 - *offset_in_millis * 1000.0) / *xscale)
+                            + width_container as f64 / 2.0) as f32
+                    };
+                    let loop_color = Color32::from_rgba_unmultiplied(0, 0, 255, 40);
+                    match self.loop_region {
+                        Some((start, Some(end))) => {
+                            let region = Rect::from_points(&[
+                                to_screen * pos2(to_x(start).max(0.0), 0.0),
+                                to_screen * pos2(to_x(end).min(width_container), maxy),
+                            ]);
+                            if to_x(start) < width_container && to_x(end) > 0.0 {
+                                painter.add(RectShape::filled(
+                                    region,
+                                    Rounding::default(),
+                                    loop_color,
+                                ));
+                            }
+                        }
+                        Some((start, None)) => {
+                            let x = to_x(start);
+                            painter.line_segment(
+                                [to_screen * pos2(x, 0.0), to_screen * pos2(x, maxy)],
+                                Stroke::new(2.0, Color32::BLUE),
+                            );
+                        }
+                        None => {}
+                    }
+
+                    // markers
+                    let marker_color = Color32::from_rgb(0, 128, 0);
+                    for (timestamp, label) in self.markers.iter() {
+                        let x = to_x(*timestamp);
+                        if x < 0.0 || x > width_container {
+                            continue;
+                        }
+                        painter.line_segment(
+                            [to_screen * pos2(x, 0.0), to_screen * pos2(x, maxy)],
+                            Stroke::new(2.0, marker_color),
+                        );
+                        painter.text(
+                            to_screen * pos2(x + 4.0, 4.0),
+                            Align2::LEFT_TOP,
+                            label,
+                            FontId::proportional(16.0),
+                            marker_color,
+                        );
+                    }
+
                     painter.add(RectShape::filled(bar, Rounding::default(), Color32::BLUE));
                 } else {
                     // no virtualbook